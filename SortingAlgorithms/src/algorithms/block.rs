@@ -0,0 +1,935 @@
+/*****************************************************************/
+//! [Block Merge Sorts]
+/*****************************************************************/
+//!
+//! A stable O(n log n) sort normally needs O(n) extra memory to merge
+//! into, like the merge sorts' auxiliary buffer. Block merge sorts
+//! show that it can be done with O(1). They gather a few square
+//! roots' worth of distinct values at the front of the columns, and
+//! use them as an internal buffer. Merging swaps elements through the
+//! buffer rather than copying them, so the buffer gets scrambled but
+//! never loses anything. Runs too long for the buffer are cut into
+//! blocks, which are moved around with block swaps and rotations. At
+//! the very end the buffer is sorted and merged back in.
+//!
+//! Both sorts are built from a handful of operations, each a little
+//! state machine of its own, which are queued up as BlockOps and
+//! performed one swap or comparison per step. The sorts themselves
+//! only decide what to queue next once the queue has drained.
+
+use std::collections::VecDeque;
+
+use super::merge::gallop;
+use super::{Array, Event, in_order, InsertionSort, Marker, SortAlgorithm, Step};
+
+/// [Chunk]
+/// The columns are first insertion sorted in chunks of this many.
+const CHUNK: usize = 8;
+
+/// [Before]
+/// True when a must strictly come before b in the given direction.
+fn before(a: i32, b: i32, direction: i32) -> bool {
+    !in_order(b, a, direction)
+}
+
+/// [Outcome]
+/// What a block operation did when it was stepped.
+pub enum Outcome {
+    /// Performed one operation on the columns.
+    Step(Step),
+    /// Performed its one and only operation.
+    Last(Step),
+    /// Decided to become this sequence of operations instead.
+    Expand(Vec<BlockOp>),
+    /// Had nothing left to do.
+    Finished,
+}
+
+/// [Block Op]
+/// One of the operations the block merge sorts are built from.
+#[derive(Clone)]
+pub enum BlockOp {
+    /// A comparison made while deciding, shown as it was made.
+    Show(Step),
+    /// A single swap.
+    Swap(usize, usize),
+    /// Swaps the blocks of len columns starting at a and b, a pair at
+    /// a time, where done pairs have been swapped so far.
+    BlockSwap { a: usize, b: usize, len: usize, done: usize },
+    /// Rotates low..high so that the column at mid comes first. This
+    /// is done with block swaps, swapping the shorter side with the
+    /// far end of the longer side, which puts the shorter side in its
+    /// final place and leaves a smaller rotation still to do.
+    Rotate { low: usize, mid: usize, high: usize, done: usize },
+    /// An insertion sort of low..high.
+    Insertion { low: usize, high: usize, sort: InsertionSort },
+    /// Merges a run which has been swapped into the buffer at
+    /// left..left_end with the run at right..right_end, writing the
+    /// result from out, which is where the buffered run used to be.
+    /// Each write swaps the element into place, which hands the
+    /// buffer's scrambled contents back bit by bit. Ties go to the
+    /// buffered run when left_first is set.
+    BufferMerge { out: usize, left: usize, left_end: usize, right: usize, right_end: usize, left_first: bool },
+    /// Merges low..mid with mid..high without any buffer, by finding
+    /// where the head of the right run belongs in the left run and
+    /// rotating as much of the right run as belongs there into place.
+    RotationMerge { low: usize, mid: usize, high: usize },
+}
+
+impl BlockOp {
+    /// [Rotate]
+    /// A rotation of low..high so that the column at mid comes first.
+    fn rotate(low: usize, mid: usize, high: usize) -> BlockOp {
+        BlockOp::Rotate { low, mid, high, done: 0 }
+    }
+
+    /// [Block Swap]
+    /// A swap of the blocks of len columns starting at a and b.
+    fn block_swap(a: usize, b: usize, len: usize) -> BlockOp {
+        BlockOp::BlockSwap { a, b, len, done: 0 }
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Outcome {
+        match self {
+            BlockOp::Show(step) => {
+                if let Step::Compare(i, j) = *step {
+                    columns.record(Event::Compare(i, j));
+                }
+                Outcome::Last(*step)
+            }
+            BlockOp::Swap(i, j) => {
+                columns.swap(*i, *j);
+                Outcome::Last(Step::Swap(*i, *j))
+            }
+            BlockOp::BlockSwap { a, b, len, done } => {
+                if *done == *len {
+                    return Outcome::Finished;
+                }
+                let (i, j) = (*a + *done, *b + *done);
+                columns.swap(i, j);
+                *done += 1;
+                Outcome::Step(Step::Swap(i, j))
+            }
+            BlockOp::Rotate { low, mid, high, done } => {
+                if low == mid || mid == high {
+                    return Outcome::Finished;
+                }
+
+                let (left, right) = (*mid - *low, *high - *mid);
+                let (i, j) = if left <= right { (*low + *done, *high - left + *done) } else { (*low + *done, *mid + *done) };
+                columns.swap(i, j);
+                *done += 1;
+
+                if left <= right && *done == left {
+                    *high -= left;
+                    *done = 0;
+                } else if left > right && *done == right {
+                    *low += right;
+                    *done = 0;
+                }
+                Outcome::Step(Step::Swap(i, j))
+            }
+            BlockOp::Insertion { low, high, sort } => match sort.step(&mut columns.slice(*low, *high), direction) {
+                Step::Done => Outcome::Finished,
+                step => Outcome::Step(step.offset(*low)),
+            },
+            BlockOp::BufferMerge { out, left, left_end, right, right_end, left_first } => {
+                if left == left_end {
+                    return Outcome::Finished;
+                }
+
+                let take_left = *right == *right_end
+                    || if *left_first {
+                        columns.compare(*left, *right, direction)
+                    } else {
+                        !columns.compare(*right, *left, direction)
+                    };
+
+                let from = if take_left { left } else { right };
+                let (i, j) = (*out, *from);
+                columns.swap(i, j);
+                *from += 1;
+                *out += 1;
+                Outcome::Step(Step::Swap(i, j))
+            }
+            BlockOp::RotationMerge { low, mid, high } => {
+                let (low, mid, high) = (*low, *mid, *high);
+                if low >= mid || mid >= high {
+                    return Outcome::Finished;
+                }
+
+                // Everything in the left run up to the head of the right
+                // run is already in place.
+                let columns = columns.values();
+                let mut ops = vec![];
+                let head = columns[mid];
+                let p = low
+                    + gallop(mid - low, |k| {
+                        ops.push(BlockOp::Show(Step::Compare(low + k, mid)));
+                        in_order(columns[low + k], head, direction)
+                    });
+                if p == mid {
+                    return Outcome::Expand(ops);
+                }
+
+                // Then as much of the right run as belongs before the
+                // next element of the left run is rotated in front of it.
+                let next = columns[p];
+                let count = gallop(high - mid, |k| {
+                    ops.push(BlockOp::Show(Step::Compare(mid + k, p)));
+                    before(columns[mid + k], next, direction)
+                });
+                ops.push(BlockOp::rotate(p, mid, mid + count));
+                ops.push(BlockOp::RotationMerge { low: p + count, mid: mid + count, high });
+                Outcome::Expand(ops)
+            }
+        }
+    }
+}
+
+/// [Ops]
+/// A queue of block operations, performed one step at a time. An
+/// operation which expands is replaced by its expansion at the front
+/// of the queue.
+#[derive(Clone, Default)]
+pub struct Ops {
+    queue: VecDeque<BlockOp>,
+}
+
+impl Ops {
+    fn push(&mut self, op: BlockOp) {
+        self.queue.push_back(op);
+    }
+
+    fn extend(&mut self, ops: Vec<BlockOp>) {
+        self.queue.extend(ops);
+    }
+
+    /// [Step]
+    /// Performs the next step of the queued operations, if there are
+    /// any left.
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Option<Step> {
+        loop {
+            match self.queue.front_mut()?.step(columns, direction) {
+                Outcome::Step(step) => return Some(step),
+                Outcome::Last(step) => {
+                    self.queue.pop_front();
+                    return Some(step);
+                }
+                Outcome::Expand(ops) => {
+                    self.queue.pop_front();
+                    for op in ops.into_iter().rev() {
+                        self.queue.push_front(op);
+                    }
+                }
+                Outcome::Finished => {
+                    self.queue.pop_front();
+                }
+            }
+        }
+    }
+
+    /// [Markers]
+    /// The blocks being moved by the operation in progress.
+    fn markers(&self) -> Vec<Marker> {
+        match self.queue.front() {
+            Some(&BlockOp::Rotate { low, high, .. }) => vec![Marker::Rotation(low, high)],
+            Some(&BlockOp::BlockSwap { a, b, len, .. }) => vec![Marker::Rotation(a, a + len), Marker::Rotation(b, b + len)],
+            _ => vec![],
+        }
+    }
+}
+
+/// [Buffered Merge]
+/// Queues a merge of low..mid with mid..high through the buffer
+/// starting at buffer, which must hold at least mid - low columns.
+fn buffered_merge(ops: &mut Ops, buffer: usize, (low, mid, high): (usize, usize, usize), left_first: bool) {
+    let len = mid - low;
+    ops.push(BlockOp::block_swap(low, buffer, len));
+    ops.push(BlockOp::BufferMerge { out: low, left: buffer, left_end: buffer + len, right: mid, right_end: high, left_first });
+}
+
+/// [Merger]
+/// How a block merge sort merges two neighbouring sorted runs, given
+/// the distinct values gathered at the front of the columns.
+pub trait Merger: Clone + Default {
+    /// [Wanted]
+    /// How many distinct values to gather for a sort of len columns.
+    fn wanted(len: usize) -> usize;
+
+    /// [Setup]
+    /// Lays out the buffers over the found distinct values at the
+    /// front of the columns. Finding fewer than wanted means a slower
+    /// fallback has to be used.
+    fn setup(&mut self, found: usize, len: usize);
+
+    /// [Begin]
+    /// Starts merging low..mid with mid..high.
+    fn begin(&mut self, runs: (usize, usize, usize));
+
+    /// [Next]
+    /// Queues the next batch of the merge, and returns true once the
+    /// whole merge has been queued.
+    fn next(&mut self, columns: &[i32], direction: i32, ops: &mut Ops) -> bool;
+
+    /// [Buffers]
+    /// The internal buffers, for highlighting.
+    fn buffers(&self) -> Vec<Marker>;
+
+    /// [Phase]
+    /// What the merge is doing right now.
+    fn phase(&self) -> &'static str;
+
+    /// [Block]
+    /// The block size of the merge in progress, if it uses blocks.
+    fn block(&self) -> usize;
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Phase {
+    #[default]
+    Start,
+    Collect,
+    Chunks,
+    Levels,
+    Merge,
+    Redistribute,
+    Done,
+}
+
+/// [Block Merge Sort]
+/// The driver shared by the block merge sorts. It gathers distinct
+/// values to the front of the columns, insertion sorts the rest in
+/// chunks, and merges the chunks bottom-up in passes of doubling
+/// width using the Merger. Finally the gathered values are sorted and
+/// merged back in with a rotation merge. The gathered values are the
+/// first of each value found, so they belong before any duplicates,
+/// which keeps the whole sort stable.
+#[derive(Clone, Default)]
+pub struct BlockMergeSort<M: Merger> {
+    ops: Ops,
+    merger: M,
+    phase: Phase,
+    wanted: usize,
+    start: usize,
+    found: usize,
+    next: usize,
+    width: usize,
+    low: usize,
+    range: (usize, usize),
+}
+
+pub type WikiSort = BlockMergeSort<Wiki>;
+pub type GrailSort = BlockMergeSort<Grail>;
+
+impl<M: Merger> BlockMergeSort<M> {
+    /// [Collect]
+    /// Queues the next batch of gathering distinct values, and returns
+    /// true once enough have been found or the columns run out. The
+    /// values found so far are kept sorted at start..start + found,
+    /// just behind the next column to look at. A new value is found by
+    /// binary search, then rotated into place along with the rest.
+    fn collect(&mut self, columns: &[i32], direction: i32) -> bool {
+        let (start, found, next) = (self.start, self.found, self.next);
+
+        if found == self.wanted || next == columns.len() {
+            self.ops.push(BlockOp::rotate(0, start, start + found));
+            return true;
+        }
+
+        let mut ops = vec![];
+        let key = columns[next];
+        let p = gallop(found, |k| {
+            ops.push(BlockOp::Show(Step::Compare(start + k, next)));
+            before(columns[start + k], key, direction)
+        });
+        let duplicate = p < found && {
+            ops.push(BlockOp::Show(Step::Compare(next, start + p)));
+            !before(key, columns[start + p], direction)
+        };
+
+        if !duplicate {
+            ops.push(BlockOp::rotate(start, start + found, next));
+            ops.push(BlockOp::rotate(next - found + p, next, next + 1));
+            self.start = next - found;
+            self.found += 1;
+        }
+        self.next += 1;
+        self.ops.extend(ops);
+        false
+    }
+}
+
+impl<M: Merger + 'static> SortAlgorithm for BlockMergeSort<M> {
+    fn reset(&mut self) {
+        *self = BlockMergeSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            if let Some(step) = self.ops.step(columns, direction) {
+                return step;
+            }
+
+            let len = columns.len();
+            match self.phase {
+                Phase::Start => {
+                    if len < 2 {
+                        return Step::Done;
+                    }
+                    self.wanted = M::wanted(len);
+                    self.phase = Phase::Collect;
+                }
+                Phase::Collect => {
+                    if self.collect(columns.values(), direction) {
+                        self.merger.setup(self.found, len);
+                        self.low = self.found;
+                        self.phase = Phase::Chunks;
+                    }
+                }
+                Phase::Chunks => {
+                    if self.low >= len {
+                        self.width = CHUNK;
+                        self.low = self.found;
+                        self.phase = Phase::Levels;
+                        continue;
+                    }
+                    let high = (self.low + CHUNK).min(len);
+                    self.ops.push(BlockOp::Insertion { low: self.low, high, sort: InsertionSort::default() });
+                    self.range = (self.low, high);
+                    self.low = high;
+                }
+                Phase::Levels => {
+                    if self.found + self.width >= len {
+                        self.phase = Phase::Redistribute;
+                        continue;
+                    }
+                    if self.low + self.width >= len {
+                        self.width *= 2;
+                        self.low = self.found;
+                        continue;
+                    }
+
+                    let (low, mid) = (self.low, self.low + self.width);
+                    let high = (mid + self.width).min(len);
+                    self.low = high;
+                    self.range = (low, high);
+
+                    // Runs which are already in order need no merging.
+                    self.ops.push(BlockOp::Show(Step::Compare(mid - 1, mid)));
+                    if in_order(columns.values()[mid - 1], columns.values()[mid], direction) {
+                        continue;
+                    }
+                    self.merger.begin((low, mid, high));
+                    self.phase = Phase::Merge;
+                }
+                Phase::Merge => {
+                    if self.merger.next(columns.values(), direction, &mut self.ops) {
+                        self.phase = Phase::Levels;
+                    }
+                }
+                Phase::Redistribute => {
+                    let found = self.found;
+                    self.ops.push(BlockOp::Insertion { low: 0, high: found, sort: InsertionSort::default() });
+                    self.ops.push(BlockOp::RotationMerge { low: 0, mid: found, high: len });
+                    self.range = (0, len);
+                    self.phase = Phase::Done;
+                }
+                Phase::Done => return Step::Done,
+            }
+        }
+    }
+
+    /// [Aux Memory]
+    /// The gathered values are the sort's buffer from the moment they
+    /// start being collected until they are merged back in at the end,
+    /// even though they are kept at the front of the columns.
+    fn aux_memory(&self) -> usize {
+        match self.phase {
+            Phase::Start => 0,
+            Phase::Done if self.ops.queue.is_empty() => 0,
+            _ => self.found,
+        }
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        let mut markers = self.ops.markers();
+        match self.phase {
+            Phase::Chunks | Phase::Levels | Phase::Merge => {
+                markers.extend(self.merger.buffers());
+                markers.push(Marker::Range(self.range.0, self.range.1));
+            }
+            Phase::Collect => markers.push(Marker::Buffer(self.start, self.start + self.found)),
+            _ => {}
+        }
+        markers
+    }
+
+    fn status(&self) -> Option<String> {
+        let phase = match self.phase {
+            Phase::Start => return None,
+            Phase::Collect => "collecting keys",
+            Phase::Chunks => "insertion sort",
+            Phase::Levels | Phase::Merge => self.merger.phase(),
+            Phase::Redistribute | Phase::Done => "redistributing buffer",
+        };
+
+        match self.merger.block() {
+            0 => Some(format!("{} - {} keys", phase, self.found)),
+            block => Some(format!("{} - {} keys - block size {}", phase, self.found, block)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum WikiPhase {
+    #[default]
+    Start,
+    Roll,
+}
+
+/// [Wiki]
+/// The merge of WikiSort, Arne Kutzner and Pok-Son Kim's block merge
+/// as popularised by Mike McFadden. The gathered values are split
+/// into two buffers of size columns each. Runs which fit are merged
+/// through the second buffer directly.
+///
+/// Longer runs are merged by cutting A, the left run, into blocks,
+/// the first of which may be short, and rolling them through B. Each
+/// A block after the first is tagged by swapping its first element
+/// with a value from the first buffer, so the tags say which block
+/// originally came first even after the blocks are shuffled. Whole A
+/// blocks are swapped past B blocks until the smallest A block, found
+/// by its tag, belongs before the end of the last B block. Then it is
+/// dropped there, untagged, and the previous A block is merged with
+/// the B values between the two, through the second buffer.
+#[derive(Clone, Default)]
+pub struct Wiki {
+    size: usize,
+    low: usize,
+    mid: usize,
+    high: usize,
+    block: usize,
+    block_a: (usize, usize),
+    block_b: (usize, usize),
+    last_a: (usize, usize),
+    last_b: (usize, usize),
+    min_a: Option<usize>,
+    index_a: usize,
+    phase: WikiPhase,
+    label: &'static str,
+}
+
+impl Wiki {
+    /// [Merge Last A]
+    /// Queues a merge of the previous A block with the B values from
+    /// its end up to end.
+    fn merge_last_a(&self, ops: &mut Ops, end: usize) {
+        let (start, mid) = self.last_a;
+        if start < mid && mid < end {
+            buffered_merge(ops, self.size, (start, mid, end), true);
+        }
+    }
+}
+
+impl Merger for Wiki {
+    fn wanted(len: usize) -> usize {
+        2 * (len.isqrt() + 1)
+    }
+
+    fn setup(&mut self, found: usize, _len: usize) {
+        self.size = found / 2;
+        self.label = "merging runs";
+    }
+
+    fn begin(&mut self, (low, mid, high): (usize, usize, usize)) {
+        (self.low, self.mid, self.high) = (low, mid, high);
+        self.block = 0;
+        self.phase = WikiPhase::Start;
+    }
+
+    fn next(&mut self, columns: &[i32], direction: i32, ops: &mut Ops) -> bool {
+        let (low, mid, high) = (self.low, self.mid, self.high);
+
+        if self.phase == WikiPhase::Start {
+            // When all of B belongs before A, a rotation does it all.
+            ops.push(BlockOp::Show(Step::Compare(high - 1, low)));
+            if !in_order(columns[low], columns[high - 1], direction) {
+                ops.push(BlockOp::rotate(low, mid, high));
+                self.label = "rotating runs";
+                return true;
+            }
+
+            let len = mid - low;
+            if len <= self.size {
+                buffered_merge(ops, self.size, (low, mid, high), true);
+                self.label = "buffered merge";
+                return true;
+            }
+
+            // The blocks must fit in the second buffer, and there must
+            // be a tag in the first buffer for every block.
+            let block = len.isqrt().max(len.div_ceil(self.size.max(1)));
+            if block > self.size {
+                ops.push(BlockOp::RotationMerge { low, mid, high });
+                self.label = "in-place merge";
+                return true;
+            }
+
+            let first = len % block;
+            self.block = block;
+            self.block_a = (low + first, mid);
+            self.block_b = (mid, mid + block.min(high - mid));
+            self.last_a = (low, low + first);
+            self.last_b = (low + first, low + first);
+            self.min_a = Some(low + first);
+            self.index_a = 0;
+            for k in 0..len / block {
+                ops.push(BlockOp::Swap(low + first + k * block, k));
+            }
+            self.label = "rolling blocks";
+            self.phase = WikiPhase::Roll;
+            return false;
+        }
+
+        let block = self.block;
+        let (block_a, block_b, last_b) = (self.block_a, self.block_b, self.last_b);
+
+        // After a drop, the smallest A block left is the one with the
+        // smallest tag.
+        let min_a = match self.min_a {
+            Some(min_a) => min_a,
+            None => {
+                let mut min_a = block_a.0;
+                for start in (block_a.0 + block..block_a.1).step_by(block) {
+                    ops.push(BlockOp::Show(Step::Compare(start, min_a)));
+                    if !in_order(columns[min_a], columns[start], direction) {
+                        min_a = start;
+                    }
+                }
+                min_a
+            }
+        };
+        self.min_a = Some(min_a);
+
+        // The smallest A block's real first value is in the first
+        // buffer, since blocks are dropped in their original order.
+        let first = columns[self.index_a];
+        let drop = block_b.0 == block_b.1 || {
+            last_b.0 < last_b.1 && {
+                ops.push(BlockOp::Show(Step::Compare(last_b.1 - 1, self.index_a)));
+                !before(columns[last_b.1 - 1], first, direction)
+            }
+        };
+
+        if drop {
+            // The last B block is split where the A block belongs.
+            let split = last_b.0
+                + gallop(last_b.1 - last_b.0, |k| {
+                    ops.push(BlockOp::Show(Step::Compare(last_b.0 + k, self.index_a)));
+                    before(columns[last_b.0 + k], first, direction)
+                });
+            let remaining = last_b.1 - split;
+
+            if min_a != block_a.0 {
+                ops.push(BlockOp::block_swap(block_a.0, min_a, block));
+            }
+            ops.push(BlockOp::Swap(block_a.0, self.index_a));
+            self.index_a += 1;
+
+            self.merge_last_a(ops, split);
+            ops.push(BlockOp::rotate(split, block_a.0, block_a.0 + block));
+
+            self.last_a = (block_a.0 - remaining, block_a.0 - remaining + block);
+            self.last_b = (self.last_a.1, self.last_a.1 + remaining);
+            self.block_a.0 += block;
+            self.min_a = None;
+
+            if self.block_a.0 == self.block_a.1 {
+                self.merge_last_a(ops, high);
+                return true;
+            }
+        } else if block_b.1 - block_b.0 < block {
+            // The last B block is short, so it is rotated in front of
+            // the A blocks rather than swapped past them.
+            let len = block_b.1 - block_b.0;
+            ops.push(BlockOp::rotate(block_a.0, block_b.0, block_b.1));
+            self.last_b = (block_a.0, block_a.0 + len);
+            self.block_a = (block_a.0 + len, block_a.1 + len);
+            self.min_a = Some(min_a + len);
+            self.block_b = (block_b.1, block_b.1);
+        } else {
+            // Roll the first A block past the next B block.
+            ops.push(BlockOp::block_swap(block_a.0, block_b.0, block));
+            self.last_b = (block_a.0, block_a.0 + block);
+            if min_a == block_a.0 {
+                self.min_a = Some(block_a.1);
+            }
+            self.block_a = (block_a.0 + block, block_a.1 + block);
+            self.block_b = (block_b.0 + block, (block_b.1 + block).min(high));
+        }
+        false
+    }
+
+    fn buffers(&self) -> Vec<Marker> {
+        if self.size == 0 {
+            return vec![];
+        }
+        vec![Marker::Buffer(0, self.size), Marker::Buffer(self.size, 2 * self.size)]
+    }
+
+    fn phase(&self) -> &'static str {
+        self.label
+    }
+
+    fn block(&self) -> usize {
+        self.block
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum GrailPhase {
+    #[default]
+    Start,
+    Select,
+    Combine,
+}
+
+/// [Grail]
+/// The merge of GrailSort, Andrey Astrelin's block merge. The
+/// gathered values are split into keys, which tag blocks, and a
+/// buffer one block long. Runs which fit are merged through the
+/// buffer directly.
+///
+/// Longer runs are cut into blocks, each tagged with a key. A blocks
+/// get keys before the middle key and B blocks the middle key and
+/// after. All the blocks are selection sorted by their first element,
+/// taking the key along, and equal first elements are ordered by
+/// their keys so A blocks stay before B blocks. Then a single pass
+/// merges each block with the unfinished tail of the blocks before
+/// it, the rest, whenever the two came from different runs. Whatever
+/// is left over of the pair is the new rest. B's last partial block
+/// is merged in by rotations at the end, and the keys are sorted
+/// again for next time.
+#[derive(Clone, Default)]
+pub struct Grail {
+    keys: usize,
+    block: usize,
+    lazy: bool,
+    low: usize,
+    mid: usize,
+    high: usize,
+    count: usize,
+    middle_key: i32,
+    next: usize,
+    rest: (usize, usize, bool),
+    phase: GrailPhase,
+    label: &'static str,
+    merging: bool,
+}
+
+impl Grail {
+    /// [Block Size]
+    /// The block size for len columns, a power of two times the chunk
+    /// size, so that run widths are always a whole number of blocks.
+    fn block_size(len: usize) -> usize {
+        let mut block = CHUNK;
+        while block * block < len {
+            block *= 2;
+        }
+        block
+    }
+
+    /// [Start]
+    /// The first column of the n-th block of the merge.
+    fn start(&self, n: usize) -> usize {
+        self.low + n * self.block
+    }
+}
+
+impl Merger for Grail {
+    fn wanted(len: usize) -> usize {
+        let block = Grail::block_size(len);
+        block + len.div_ceil(block)
+    }
+
+    fn setup(&mut self, found: usize, len: usize) {
+        self.block = Grail::block_size(len);
+        self.lazy = found < Grail::wanted(len);
+        self.keys = if self.lazy { 0 } else { found - self.block };
+        self.label = "merging runs";
+    }
+
+    fn begin(&mut self, (low, mid, high): (usize, usize, usize)) {
+        (self.low, self.mid, self.high) = (low, mid, high);
+        self.phase = GrailPhase::Start;
+        self.merging = false;
+    }
+
+    fn next(&mut self, columns: &[i32], direction: i32, ops: &mut Ops) -> bool {
+        let (low, mid, high, block) = (self.low, self.mid, self.high, self.block);
+
+        match self.phase {
+            GrailPhase::Start => {
+                let blocks = (high - mid) / block;
+                if self.lazy || blocks == 0 {
+                    ops.push(BlockOp::RotationMerge { low, mid, high });
+                    self.label = "in-place merge";
+                    return true;
+                }
+
+                if mid - low <= block {
+                    buffered_merge(ops, self.keys, (low, mid, high), true);
+                    self.label = "buffered merge";
+                    return true;
+                }
+
+                self.count = (mid - low) / block + blocks;
+                self.middle_key = columns[(mid - low) / block];
+                self.next = 0;
+                self.merging = true;
+                self.label = "selecting blocks";
+                self.phase = GrailPhase::Select;
+            }
+            GrailPhase::Select => {
+                let i = self.next;
+                if i + 1 >= self.count {
+                    self.next = 0;
+                    self.rest = (low, low, true);
+                    self.label = "merging blocks";
+                    self.phase = GrailPhase::Combine;
+                    return false;
+                }
+
+                // Ties between first elements are broken by the keys.
+                let mut best = i;
+                for j in i + 1..self.count {
+                    let (a, b) = (columns[self.start(j)], columns[self.start(best)]);
+                    ops.push(BlockOp::Show(Step::Compare(self.start(j), self.start(best))));
+                    let better = before(a, b, direction)
+                        || !before(b, a, direction) && {
+                            ops.push(BlockOp::Show(Step::Compare(j, best)));
+                            !in_order(columns[best], columns[j], direction)
+                        };
+                    if better {
+                        best = j;
+                    }
+                }
+
+                if best != i {
+                    ops.push(BlockOp::block_swap(self.start(i), self.start(best), block));
+                    ops.push(BlockOp::Swap(i, best));
+                }
+                self.next += 1;
+            }
+            GrailPhase::Combine => {
+                let n = self.next;
+                let tail = self.start(self.count);
+                if n == self.count {
+                    if tail < high {
+                        ops.push(BlockOp::RotationMerge { low, mid: tail, high });
+                    }
+                    ops.push(BlockOp::Insertion { low: 0, high: self.count, sort: InsertionSort::default() });
+                    self.merging = false;
+                    return true;
+                }
+
+                let (start, end) = (self.start(n), self.start(n + 1));
+                let from_a = before(columns[n], self.middle_key, direction);
+                let (rest_start, rest_end, rest_a) = self.rest;
+                self.next += 1;
+
+                // The rest is final once a block from the same run
+                // follows it.
+                if rest_start == rest_end || rest_a == from_a {
+                    self.rest = (start, end, from_a);
+                    return false;
+                }
+
+                // Work out which side runs out first, and so what the
+                // new rest will be. Ties always go to the A side.
+                let (rest_last, block_last) = (columns[rest_end - 1], columns[end - 1]);
+                ops.push(BlockOp::Show(Step::Compare(rest_end - 1, end - 1)));
+                let rest_first = if rest_a { in_order(rest_last, block_last, direction) } else { before(rest_last, block_last, direction) };
+
+                let mut shows = vec![];
+                if rest_first {
+                    let taken = gallop(block, |k| {
+                        shows.push(BlockOp::Show(Step::Compare(start + k, rest_end - 1)));
+                        if rest_a { before(columns[start + k], rest_last, direction) } else { in_order(columns[start + k], rest_last, direction) }
+                    });
+                    self.rest = (start + taken, end, from_a);
+                } else {
+                    let len = rest_end - rest_start;
+                    let taken = gallop(len, |k| {
+                        shows.push(BlockOp::Show(Step::Compare(rest_start + k, end - 1)));
+                        if rest_a { in_order(columns[rest_start + k], block_last, direction) } else { before(columns[rest_start + k], block_last, direction) }
+                    });
+                    self.rest = (end - (len - taken), end, rest_a);
+                }
+                ops.extend(shows);
+                buffered_merge(ops, self.keys, (rest_start, rest_end, end), rest_a);
+            }
+        }
+        false
+    }
+
+    fn buffers(&self) -> Vec<Marker> {
+        if self.lazy {
+            return vec![];
+        }
+        vec![Marker::Buffer(0, self.keys), Marker::Buffer(self.keys, self.keys + self.block)]
+    }
+
+    fn phase(&self) -> &'static str {
+        self.label
+    }
+
+    fn block(&self) -> usize {
+        if self.merging {
+            self.block
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{sort, sorted};
+    use super::super::Trace;
+    use super::*;
+
+    #[test]
+    fn block_merge_sorts_sort_with_few_keys() {
+        // With too few distinct values for a full buffer, the merges
+        // have to make do with a smaller one, or none at all.
+        for n in [0, 1, 7, 8, 9, 63, 64, 65, 200, 513] {
+            for distinct in [1, 2, 3, 10, n.max(1)] {
+                let columns: Vec<i32> = (0..n).map(|i| i * 7919 % distinct).collect();
+                for direction in [1, -1] {
+                    assert_eq!(sort(&mut WikiSort::default(), &columns, direction), sorted(&columns, direction), "WikiSort on {:?}", columns);
+                    assert_eq!(sort(&mut GrailSort::default(), &columns, direction), sorted(&columns, direction), "GrailSort on {:?}", columns);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn keys_are_collected_first() {
+        let mut columns: Vec<i32> = (0..256).map(|i| i * 7919 % 256).collect();
+        let mut algorithm = GrailSort::default();
+        let mut trace = Trace::default();
+        let mut phases = vec![];
+        while trace.step(&mut algorithm, &mut columns, 1) != Step::Done {
+            let status = algorithm.status().unwrap();
+            let phase = status.split(" - ").next().unwrap().to_string();
+            if phases.last() != Some(&phase) {
+                phases.push(phase);
+            }
+        }
+        assert_eq!(phases.first().map(String::as_str), Some("collecting keys"));
+        assert_eq!(phases.last().map(String::as_str), Some("redistributing buffer"));
+        assert_eq!(columns, (0..256).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,371 @@
+/*****************************************************************/
+//! [Bubble Sorts]
+/*****************************************************************/
+//!
+//! Bubble sort and its relatives, which all sort by comparing pairs
+//! of elements and swapping the ones that are out of order. Most of
+//! them only ever look at neighbours.
+
+use super::{Array, SortAlgorithm, Step};
+
+/// [Compare Swap]
+/// Compares the columns at i and j, where i comes first, and swaps
+/// them if they are out of order. This is the single operation that
+/// every algorithm in this family is built from.
+fn compare_swap(columns: &mut Array, i: usize, j: usize, direction: i32) -> Step {
+    if columns.compare(i, j, direction) {
+        Step::Compare(i, j)
+    } else {
+        columns.swap(i, j);
+        Step::Swap(i, j)
+    }
+}
+
+/// [Bubble Sort]
+/// Repeatedly walks over the columns swapping neighbours that are out
+/// of order. We keep track of the current progress using the pointer
+/// and completed fields. Once a pass is completed, we reset the
+/// pointer and start anew, ignoring the tail which is already sorted.
+/// A pass which swaps nothing means the columns are sorted, so we stop
+/// there rather than carrying on with passes that can't change
+/// anything.
+#[derive(Clone, Default)]
+pub struct BubbleSort {
+    pointer: usize,
+    completed: usize,
+    swapped: bool,
+}
+
+impl SortAlgorithm for BubbleSort {
+    fn reset(&mut self) {
+        *self = BubbleSort::default();
+    }
+
+    /// [Step]
+    /// This method performs exactly one step of a bubble sort algorithm.
+    /// Doing it one step at a time is nice for visualizing exactly what
+    /// is going on, and so I opted to do it this way.
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if self.completed + 1 >= columns.len() {
+            return Step::Done;
+        }
+
+        let i = self.pointer;
+        let step = compare_swap(columns, i, i + 1, direction);
+        self.swapped |= step == Step::Swap(i, i + 1);
+
+        if self.pointer < columns.len() - 2 - self.completed {
+            self.pointer += 1;
+        } else {
+            // End of a pass. If nothing moved, we're done.
+            self.completed = if self.swapped { self.completed + 1 } else { columns.len() };
+            self.pointer = 0;
+            self.swapped = false;
+        }
+
+        step
+    }
+}
+
+/// [Cocktail Shaker Sort]
+/// Bubble sort which alternates between passes to the right and passes
+/// to the left, so that small elements near the end travel home as
+/// quickly as large elements near the start. The unsorted region is
+/// low..=high, which shrinks from whichever end a pass finished at.
+#[derive(Clone, Default)]
+pub struct CocktailShakerSort {
+    low: usize,
+    high: usize,
+    pointer: usize,
+    backwards: bool,
+    swapped: bool,
+    started: bool,
+}
+
+impl SortAlgorithm for CocktailShakerSort {
+    fn reset(&mut self) {
+        *self = CocktailShakerSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            self.high = columns.len().saturating_sub(1);
+        }
+
+        if self.low >= self.high {
+            return Step::Done;
+        }
+
+        let i = self.pointer;
+        let step = compare_swap(columns, i, i + 1, direction);
+        self.swapped |= matches!(step, Step::Swap(..));
+
+        if !self.backwards && self.pointer + 1 < self.high {
+            self.pointer += 1;
+        } else if self.backwards && self.pointer > self.low {
+            self.pointer -= 1;
+        } else {
+            // End of a pass, where a pass without swaps ends the sort.
+            if !self.swapped {
+                self.low = self.high;
+            } else if self.backwards {
+                self.low += 1;
+                self.pointer = self.low;
+            } else {
+                self.high -= 1;
+                self.pointer = self.high.saturating_sub(1).max(self.low);
+            }
+            self.backwards = !self.backwards;
+            self.swapped = false;
+        }
+
+        step
+    }
+}
+
+/// [Odd-Even Sort]
+/// Odd-even transposition sort. Passes alternate between comparing
+/// the pairs starting at even indices and the pairs starting at odd
+/// indices. No two pairs in a pass overlap, which is what makes it
+/// a parallel algorithm. Once an odd and an even pass in a row have
+/// both swapped nothing, the columns are sorted.
+#[derive(Clone, Default)]
+pub struct OddEvenSort {
+    parity: usize,
+    pointer: usize,
+    swapped: bool,
+    quiet: usize,
+}
+
+impl SortAlgorithm for OddEvenSort {
+    fn reset(&mut self) {
+        *self = OddEvenSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            if self.quiet >= 2 || columns.len() < 2 {
+                return Step::Done;
+            }
+
+            let i = self.parity + 2 * self.pointer;
+            if i + 1 >= columns.len() {
+                // End of a pass, swap to the other parity.
+                self.quiet = if self.swapped { 0 } else { self.quiet + 1 };
+                self.parity = 1 - self.parity;
+                self.pointer = 0;
+                self.swapped = false;
+                continue;
+            }
+
+            self.pointer += 1;
+            let step = compare_swap(columns, i, i + 1, direction);
+            self.swapped |= matches!(step, Step::Swap(..));
+            return step;
+        }
+    }
+}
+
+/// [Gnome Sort]
+/// A garden gnome sorting flower pots. If the pot in front of him and
+/// the one behind him are in order he steps forward, otherwise he
+/// swaps them and steps back. When the optimized flag is set, the
+/// gnome remembers the furthest he has been, and walks straight back
+/// there once the pot is in place, which makes it insertion sort.
+#[derive(Clone, Default)]
+pub struct GnomeSort {
+    optimized: bool,
+    position: usize,
+    furthest: usize,
+}
+
+impl GnomeSort {
+    /// [Optimized]
+    /// A gnome which remembers where it was.
+    pub fn optimized() -> Self {
+        GnomeSort { optimized: true, ..GnomeSort::default() }
+    }
+}
+
+impl SortAlgorithm for GnomeSort {
+    fn reset(&mut self) {
+        *self = GnomeSort { optimized: self.optimized, ..GnomeSort::default() };
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if self.position == 0 {
+            self.position = 1;
+        }
+
+        if self.position >= columns.len() {
+            return Step::Done;
+        }
+
+        let (i, j) = (self.position - 1, self.position);
+        let step = compare_swap(columns, i, j, direction);
+        let forward = if self.optimized { self.furthest.max(self.position) + 1 } else { self.position + 1 };
+
+        self.position = match step {
+            Step::Swap(..) if self.position > 1 => self.position - 1,
+            _ => forward,
+        };
+        self.furthest = self.furthest.max(self.position);
+
+        step
+    }
+}
+
+/// [Exchange Sort]
+/// The simplest sort of all. Every element is compared with every
+/// element after it, and swapped whenever the two are out of order.
+/// After comparing first with everything else, it is in place.
+#[derive(Clone, Default)]
+pub struct ExchangeSort {
+    first: usize,
+    second: usize,
+}
+
+impl SortAlgorithm for ExchangeSort {
+    fn reset(&mut self) {
+        *self = ExchangeSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if self.second <= self.first {
+            self.second = self.first + 1;
+        }
+
+        if self.second >= columns.len() {
+            if self.first + 2 >= columns.len() {
+                return Step::Done;
+            }
+            self.first += 1;
+            self.second = self.first + 1;
+        }
+
+        let (i, j) = (self.first, self.second);
+        self.second += 1;
+        compare_swap(columns, i, j, direction)
+    }
+}
+
+/// [Circle Sort]
+/// Compares the first element with the last, the second with the
+/// second last, and so on, like folding the range in half, then does
+/// the same to each half. Whole rounds repeat until one swaps nothing.
+/// The ranges still to be folded this round are kept on a stack, as
+/// inclusive (start, end) pairs, and the fold in progress is between
+/// the left and right pointers.
+#[derive(Clone, Default)]
+pub struct CircleSort {
+    ranges: Vec<(usize, usize)>,
+    range: Option<(usize, usize)>,
+    left: usize,
+    right: usize,
+    swapped: bool,
+    rounds: usize,
+}
+
+impl SortAlgorithm for CircleSort {
+    fn reset(&mut self) {
+        *self = CircleSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            let Some((start, end)) = self.range else {
+                match self.ranges.pop() {
+                    Some((start, end)) => {
+                        if start < end {
+                            self.range = Some((start, end));
+                            (self.left, self.right) = (start, end);
+                        }
+                    }
+                    None => {
+                        // The round is over, and another is needed
+                        // unless this one swapped nothing.
+                        if columns.len() < 2 || (self.rounds > 0 && !self.swapped) {
+                            return Step::Done;
+                        }
+                        self.ranges.push((0, columns.len() - 1));
+                        self.swapped = false;
+                        self.rounds += 1;
+                    }
+                }
+                continue;
+            };
+
+            if self.left > self.right {
+                // The fold has finished, so move on to both halves.
+                self.ranges.push((self.left, end));
+                self.ranges.push((start, self.right));
+                self.range = None;
+                continue;
+            }
+
+            // With an odd number of elements, the middle one is
+            // compared with its right hand neighbour instead.
+            let step = if self.left == self.right {
+                let step = compare_swap(columns, self.left, self.left + 1, direction);
+                self.left += 1;
+                step
+            } else {
+                let step = compare_swap(columns, self.left, self.right, direction);
+                self.left += 1;
+                self.right -= 1;
+                step
+            };
+
+            self.swapped |= matches!(step, Step::Swap(..));
+            return step;
+        }
+    }
+
+    fn status(&self) -> Option<String> {
+        if self.rounds > 0 {
+            Some(format!("round: {}", self.rounds))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{shuffled, sort, sorted};
+    use super::super::Trace;
+    use super::*;
+
+    #[test]
+    fn bubble_sort_stops_after_a_quiet_pass() {
+        let mut columns: Vec<i32> = (0..50).collect();
+        let mut algorithm = BubbleSort::default();
+        let mut trace = Trace::default();
+        let mut steps = 0;
+        while trace.step(&mut algorithm, &mut columns, 1) != Step::Done {
+            steps += 1;
+        }
+        assert_eq!(steps, 49);
+    }
+
+    #[test]
+    fn relatives_sort_every_length() {
+        // Circle sort folds odd lengths around a middle element, and
+        // odd-even sort alternates which pairs it looks at.
+        for n in 0..=33 {
+            let columns = shuffled(n);
+            let algorithms: [Box<dyn SortAlgorithm>; 6] = [
+                Box::new(CocktailShakerSort::default()),
+                Box::new(OddEvenSort::default()),
+                Box::new(GnomeSort::default()),
+                Box::new(GnomeSort::optimized()),
+                Box::new(ExchangeSort::default()),
+                Box::new(CircleSort::default()),
+            ];
+            for mut algorithm in algorithms {
+                assert_eq!(sort(algorithm.as_mut(), &columns, -1), sorted(&columns, -1), "{:?}", columns);
+            }
+        }
+    }
+}
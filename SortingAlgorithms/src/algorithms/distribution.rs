@@ -0,0 +1,884 @@
+/*****************************************************************/
+//! [Distribution Sorts]
+/*****************************************************************/
+//!
+//! These algorithms never compare two columns with each other.
+//! Instead they scatter values into counts or buckets keyed on the
+//! value itself, then gather them back out in order. The counts and
+//! buckets are exposed as a histogram panel, so that the scatter and
+//! gather phases can be watched.
+//!
+//! Every algorithm starts with a pass reading the columns to find the
+//! smallest and largest values, which turns values into keys counting
+//! up from zero in the order the direction asks for.
+
+use std::collections::VecDeque;
+
+use super::{Array, Marker, Panel, SortAlgorithm, Step};
+
+/// [Radix Bases]
+/// The bases the radix sorts can cycle through.
+const RADIX_BASES: [usize; 5] = [10, 16, 2, 4, 8];
+
+/// [Bucket Counts]
+/// The numbers of buckets bucket sort can cycle through.
+const BUCKET_COUNTS: [usize; 4] = [10, 4, 8, 16];
+
+/// [Most Keys]
+/// The widest range of values counting sort and pigeonhole sort will
+/// make room for, as they need a count or a hole for every one.
+const MOST_KEYS: usize = 1 << 20;
+
+/// [Keys]
+/// Maps values onto keys from zero up to range - 1. In descending
+/// order the largest value gets key zero.
+#[derive(Clone, Copy, Default)]
+struct Keys {
+    min: i32,
+    max: i32,
+    direction: i32,
+}
+
+impl Keys {
+    // Keys are worked out in i64, as the distance between two i32s
+    // doesn't always fit in an i32.
+    fn key(&self, value: i32) -> usize {
+        if self.direction > 0 {
+            (value as i64 - self.min as i64) as usize
+        } else {
+            (self.max as i64 - value as i64) as usize
+        }
+    }
+
+    fn value(&self, key: usize) -> i32 {
+        if self.direction > 0 {
+            (self.min as i64 + key as i64) as i32
+        } else {
+            (self.max as i64 - key as i64) as i32
+        }
+    }
+
+    fn range(&self) -> usize {
+        (self.max as i64 - self.min as i64) as usize + 1
+    }
+
+    /// [Digits]
+    /// How many digits in the given base the largest key needs.
+    fn digits(&self, base: usize) -> u32 {
+        let mut digits = 1;
+        let mut limit = base;
+        while limit < self.range() {
+            limit *= base;
+            digits += 1;
+        }
+        digits
+    }
+}
+
+/// [Too Wide]
+/// Why values can't be sorted by a sort which needs room for every
+/// key, if their range is wider than it will make room for.
+fn too_wide(values: &[i32]) -> Option<String> {
+    let (&min, &max) = (values.iter().min()?, values.iter().max()?);
+    let range = Keys { min, max, direction: 1 }.range();
+    (range > MOST_KEYS).then(|| format!("values span {}, more than the {} it makes room for", range, MOST_KEYS))
+}
+
+/// [Digit]
+/// The digit of a key at the given position, counting from the least
+/// significant digit.
+fn digit(key: usize, position: u32, base: usize) -> usize {
+    key / base.pow(position) % base
+}
+
+/// [Range Scan]
+/// Reads every column once to find the smallest and largest values.
+#[derive(Clone, Default)]
+struct RangeScan {
+    pointer: usize,
+    min: i32,
+    max: i32,
+}
+
+impl RangeScan {
+    /// [Step]
+    /// Reads the next column, or returns None once all of them have
+    /// been read.
+    fn step(&mut self, columns: &mut Array) -> Option<Step> {
+        let i = self.pointer;
+        if i == columns.len() {
+            return None;
+        }
+        let value = columns.read(i);
+
+        if i == 0 {
+            (self.min, self.max) = (value, value);
+        } else {
+            (self.min, self.max) = (self.min.min(value), self.max.max(value));
+        }
+
+        self.pointer += 1;
+        Some(Step::Read(i))
+    }
+
+    fn keys(&self, direction: i32) -> Keys {
+        Keys { min: self.min, max: self.max, direction }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Phase {
+    #[default]
+    Scan,
+    Count,
+    Scatter,
+    Gather,
+    Insert,
+    Done,
+}
+
+/// [Counting Sort]
+/// Counts how many times each value occurs, then writes each value
+/// back out as many times as it was counted. The histogram fills up
+/// while counting and drains again while writing.
+#[derive(Clone, Default)]
+pub struct CountingSort {
+    phase: Phase,
+    scan: RangeScan,
+    keys: Keys,
+    counts: Vec<usize>,
+    pointer: usize,
+    key: usize,
+}
+
+impl SortAlgorithm for CountingSort {
+    fn reset(&mut self) {
+        *self = CountingSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            match self.phase {
+                Phase::Scan => {
+                    if let Some(step) = self.scan.step(columns) {
+                        return step;
+                    }
+                    if columns.is_empty() {
+                        self.phase = Phase::Done;
+                        continue;
+                    }
+                    self.keys = self.scan.keys(direction);
+                    self.counts = vec![0; self.keys.range()];
+                    self.phase = Phase::Count;
+                }
+                Phase::Count => {
+                    let i = self.pointer;
+                    if i == columns.len() {
+                        (self.pointer, self.key) = (0, 0);
+                        self.phase = Phase::Gather;
+                        continue;
+                    }
+                    self.key = self.keys.key(columns.read(i));
+                    self.counts[self.key] += 1;
+                    self.pointer += 1;
+                    return Step::Read(i);
+                }
+                Phase::Gather => {
+                    while self.key < self.counts.len() && self.counts[self.key] == 0 {
+                        self.key += 1;
+                    }
+                    if self.key == self.counts.len() {
+                        self.phase = Phase::Done;
+                        continue;
+                    }
+                    let i = self.pointer;
+                    columns.write(i, self.keys.value(self.key));
+                    self.counts[self.key] -= 1;
+                    self.pointer += 1;
+                    return Step::Write(i);
+                }
+                _ => return Step::Done,
+            }
+        }
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        vec![Panel::Histogram(&self.counts, Some(self.key))]
+    }
+
+    fn refusal(&self, values: &[i32]) -> Option<String> {
+        too_wide(values)
+    }
+}
+
+/// [Pigeonhole Sort]
+/// Like counting sort, except that every value gets a hole which the
+/// elements themselves are moved into, rather than just being counted.
+/// The holes are then emptied back into the columns in order.
+#[derive(Clone, Default)]
+pub struct PigeonholeSort {
+    phase: Phase,
+    scan: RangeScan,
+    keys: Keys,
+    holes: Vec<VecDeque<i32>>,
+    sizes: Vec<usize>,
+    pointer: usize,
+    hole: usize,
+}
+
+impl SortAlgorithm for PigeonholeSort {
+    fn reset(&mut self) {
+        *self = PigeonholeSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            match self.phase {
+                Phase::Scan => {
+                    if let Some(step) = self.scan.step(columns) {
+                        return step;
+                    }
+                    if columns.is_empty() {
+                        self.phase = Phase::Done;
+                        continue;
+                    }
+                    self.keys = self.scan.keys(direction);
+                    self.holes = vec![VecDeque::new(); self.keys.range()];
+                    self.sizes = vec![0; self.keys.range()];
+                    self.phase = Phase::Scatter;
+                }
+                Phase::Scatter => {
+                    let i = self.pointer;
+                    if i == columns.len() {
+                        (self.pointer, self.hole) = (0, 0);
+                        self.phase = Phase::Gather;
+                        continue;
+                    }
+                    let value = columns.read(i);
+                    self.hole = self.keys.key(value);
+                    self.holes[self.hole].push_back(value);
+                    self.sizes[self.hole] += 1;
+                    self.pointer += 1;
+                    return Step::Read(i);
+                }
+                Phase::Gather => {
+                    while self.hole < self.holes.len() && self.holes[self.hole].is_empty() {
+                        self.hole += 1;
+                    }
+                    if self.hole == self.holes.len() {
+                        self.phase = Phase::Done;
+                        continue;
+                    }
+                    let i = self.pointer;
+                    columns.write(i, self.holes[self.hole].pop_front().unwrap());
+                    self.sizes[self.hole] -= 1;
+                    self.pointer += 1;
+                    return Step::Write(i);
+                }
+                _ => return Step::Done,
+            }
+        }
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        vec![Panel::Histogram(&self.sizes, Some(self.hole))]
+    }
+
+    fn refusal(&self, values: &[i32]) -> Option<String> {
+        too_wide(values)
+    }
+}
+
+/// [Bucket Sort]
+/// Scatters the elements into a fixed number of buckets which each
+/// cover an equal share of the value range, gathers them back bucket
+/// by bucket, then insertion sorts within each bucket. Bucket edges
+/// within the columns are kept in edges while insertion sorting.
+#[derive(Clone, Default)]
+pub struct BucketSort {
+    count: usize,
+    phase: Phase,
+    scan: RangeScan,
+    keys: Keys,
+    buckets: Vec<VecDeque<i32>>,
+    sizes: Vec<usize>,
+    edges: Vec<usize>,
+    pointer: usize,
+    bucket: usize,
+    next: usize,
+}
+
+impl BucketSort {
+    fn bucket_of(&self, value: i32) -> usize {
+        self.keys.key(value) * self.buckets.len() / self.keys.range()
+    }
+}
+
+impl SortAlgorithm for BucketSort {
+    fn reset(&mut self) {
+        *self = BucketSort { count: self.count, ..BucketSort::default() };
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            match self.phase {
+                Phase::Scan => {
+                    if let Some(step) = self.scan.step(columns) {
+                        return step;
+                    }
+                    if columns.is_empty() {
+                        self.phase = Phase::Done;
+                        continue;
+                    }
+                    self.keys = self.scan.keys(direction);
+                    self.buckets = vec![VecDeque::new(); BUCKET_COUNTS[self.count]];
+                    self.sizes = vec![0; BUCKET_COUNTS[self.count]];
+                    self.phase = Phase::Scatter;
+                }
+                Phase::Scatter => {
+                    let i = self.pointer;
+                    if i == columns.len() {
+                        (self.pointer, self.bucket) = (0, 0);
+                        self.edges = vec![0];
+                        self.phase = Phase::Gather;
+                        continue;
+                    }
+                    let value = columns.read(i);
+                    self.bucket = self.bucket_of(value);
+                    self.buckets[self.bucket].push_back(value);
+                    self.sizes[self.bucket] += 1;
+                    self.pointer += 1;
+                    return Step::Read(i);
+                }
+                Phase::Gather => {
+                    while self.bucket < self.buckets.len() && self.buckets[self.bucket].is_empty() {
+                        self.bucket += 1;
+                        self.edges.push(self.pointer);
+                    }
+                    if self.bucket == self.buckets.len() {
+                        self.edges.dedup();
+                        (self.bucket, self.next, self.pointer) = (0, 0, 0);
+                        self.phase = Phase::Insert;
+                        continue;
+                    }
+                    let i = self.pointer;
+                    columns.write(i, self.buckets[self.bucket].pop_front().unwrap());
+                    self.sizes[self.bucket] -= 1;
+                    self.pointer += 1;
+                    return Step::Write(i);
+                }
+                Phase::Insert => {
+                    // Insertion sort, sinking the element at pointer, but
+                    // never past the start of its bucket.
+                    let start = self.edges[self.bucket];
+                    if self.pointer <= start {
+                        self.next += 1;
+                        if self.next == columns.len() {
+                            self.phase = Phase::Done;
+                            continue;
+                        }
+                        if self.bucket + 1 < self.edges.len() && self.next >= self.edges[self.bucket + 1] {
+                            self.bucket += 1;
+                        }
+                        self.pointer = self.next;
+                        continue;
+                    }
+
+                    let (i, j) = (self.pointer - 1, self.pointer);
+                    if columns.compare(i, j, direction) {
+                        self.pointer = start;
+                        return Step::Compare(i, j);
+                    }
+                    columns.swap(i, j);
+                    self.pointer -= 1;
+                    return Step::Swap(i, j);
+                }
+                _ => return Step::Done,
+            }
+        }
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        let active = if self.phase == Phase::Insert { None } else { Some(self.bucket) };
+        vec![Panel::Histogram(&self.sizes, active)]
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        if self.phase == Phase::Insert {
+            self.edges.iter().map(|&edge| Marker::Boundary(edge)).collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn option(&self) -> Option<String> {
+        Some(format!("buckets: {}", BUCKET_COUNTS[self.count]))
+    }
+
+    /// [Next Option]
+    /// Changes the number of buckets, which is used from the next sort.
+    fn next_option(&mut self) {
+        self.count = (self.count + 1) % BUCKET_COUNTS.len();
+    }
+}
+
+/// [Radix Pass]
+/// One counting pass of a radix sort over the range low..high, on the
+/// digit at position. The digits are counted, then every element is
+/// scattered into its digit's region of the auxiliary buffer, and
+/// finally the buffer is copied back. The scatter keeps elements with
+/// equal digits in their original order, which LSD radix sort needs.
+#[derive(Clone, Default)]
+struct RadixPass {
+    phase: Phase,
+    low: usize,
+    high: usize,
+    position: u32,
+    base: usize,
+    counts: Vec<usize>,
+    starts: Vec<usize>,
+    pointer: usize,
+    digit: usize,
+}
+
+impl RadixPass {
+    fn start(&mut self, low: usize, high: usize, position: u32, base: usize) {
+        (self.low, self.high, self.position, self.base) = (low, high, position, base);
+        self.counts = vec![0; base];
+        self.pointer = low;
+        self.phase = Phase::Count;
+    }
+
+    /// [Step]
+    /// Performs one operation of the pass, or returns None once the
+    /// buffer has been copied back.
+    fn step(&mut self, columns: &mut Array, aux: &mut Vec<Option<i32>>, keys: &Keys) -> Option<Step> {
+        loop {
+            match self.phase {
+                Phase::Count => {
+                    let i = self.pointer;
+                    if i == self.high {
+                        // Each digit's region starts after all smaller digits.
+                        self.starts = vec![self.low; self.base];
+                        for d in 1..self.base {
+                            self.starts[d] = self.starts[d - 1] + self.counts[d - 1];
+                        }
+                        self.pointer = self.low;
+                        self.phase = Phase::Scatter;
+                        aux.resize(columns.len(), None);
+                        continue;
+                    }
+                    self.digit = digit(keys.key(columns.read(i)), self.position, self.base);
+                    self.counts[self.digit] += 1;
+                    self.pointer += 1;
+                    return Some(Step::Read(i));
+                }
+                Phase::Scatter => {
+                    let i = self.pointer;
+                    if i == self.high {
+                        self.pointer = self.low;
+                        self.phase = Phase::Gather;
+                        continue;
+                    }
+                    let value = columns.read(i);
+                    self.digit = digit(keys.key(value), self.position, self.base);
+                    let k = self.starts[self.digit];
+                    aux[k] = Some(value);
+                    columns.aux_write(k, value);
+                    self.starts[self.digit] += 1;
+                    self.counts[self.digit] -= 1;
+                    self.pointer += 1;
+                    return Some(Step::AuxWrite(k));
+                }
+                Phase::Gather => {
+                    let i = self.pointer;
+                    if i == self.high {
+                        self.phase = Phase::Done;
+                        continue;
+                    }
+                    columns.write(i, aux[i].take().unwrap());
+                    self.pointer += 1;
+                    return Some(Step::Write(i));
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn active(&self) -> Option<usize> {
+        match self.phase {
+            Phase::Count | Phase::Scatter => Some(self.digit),
+            _ => None,
+        }
+    }
+}
+
+/// [LSD Radix Sort]
+/// Sorts by the least significant digit first, with one stable
+/// counting pass per digit. After the pass on the most significant
+/// digit, the columns are sorted. A new base takes effect from the
+/// next pass, starting over from the least significant digit.
+#[derive(Clone, Default)]
+pub struct LsdRadixSort {
+    base: usize,
+    in_use: usize,
+    phase: Phase,
+    scan: RangeScan,
+    keys: Keys,
+    pass: RadixPass,
+    aux: Vec<Option<i32>>,
+    position: u32,
+}
+
+impl SortAlgorithm for LsdRadixSort {
+    fn reset(&mut self) {
+        *self = LsdRadixSort { base: self.base, ..LsdRadixSort::default() };
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            match self.phase {
+                Phase::Scan => {
+                    if let Some(step) = self.scan.step(columns) {
+                        return step;
+                    }
+                    if columns.is_empty() {
+                        self.phase = Phase::Done;
+                        continue;
+                    }
+                    self.keys = self.scan.keys(direction);
+                    self.in_use = self.base;
+                    self.pass.start(0, columns.len(), 0, RADIX_BASES[self.in_use]);
+                    self.phase = Phase::Count;
+                }
+                Phase::Count => {
+                    if let Some(step) = self.pass.step(columns, &mut self.aux, &self.keys) {
+                        return step;
+                    }
+
+                    if self.in_use != self.base {
+                        self.in_use = self.base;
+                        self.position = 0;
+                    } else {
+                        self.position += 1;
+                    }
+
+                    if self.position == self.keys.digits(RADIX_BASES[self.in_use]) {
+                        self.phase = Phase::Done;
+                    } else {
+                        self.pass.start(0, columns.len(), self.position, RADIX_BASES[self.in_use]);
+                    }
+                }
+                _ => return Step::Done,
+            }
+        }
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        vec![Panel::Histogram(&self.pass.counts, self.pass.active()), Panel::Aux(&self.aux)]
+    }
+
+    fn option(&self) -> Option<String> {
+        Some(format!("base: {}", RADIX_BASES[self.base]))
+    }
+
+    fn next_option(&mut self) {
+        self.base = (self.base + 1) % RADIX_BASES.len();
+    }
+
+    fn status(&self) -> Option<String> {
+        (self.phase == Phase::Count).then(|| format!("digit: {}", self.position))
+    }
+}
+
+/// [MSD Radix Sort]
+/// Sorts by the most significant digit first. One counting pass splits
+/// a range into a region per digit, and then each region with more
+/// than one element is sorted on the next digit down. Regions still
+/// to be sorted are kept on an explicit stack. The base is fixed for
+/// the whole sort, so a new base takes effect from the next sort.
+#[derive(Clone, Default)]
+pub struct MsdRadixSort {
+    base: usize,
+    in_use: usize,
+    phase: Phase,
+    scan: RangeScan,
+    keys: Keys,
+    pass: RadixPass,
+    aux: Vec<Option<i32>>,
+    ranges: Vec<(usize, usize, u32)>,
+}
+
+impl SortAlgorithm for MsdRadixSort {
+    fn reset(&mut self) {
+        *self = MsdRadixSort { base: self.base, ..MsdRadixSort::default() };
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            match self.phase {
+                Phase::Scan => {
+                    if let Some(step) = self.scan.step(columns) {
+                        return step;
+                    }
+                    if columns.is_empty() {
+                        self.phase = Phase::Done;
+                        continue;
+                    }
+                    self.keys = self.scan.keys(direction);
+                    self.in_use = self.base;
+                    let top = self.keys.digits(RADIX_BASES[self.in_use]) - 1;
+                    self.ranges.push((0, columns.len(), top));
+                    self.phase = Phase::Gather;
+                }
+                Phase::Count => {
+                    if let Some(step) = self.pass.step(columns, &mut self.aux, &self.keys) {
+                        return step;
+                    }
+
+                    // After scattering, starts holds where each digit's
+                    // region ends. Split into one range per region, with
+                    // the smallest digit on top of the stack.
+                    let pass = &self.pass;
+                    if pass.position > 0 {
+                        for d in (0..pass.base).rev() {
+                            let start = if d == 0 { pass.low } else { pass.starts[d - 1] };
+                            if pass.starts[d] - start > 1 {
+                                self.ranges.push((start, pass.starts[d], pass.position - 1));
+                            }
+                        }
+                    }
+                    self.phase = Phase::Gather;
+                }
+                Phase::Gather => match self.ranges.pop() {
+                    Some((low, high, position)) => {
+                        self.pass.start(low, high, position, RADIX_BASES[self.in_use]);
+                        self.phase = Phase::Count;
+                    }
+                    None => self.phase = Phase::Done,
+                },
+                _ => return Step::Done,
+            }
+        }
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        vec![Panel::Histogram(&self.pass.counts, self.pass.active()), Panel::Aux(&self.aux)]
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        if self.phase == Phase::Count {
+            vec![Marker::Range(self.pass.low, self.pass.high)]
+        } else {
+            vec![]
+        }
+    }
+
+    fn option(&self) -> Option<String> {
+        Some(format!("base: {}", RADIX_BASES[self.base]))
+    }
+
+    fn next_option(&mut self) {
+        self.base = (self.base + 1) % RADIX_BASES.len();
+    }
+
+    fn status(&self) -> Option<String> {
+        (self.phase == Phase::Count).then(|| format!("digit: {}", self.pass.position))
+    }
+}
+
+/// [American Flag Sort]
+/// An in-place MSD radix sort. After counting the digits of a range,
+/// each region is filled by cycling elements directly into the region
+/// their digit belongs to, with one swap per misplaced element. Next
+/// holds where each region will be filled next, and end where it ends.
+#[derive(Clone, Default)]
+pub struct AmericanFlagSort {
+    base: usize,
+    in_use: usize,
+    phase: Phase,
+    scan: RangeScan,
+    keys: Keys,
+    ranges: Vec<(usize, usize, u32)>,
+    low: usize,
+    high: usize,
+    position: u32,
+    pointer: usize,
+    counts: Vec<usize>,
+    next: Vec<usize>,
+    end: Vec<usize>,
+    region: usize,
+    digit: usize,
+}
+
+impl AmericanFlagSort {
+    fn digit_of(&self, value: i32) -> usize {
+        digit(self.keys.key(value), self.position, RADIX_BASES[self.in_use])
+    }
+}
+
+impl SortAlgorithm for AmericanFlagSort {
+    fn reset(&mut self) {
+        *self = AmericanFlagSort { base: self.base, ..AmericanFlagSort::default() };
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            let base = RADIX_BASES[self.in_use];
+
+            match self.phase {
+                Phase::Scan => {
+                    if let Some(step) = self.scan.step(columns) {
+                        return step;
+                    }
+                    if columns.is_empty() {
+                        self.phase = Phase::Done;
+                        continue;
+                    }
+                    self.keys = self.scan.keys(direction);
+                    self.in_use = self.base;
+                    let top = self.keys.digits(RADIX_BASES[self.in_use]) - 1;
+                    self.ranges.push((0, columns.len(), top));
+                    self.phase = Phase::Gather;
+                }
+                Phase::Gather => match self.ranges.pop() {
+                    Some((low, high, position)) => {
+                        (self.low, self.high, self.position) = (low, high, position);
+                        self.counts = vec![0; base];
+                        self.pointer = low;
+                        self.phase = Phase::Count;
+                    }
+                    None => self.phase = Phase::Done,
+                },
+                Phase::Count => {
+                    let i = self.pointer;
+                    if i < self.high {
+                        self.digit = self.digit_of(columns.read(i));
+                        self.counts[self.digit] += 1;
+                        self.pointer += 1;
+                        return Step::Read(i);
+                    }
+
+                    self.next = vec![self.low; base];
+                    for d in 1..base {
+                        self.next[d] = self.next[d - 1] + self.counts[d - 1];
+                    }
+                    self.end = (0..base).map(|d| self.next[d] + self.counts[d]).collect();
+                    self.region = 0;
+                    self.phase = Phase::Scatter;
+                }
+                Phase::Scatter => {
+                    while self.region < base && self.next[self.region] == self.end[self.region] {
+                        self.region += 1;
+                    }
+
+                    if self.region == base {
+                        // Every region is filled, so sort each on the
+                        // next digit down, smallest digit first.
+                        if self.position > 0 {
+                            for d in (0..base).rev() {
+                                let start = if d == 0 { self.low } else { self.end[d - 1] };
+                                if self.end[d] - start > 1 {
+                                    self.ranges.push((start, self.end[d], self.position - 1));
+                                }
+                            }
+                        }
+                        self.phase = Phase::Gather;
+                        continue;
+                    }
+
+                    let i = self.next[self.region];
+                    self.digit = self.digit_of(columns.read(i));
+                    self.counts[self.digit] -= 1;
+
+                    if self.digit == self.region {
+                        self.next[self.region] += 1;
+                        return Step::Read(i);
+                    }
+
+                    let j = self.next[self.digit];
+                    self.next[self.digit] += 1;
+                    columns.swap(i, j);
+                    return Step::Swap(i, j);
+                }
+                _ => return Step::Done,
+            }
+        }
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        let active = matches!(self.phase, Phase::Count | Phase::Scatter).then_some(self.digit);
+        vec![Panel::Histogram(&self.counts, active)]
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        if self.phase != Phase::Scatter {
+            return vec![];
+        }
+
+        let mut markers = vec![Marker::Range(self.low, self.high)];
+        markers.extend(self.end.iter().map(|&end| Marker::Boundary(end)));
+        markers
+    }
+
+    fn option(&self) -> Option<String> {
+        Some(format!("base: {}", RADIX_BASES[self.base]))
+    }
+
+    fn next_option(&mut self) {
+        self.base = (self.base + 1) % RADIX_BASES.len();
+    }
+
+    fn status(&self) -> Option<String> {
+        matches!(self.phase, Phase::Count | Phase::Scatter).then(|| format!("digit: {}", self.position))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{shuffled, sort, sorted};
+    use super::super::Constructor;
+    use super::*;
+
+    #[test]
+    fn every_setting_sorts() {
+        let algorithms: [(Constructor, usize); 6] = [
+            (|| Box::new(CountingSort::default()), 1),
+            (|| Box::new(PigeonholeSort::default()), 1),
+            (|| Box::new(BucketSort::default()), BUCKET_COUNTS.len()),
+            (|| Box::new(LsdRadixSort::default()), RADIX_BASES.len()),
+            (|| Box::new(MsdRadixSort::default()), RADIX_BASES.len()),
+            (|| Box::new(AmericanFlagSort::default()), RADIX_BASES.len()),
+        ];
+        let inputs = [shuffled(150), (500..650).collect(), vec![-4; 20], vec![]];
+        for (create, settings) in algorithms {
+            for setting in 0..settings {
+                for columns in &inputs {
+                    for direction in [1, -1] {
+                        let mut algorithm = create();
+                        for _ in 0..setting {
+                            algorithm.next_option();
+                        }
+                        assert_eq!(sort(algorithm.as_mut(), columns, direction), sorted(columns, direction), "{:?}", algorithm.option());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn keys_span_all_of_i32() {
+        let keys = Keys { min: i32::MIN, max: i32::MAX, direction: -1 };
+        assert_eq!(keys.range(), 1 << 32);
+        assert_eq!((keys.key(i32::MAX), keys.key(i32::MIN)), (0, u32::MAX as usize));
+        assert_eq!((keys.value(0), keys.value(u32::MAX as usize)), (i32::MAX, i32::MIN));
+
+        // Only the sorts which don't need room for every key can take
+        // them on.
+        let columns = [i32::MAX, 0, i32::MIN, -1, 1, i32::MIN + 1];
+        let algorithms: [Box<dyn SortAlgorithm>; 4] = [Box::new(BucketSort::default()), Box::new(LsdRadixSort::default()), Box::new(MsdRadixSort::default()), Box::new(AmericanFlagSort::default())];
+        for mut algorithm in algorithms {
+            assert_eq!(sort(algorithm.as_mut(), &columns, 1), sorted(&columns, 1));
+        }
+    }
+}
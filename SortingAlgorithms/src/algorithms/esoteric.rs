@@ -0,0 +1,521 @@
+/*****************************************************************/
+//! [Esoteric Sorts]
+/*****************************************************************/
+//!
+//! Sorts which are mostly here for fun, and as a contrast to the
+//! efficient ones. Some of them are jokes which could take longer
+//! than the age of the universe on a hundred columns, so they set a
+//! limit on how many columns they will run on without asking. The
+//! others are just unusual: pancake sort can only ever reverse a
+//! prefix of the columns, and bead sort lets beads fall on an abacus.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{Array, Bead, Marker, SortAlgorithm, Step};
+
+// The most levels bead sort will thread rods across, as every level
+// needs a rod as long as the columns.
+const MOST_LEVELS: i64 = 1 << 12;
+
+/// [Compare Swap]
+/// Compares the columns at i and j, where i comes first, and swaps
+/// them if they are out of order.
+fn compare_swap(columns: &mut Array, i: usize, j: usize, direction: i32) -> Step {
+    if columns.compare(i, j, direction) {
+        Step::Compare(i, j)
+    } else {
+        columns.swap(i, j);
+        Step::Swap(i, j)
+    }
+}
+
+/// [Bogosort]
+/// Checks whether the columns are sorted, and if they aren't,
+/// shuffles them and checks again. The shuffle is a Fisher-Yates
+/// shuffle done one swap per step, from the last column down, where
+/// shuffling is the column about to be swapped.
+#[derive(Clone)]
+pub struct BogoSort {
+    rng: StdRng,
+    pointer: usize,
+    shuffling: Option<usize>,
+    shuffles: usize,
+}
+
+impl Default for BogoSort {
+    fn default() -> Self {
+        BogoSort { rng: StdRng::from_entropy(), pointer: 0, shuffling: None, shuffles: 0 }
+    }
+}
+
+impl SortAlgorithm for BogoSort {
+    fn reset(&mut self) {
+        *self = BogoSort::default();
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if let Some(i) = self.shuffling {
+            let j = self.rng.gen_range(0..=i);
+            columns.swap(i, j);
+            self.shuffling = if i > 1 { Some(i - 1) } else { None };
+            self.pointer = 0;
+            return Step::Swap(i, j);
+        }
+
+        let i = self.pointer;
+        if i + 1 >= columns.len() {
+            return Step::Done;
+        }
+
+        if columns.compare(i, i + 1, direction) {
+            self.pointer += 1;
+        } else {
+            self.shuffling = Some(columns.len() - 1);
+            self.shuffles += 1;
+        }
+        Step::Compare(i, i + 1)
+    }
+
+    fn limit(&self) -> Option<usize> {
+        Some(8)
+    }
+
+    fn status(&self) -> Option<String> {
+        Some(format!("shuffles: {}", self.shuffles))
+    }
+}
+
+/// [Bozosort]
+/// Bogosort's even less ambitious cousin. Rather than shuffling all of
+/// the columns when they turn out not to be sorted, it swaps just two
+/// at random, and then checks all over again.
+#[derive(Clone)]
+pub struct BozoSort {
+    rng: StdRng,
+    pointer: usize,
+    swapping: bool,
+    swaps: usize,
+}
+
+impl Default for BozoSort {
+    fn default() -> Self {
+        BozoSort { rng: StdRng::from_entropy(), pointer: 0, swapping: false, swaps: 0 }
+    }
+}
+
+impl SortAlgorithm for BozoSort {
+    fn reset(&mut self) {
+        *self = BozoSort::default();
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if self.swapping {
+            let i = self.rng.gen_range(0..columns.len());
+            let j = self.rng.gen_range(0..columns.len());
+            columns.swap(i, j);
+            self.swapping = false;
+            self.pointer = 0;
+            return Step::Swap(i, j);
+        }
+
+        let i = self.pointer;
+        if i + 1 >= columns.len() {
+            return Step::Done;
+        }
+
+        if columns.compare(i, i + 1, direction) {
+            self.pointer += 1;
+        } else {
+            self.swapping = true;
+            self.swaps += 1;
+        }
+        Step::Compare(i, i + 1)
+    }
+
+    fn limit(&self) -> Option<usize> {
+        Some(8)
+    }
+
+    fn status(&self) -> Option<String> {
+        Some(format!("swaps: {}", self.swaps))
+    }
+}
+
+/// [Stooge Sort]
+/// Puts the first and last elements in order, then sorts the first
+/// two thirds, the last two thirds, and the first two thirds again.
+/// It runs in O(n^2.71) time, which is slower than even bubble sort.
+/// The calls still to be made are kept on a stack as inclusive
+/// (first, last) pairs, and each call makes exactly one comparison.
+#[derive(Clone, Default)]
+pub struct StoogeSort {
+    calls: Vec<(usize, usize)>,
+    range: Option<(usize, usize)>,
+    count: usize,
+    started: bool,
+}
+
+impl SortAlgorithm for StoogeSort {
+    fn reset(&mut self) {
+        *self = StoogeSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            if columns.len() > 1 {
+                self.calls.push((0, columns.len() - 1));
+            }
+        }
+
+        let Some((first, last)) = self.calls.pop() else {
+            self.range = None;
+            return Step::Done;
+        };
+
+        let step = compare_swap(columns, first, last, direction);
+        let len = last - first + 1;
+        if len > 2 {
+            // Pushed backwards, so that they are popped in order.
+            let third = len / 3;
+            self.calls.push((first, last - third));
+            self.calls.push((first + third, last));
+            self.calls.push((first, last - third));
+        }
+
+        self.range = Some((first, last));
+        self.count += 1;
+        step
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        match self.range {
+            Some((first, last)) => vec![Marker::Range(first, last + 1)],
+            None => vec![],
+        }
+    }
+
+    fn limit(&self) -> Option<usize> {
+        Some(64)
+    }
+
+    fn status(&self) -> Option<String> {
+        Some(format!("calls: {}", self.count))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum SlowCall {
+    Sort(usize, usize),
+    Fix(usize, usize, usize),
+}
+
+/// [Slowsort]
+/// A deliberately terrible sort in the spirit of "multiply and
+/// surrender". To sort a range, it sorts both halves, moves the
+/// bigger of their two maximums to the end, and then sorts everything
+/// but the end all over again. It takes longer than any polynomial.
+/// Calls are kept on a stack of inclusive ranges, where Fix is what
+/// remains to be done of a call once both of its halves are sorted.
+#[derive(Clone, Default)]
+pub struct SlowSort {
+    calls: Vec<SlowCall>,
+    range: Option<(usize, usize)>,
+    started: bool,
+}
+
+impl SortAlgorithm for SlowSort {
+    fn reset(&mut self) {
+        *self = SlowSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            if columns.len() > 1 {
+                self.calls.push(SlowCall::Sort(0, columns.len() - 1));
+            }
+        }
+
+        loop {
+            let Some(call) = self.calls.pop() else {
+                self.range = None;
+                return Step::Done;
+            };
+
+            match call {
+                SlowCall::Sort(first, last) => {
+                    if first < last {
+                        let mid = (first + last) / 2;
+                        self.calls.push(SlowCall::Fix(first, mid, last));
+                        self.calls.push(SlowCall::Sort(mid + 1, last));
+                        self.calls.push(SlowCall::Sort(first, mid));
+                    }
+                }
+                SlowCall::Fix(first, mid, last) => {
+                    self.calls.push(SlowCall::Sort(first, last - 1));
+                    self.range = Some((first, last));
+                    return compare_swap(columns, mid, last, direction);
+                }
+            }
+        }
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        match self.range {
+            Some((first, last)) => vec![Marker::Range(first, last + 1)],
+            None => vec![],
+        }
+    }
+
+    fn limit(&self) -> Option<usize> {
+        Some(48)
+    }
+
+    fn status(&self) -> Option<String> {
+        if self.started {
+            Some(format!("depth: {}", self.calls.len()))
+        } else {
+            None
+        }
+    }
+}
+
+/// [Pancake Sort]
+/// Sorts a stack of pancakes with a spatula, which can only flip the
+/// top of the stack over. The largest pancake of the unsorted stack
+/// is found, flipped to the top, and then the whole unsorted stack is
+/// flipped so it lands at the bottom. Each flip is a single step,
+/// reversing a prefix of the columns at once. The unsorted stack is
+/// 0..size, and flip is the length of a flip still to be made.
+#[derive(Clone, Default)]
+pub struct PancakeSort {
+    size: usize,
+    pointer: usize,
+    largest: usize,
+    flip: Option<usize>,
+    flips: usize,
+    started: bool,
+}
+
+impl PancakeSort {
+    /// [Flip]
+    /// Flips the top len pancakes over.
+    fn flip(&mut self, columns: &mut Array, len: usize) -> Step {
+        columns.reverse(0, len);
+        self.flips += 1;
+        Step::Reverse(0, len)
+    }
+}
+
+impl SortAlgorithm for PancakeSort {
+    fn reset(&mut self) {
+        *self = PancakeSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            self.size = columns.len();
+            self.pointer = 1;
+        }
+
+        loop {
+            if let Some(len) = self.flip.take() {
+                return self.flip(columns, len);
+            }
+
+            if self.size < 2 {
+                return Step::Done;
+            }
+
+            // Look for the largest pancake left.
+            if self.pointer < self.size {
+                let (largest, i) = (self.largest, self.pointer);
+                if columns.compare(largest, i, direction) {
+                    self.largest = i;
+                }
+                self.pointer += 1;
+                return Step::Compare(largest, i);
+            }
+
+            let (size, largest) = (self.size, self.largest);
+            self.size -= 1;
+            self.pointer = 1;
+            self.largest = 0;
+
+            if largest == size - 1 {
+                continue;
+            }
+            self.flip = Some(size);
+            if largest > 0 {
+                return self.flip(columns, largest + 1);
+            }
+        }
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        if self.size > 1 {
+            vec![Marker::Range(0, self.size), Marker::Pivot(self.largest)]
+        } else {
+            vec![]
+        }
+    }
+
+    fn status(&self) -> Option<String> {
+        Some(format!("flips: {}", self.flips))
+    }
+}
+
+/// [Bead Sort]
+/// Gravity sort. Each column is a stack of beads, one for each unit
+/// of its value, threaded onto rods which run across the columns at
+/// every level. Gravity pulls the beads along their rods toward the
+/// end the largest values belong at, and once they have all settled,
+/// every column holds a sorted value. The beads all fall at once, one
+/// place per step, where a bead falls if the place in front of it was
+/// empty at the start of the step. Values are counted from the lowest
+/// of zero and the smallest value, so negative values still work.
+#[derive(Clone, Default)]
+pub struct BeadSort {
+    rods: Vec<Vec<bool>>,
+    falling: Vec<Vec<bool>>,
+    base: i32,
+    steps: usize,
+    started: bool,
+    settled: bool,
+}
+
+impl SortAlgorithm for BeadSort {
+    fn reset(&mut self) {
+        *self = BeadSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            let values: Vec<i32> = (0..columns.len()).map(|i| columns.read(i)).collect();
+            self.base = values.iter().copied().min().unwrap_or(0).min(0);
+            let top = values.iter().copied().max().unwrap_or(0);
+            self.rods = (0..top - self.base).map(|level| values.iter().map(|&value| value - self.base > level).collect()).collect();
+            self.falling = self.rods.iter().map(|rod| vec![false; rod.len()]).collect();
+        }
+
+        if self.settled {
+            return Step::Done;
+        }
+
+        let len = columns.len();
+        let mut moved = false;
+        for (rod, falling) in self.rods.iter_mut().zip(self.falling.iter_mut()) {
+            let before = rod.clone();
+            falling.fill(false);
+            for i in 0..len.saturating_sub(1) {
+                let (from, to) = if direction > 0 { (i, i + 1) } else { (i + 1, i) };
+                if before[from] && !before[to] {
+                    rod[from] = false;
+                    rod[to] = true;
+                    falling[to] = true;
+                    moved = true;
+                }
+            }
+        }
+
+        if !moved {
+            self.settled = true;
+            return Step::Done;
+        }
+
+        // Each column's value is however many beads it now holds.
+        let mut first = None;
+        for i in 0..len {
+            let value = self.base + self.rods.iter().filter(|rod| rod[i]).count() as i32;
+            if columns.values()[i] != value {
+                columns.write(i, value);
+                first = first.or(Some(i));
+            }
+        }
+        self.steps += 1;
+        Step::Write(first.unwrap_or(0))
+    }
+
+    fn beads(&self) -> Vec<Bead> {
+        let mut beads = vec![];
+        for (level, (rod, falling)) in self.rods.iter().zip(&self.falling).enumerate() {
+            for (position, (&bead, &falling)) in rod.iter().zip(falling).enumerate() {
+                if bead {
+                    beads.push(Bead { position, value: self.base + level as i32 + 1, falling });
+                }
+            }
+        }
+        beads
+    }
+
+    fn refusal(&self, values: &[i32]) -> Option<String> {
+        let base = values.iter().copied().min()?.min(0);
+        let levels = values.iter().copied().max()? as i64 - base as i64;
+        (levels > MOST_LEVELS).then(|| format!("values span {} levels, more than the {} it has rods for", levels, MOST_LEVELS))
+    }
+
+    fn status(&self) -> Option<String> {
+        if self.started {
+            Some(format!("falls: {}", self.steps))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{shuffled, sort, sorted};
+    use super::super::Trace;
+    use super::*;
+
+    #[test]
+    fn pancake_sort_only_flips_prefixes() {
+        let mut columns = shuffled(40);
+        let mut algorithm = PancakeSort::default();
+        let mut trace = Trace::default();
+        loop {
+            match trace.step(&mut algorithm, &mut columns, 1) {
+                Step::Done => break,
+                Step::Compare(..) | Step::Reverse(0, _) => {}
+                step => panic!("pancake sort can't {:?}", step),
+            }
+        }
+        assert_eq!(columns, sorted(&columns, 1));
+    }
+
+    #[test]
+    fn bead_sort_counts_from_below_zero() {
+        for columns in [shuffled(40), vec![-3, -7, -1, -7], vec![0, 0, 0]] {
+            for direction in [1, -1] {
+                assert_eq!(sort(&mut BeadSort::default(), &columns, direction), sorted(&columns, direction));
+            }
+        }
+    }
+
+    #[test]
+    fn only_the_hopeless_sorts_have_limits() {
+        let algorithms: [Box<dyn SortAlgorithm>; 6] = [
+            Box::new(BogoSort::default()),
+            Box::new(BozoSort::default()),
+            Box::new(StoogeSort::default()),
+            Box::new(SlowSort::default()),
+            Box::new(PancakeSort::default()),
+            Box::new(BeadSort::default()),
+        ];
+        let limits: Vec<Option<usize>> = algorithms.iter().map(|algorithm| algorithm.limit()).collect();
+        assert_eq!(limits, [Some(8), Some(8), Some(64), Some(48), None, None]);
+    }
+}
@@ -0,0 +1,361 @@
+/*****************************************************************/
+//! [Gap Sorts]
+/*****************************************************************/
+//!
+//! Shell sort and comb sort both speed up a simple quadratic sort by
+//! first comparing elements which are far apart, so that elements
+//! can travel a long way in a single swap. How quickly the gap
+//! shrinks makes an enormous difference, so the sequence or shrink
+//! factor can be changed while the sort is running.
+
+use super::{Array, SortAlgorithm, Step};
+
+/// [Gap Sequence]
+/// The gap sequences Shell sort can use.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum GapSequence {
+    Shell,
+    Knuth,
+    Sedgewick,
+    #[default]
+    Ciura,
+    Tokuda,
+    Pratt,
+}
+
+impl GapSequence {
+    const ALL: [GapSequence; 6] = [
+        GapSequence::Shell,
+        GapSequence::Knuth,
+        GapSequence::Sedgewick,
+        GapSequence::Ciura,
+        GapSequence::Tokuda,
+        GapSequence::Pratt,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            GapSequence::Shell => "Shell",
+            GapSequence::Knuth => "Knuth",
+            GapSequence::Sedgewick => "Sedgewick",
+            GapSequence::Ciura => "Ciura",
+            GapSequence::Tokuda => "Tokuda",
+            GapSequence::Pratt => "Pratt",
+        }
+    }
+
+    /// [Gaps]
+    /// Every gap of the sequence below len, largest first, always
+    /// ending with a gap of one.
+    fn gaps(&self, len: usize) -> Vec<usize> {
+        let mut gaps: Vec<usize> = match self {
+            GapSequence::Shell => {
+                let mut gaps = vec![];
+                let mut gap = len / 2;
+                while gap > 0 {
+                    gaps.push(gap);
+                    gap /= 2;
+                }
+                gaps
+            }
+            GapSequence::Knuth => {
+                let mut gaps = vec![];
+                let mut gap = 1;
+                while gap < len {
+                    gaps.push(gap);
+                    gap = 3 * gap + 1;
+                }
+                gaps
+            }
+            GapSequence::Sedgewick => {
+                // 4^k + 3 * 2^(k - 1) + 1, with 1 in front.
+                let mut gaps = vec![1];
+                let mut k = 1;
+                loop {
+                    let gap = 4usize.pow(k) + 3 * 2usize.pow(k - 1) + 1;
+                    if gap >= len {
+                        break gaps;
+                    }
+                    gaps.push(gap);
+                    k += 1;
+                }
+            }
+            GapSequence::Ciura => {
+                // The known best gaps, extended by a factor of 2.25.
+                let mut gaps = vec![1, 4, 10, 23, 57, 132, 301, 701, 1750];
+                while *gaps.last().unwrap() < len {
+                    let next = (*gaps.last().unwrap() as f64 * 2.25) as usize;
+                    gaps.push(next);
+                }
+                gaps
+            }
+            GapSequence::Tokuda => {
+                // ceil((9^k - 4^k) / (5 * 4^(k - 1)))
+                let mut gaps = vec![];
+                let mut k = 1;
+                loop {
+                    let gap = ((9f64.powi(k) - 4f64.powi(k)) / (5.0 * 4f64.powi(k - 1))).ceil() as usize;
+                    if gap >= len && !gaps.is_empty() {
+                        break gaps;
+                    }
+                    gaps.push(gap);
+                    k += 1;
+                }
+            }
+            GapSequence::Pratt => {
+                // Every 3-smooth number, 2^p * 3^q.
+                let mut gaps = vec![];
+                let mut power_of_three = 1;
+                while power_of_three < len.max(2) {
+                    let mut gap = power_of_three;
+                    while gap < len.max(2) {
+                        gaps.push(gap);
+                        gap *= 2;
+                    }
+                    power_of_three *= 3;
+                }
+                gaps
+            }
+        };
+
+        gaps.retain(|&gap| gap < len.max(2));
+        gaps.sort_unstable_by(|a, b| b.cmp(a));
+        gaps.dedup();
+        gaps
+    }
+}
+
+/// [Shell Sort]
+/// An insertion sort done several times over, each pass only looking
+/// at elements gap apart. The final pass has a gap of one, and is an
+/// ordinary insertion sort over an almost sorted array. Passes use
+/// the same sinking by swaps as InsertionSort, where next is the
+/// element being inserted in this pass and pointer is where it has
+/// sunk to.
+#[derive(Clone, Default)]
+pub struct ShellSort {
+    sequence: GapSequence,
+    gaps: Vec<usize>,
+    len: usize,
+    gap: usize,
+    next: usize,
+    pointer: usize,
+    started: bool,
+}
+
+impl ShellSort {
+    /// [Start Pass]
+    /// Moves on to the next pass with the given gap.
+    fn start_pass(&mut self, gap: usize) {
+        self.gap = gap;
+        self.next = gap;
+        self.pointer = gap;
+    }
+}
+
+impl SortAlgorithm for ShellSort {
+    fn reset(&mut self) {
+        *self = ShellSort { sequence: self.sequence, ..ShellSort::default() };
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            self.len = columns.len();
+            self.gaps = self.sequence.gaps(self.len);
+            self.gaps.reverse();
+            self.gap = 0;
+            self.next = self.len;
+        }
+
+        loop {
+            if self.next >= columns.len() {
+                // This pass is finished, move on to the next gap.
+                match self.gaps.pop() {
+                    Some(gap) => self.start_pass(gap),
+                    None => return Step::Done,
+                }
+                continue;
+            }
+
+            let gap = self.gap;
+            if self.pointer < gap {
+                self.next += 1;
+                self.pointer = self.next;
+                continue;
+            }
+
+            let (i, j) = (self.pointer - gap, self.pointer);
+            if columns.compare(i, j, direction) {
+                self.next += 1;
+                self.pointer = self.next;
+                return Step::Compare(i, j);
+            }
+
+            columns.swap(i, j);
+            self.pointer -= gap;
+            return Step::Swap(i, j);
+        }
+    }
+
+    fn option(&self) -> Option<String> {
+        Some(format!("gaps: {}", self.sequence.name()))
+    }
+
+    /// [Next Option]
+    /// Switches to the next gap sequence. The pass in progress is cut
+    /// short, and the sort carries on with the gaps of the new
+    /// sequence which are smaller than the gap in use. The final pass
+    /// with a gap of one is never cut short.
+    fn next_option(&mut self) {
+        let index = GapSequence::ALL.iter().position(|&sequence| sequence == self.sequence).unwrap();
+        self.sequence = GapSequence::ALL[(index + 1) % GapSequence::ALL.len()];
+
+        if !self.started || self.gap == 1 {
+            return;
+        }
+
+        let below = if self.gap == 0 { self.len } else { self.gap };
+        self.gaps = self.sequence.gaps(below);
+        self.gaps.reverse();
+        self.next = self.len;
+    }
+
+    fn status(&self) -> Option<String> {
+        if self.started && self.gap > 0 {
+            Some(format!("gap: {}", self.gap))
+        } else {
+            None
+        }
+    }
+}
+
+/// [Shrink Factors]
+/// The shrink factors comb sort can cycle through. 1.3 is the one
+/// usually recommended.
+const SHRINK_FACTORS: [f64; 5] = [1.3, 1.25, 1.5, 2.0, 1.1];
+
+/// [Comb Sort]
+/// Bubble sort with a gap. Each pass compares elements gap apart, and
+/// the gap is divided by the shrink factor after every pass, until it
+/// reaches one. Passes with a gap of one repeat until nothing moves.
+#[derive(Clone, Default)]
+pub struct CombSort {
+    shrink: usize,
+    gap: usize,
+    pointer: usize,
+    swapped: bool,
+    started: bool,
+}
+
+impl SortAlgorithm for CombSort {
+    fn reset(&mut self) {
+        *self = CombSort { shrink: self.shrink, ..CombSort::default() };
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            self.gap = columns.len();
+            self.pointer = columns.len();
+            self.swapped = true;
+        }
+
+        loop {
+            if self.pointer + self.gap >= columns.len() {
+                // The pass is over. Once a pass with a gap of one has
+                // not swapped anything, the columns are sorted.
+                if self.gap <= 1 && !self.swapped {
+                    return Step::Done;
+                }
+                self.gap = ((self.gap as f64 / SHRINK_FACTORS[self.shrink]) as usize).max(1);
+                self.pointer = 0;
+                self.swapped = false;
+
+                if self.gap >= columns.len() {
+                    return Step::Done;
+                }
+                continue;
+            }
+
+            let (i, j) = (self.pointer, self.pointer + self.gap);
+            self.pointer += 1;
+
+            if columns.compare(i, j, direction) {
+                return Step::Compare(i, j);
+            }
+
+            columns.swap(i, j);
+            self.swapped = true;
+            return Step::Swap(i, j);
+        }
+    }
+
+    fn option(&self) -> Option<String> {
+        Some(format!("shrink factor: {}", SHRINK_FACTORS[self.shrink]))
+    }
+
+    fn next_option(&mut self) {
+        self.shrink = (self.shrink + 1) % SHRINK_FACTORS.len();
+    }
+
+    fn status(&self) -> Option<String> {
+        if self.started {
+            Some(format!("gap: {}", self.gap))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{shuffled, sort, sorted};
+    use super::super::Constructor;
+    use super::super::Trace;
+    use super::*;
+
+    #[test]
+    fn gap_sequences_shrink_to_one() {
+        for sequence in GapSequence::ALL {
+            for len in [2, 10, 100, 1000] {
+                let gaps = sequence.gaps(len);
+                assert_eq!(gaps.last(), Some(&1), "{}", sequence.name());
+                assert!(gaps.windows(2).all(|pair| pair[0] > pair[1]), "{}", sequence.name());
+                assert!(gaps.iter().all(|&gap| gap < len), "{}", sequence.name());
+            }
+        }
+    }
+
+    #[test]
+    fn every_setting_sorts() {
+        let columns = shuffled(300);
+        let algorithms: [(Constructor, usize); 2] = [(|| Box::new(ShellSort::default()), GapSequence::ALL.len()), (|| Box::new(CombSort::default()), SHRINK_FACTORS.len())];
+        for (create, settings) in algorithms {
+            for setting in 0..settings {
+                let mut algorithm = create();
+                for _ in 0..setting {
+                    algorithm.next_option();
+                }
+                assert_eq!(sort(algorithm.as_mut(), &columns, -1), sorted(&columns, -1), "{:?}", algorithm.option());
+            }
+        }
+    }
+
+    #[test]
+    fn switching_settings_mid_sort_still_sorts() {
+        for mut algorithm in [Box::new(ShellSort::default()) as Box<dyn SortAlgorithm>, Box::new(CombSort::default())] {
+            let mut columns = shuffled(300);
+            let mut trace = Trace::default();
+            for step in 0.. {
+                if step % 250 == 0 {
+                    algorithm.next_option();
+                }
+                if trace.step(algorithm.as_mut(), &mut columns, 1) == Step::Done {
+                    break;
+                }
+            }
+            assert_eq!(columns, sorted(&columns, 1), "{:?}", algorithm.option());
+        }
+    }
+}
@@ -0,0 +1,518 @@
+/*****************************************************************/
+//! [Heap Sorts]
+/*****************************************************************/
+//!
+//! Heap sorts arrange the columns into an implicit tree, so that the
+//! element which belongs last can always be found at a known root.
+//! Sifting a node down one level takes a handful of comparisons and
+//! at most one swap, so these algorithms decide a level at a time and
+//! hand the operations to an OpQueue to be shown one per step.
+
+use super::{Array, in_order, HeapLink, OpQueue, SortAlgorithm, Step};
+
+/// [After]
+/// True when a must strictly come after b in the given direction.
+/// Heaps keep the element which comes last at their root.
+fn after(a: i32, b: i32, direction: i32) -> bool {
+    !in_order(a, b, direction)
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Phase {
+    #[default]
+    Start,
+    Build,
+    Extract,
+    Finish,
+    Done,
+}
+
+/// [Heap Sort]
+/// Heapsort over a D-ary heap, where the children of node i are at
+/// D * i + 1 up to D * i + D. The heap is built bottom-up by sifting
+/// down every parent, starting from the last. Then the root is
+/// repeatedly swapped to the end of the heap, which shrinks by one,
+/// and the new root is sifted back down.
+#[derive(Clone, Default)]
+pub struct HeapSort<const D: usize> {
+    queue: OpQueue,
+    phase: Phase,
+    next: usize,
+    size: usize,
+    sifting: Option<usize>,
+}
+
+pub type BinaryHeapSort = HeapSort<2>;
+pub type TernaryHeapSort = HeapSort<3>;
+
+impl<const D: usize> HeapSort<D> {
+    /// [Sift]
+    /// Decides one level of sifting the current node down, queueing a
+    /// comparison with each child and the swap with the best of them.
+    fn sift(&mut self, node: usize, columns: &[i32], direction: i32) {
+        let mut best = node;
+
+        for child in (D * node + 1..=D * node + D).take_while(|&child| child < self.size) {
+            self.queue.compare(child, best);
+            if after(columns[child], columns[best], direction) {
+                best = child;
+            }
+        }
+
+        if best == node {
+            self.sifting = None;
+        } else {
+            self.queue.swap(node, best);
+            self.sifting = Some(best);
+        }
+    }
+}
+
+impl<const D: usize> SortAlgorithm for HeapSort<D> {
+    fn reset(&mut self) {
+        *self = HeapSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            if let Some(step) = self.queue.pop(columns) {
+                return step;
+            }
+
+            if let Some(node) = self.sifting {
+                self.sift(node, columns.values(), direction);
+                continue;
+            }
+
+            match self.phase {
+                Phase::Start => {
+                    if columns.len() < 2 {
+                        return Step::Done;
+                    }
+                    self.size = columns.len();
+                    self.next = (self.size - 2) / D + 1;
+                    self.phase = Phase::Build;
+                }
+                Phase::Build => {
+                    if self.next == 0 {
+                        self.phase = Phase::Extract;
+                    } else {
+                        self.next -= 1;
+                        self.sifting = Some(self.next);
+                    }
+                }
+                Phase::Extract => {
+                    if self.size <= 1 {
+                        self.phase = Phase::Done;
+                    } else {
+                        self.size -= 1;
+                        self.queue.swap(0, self.size);
+                        self.sifting = Some(0);
+                    }
+                }
+                Phase::Finish | Phase::Done => return Step::Done,
+            }
+        }
+    }
+
+    fn heap_links(&self) -> Vec<HeapLink> {
+        if self.phase == Phase::Start {
+            return vec![];
+        }
+
+        let sifting = self.sifting;
+        (1..self.size)
+            .map(|child| {
+                let parent = (child - 1) / D;
+                let active = sifting == Some(parent) || sifting == Some(child);
+                HeapLink { parent, child, active }
+            })
+            .collect()
+    }
+}
+
+/// [Weak-Heap Sort]
+/// Dutton's weak-heap sort. A weak heap only promises that a node is
+/// not smaller than anything in its right subtree, and each node has
+/// a reverse bit which swaps its two children. That makes joining two
+/// weak heaps a single comparison, with the reverse bit flipped
+/// instead of moving whole subtrees around.
+#[derive(Clone, Default)]
+pub struct WeakHeapSort {
+    queue: OpQueue,
+    phase: Phase,
+    bits: Vec<bool>,
+    next: usize,
+    size: usize,
+    climbing: usize,
+    joining: Option<(usize, usize)>,
+}
+
+impl WeakHeapSort {
+    /// [Ancestor]
+    /// The distinguished ancestor of j, the closest ancestor which has
+    /// j in its right subtree.
+    fn ancestor(&self, mut j: usize) -> usize {
+        while (j & 1 == 1) == self.bits[j >> 1] {
+            j >>= 1;
+        }
+        j >> 1
+    }
+
+    /// [Join]
+    /// Makes i the root of the weak heap rooted at j, swapping them and
+    /// flipping the reverse bit of j if j belongs above i.
+    fn join(&mut self, i: usize, j: usize, columns: &[i32], direction: i32) {
+        self.queue.compare(i, j);
+        if after(columns[j], columns[i], direction) {
+            self.queue.swap(i, j);
+            self.bits[j] = !self.bits[j];
+        }
+        self.joining = Some((i, j));
+    }
+}
+
+impl SortAlgorithm for WeakHeapSort {
+    fn reset(&mut self) {
+        *self = WeakHeapSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            if let Some(step) = self.queue.pop(columns) {
+                return step;
+            }
+
+            // Climbing back up from the bottom of the leftmost path,
+            // joining each node on it with the root.
+            if self.climbing > 0 {
+                let x = self.climbing;
+                self.climbing >>= 1;
+                self.join(0, x, columns.values(), direction);
+                continue;
+            }
+
+            match self.phase {
+                Phase::Start => {
+                    if columns.len() < 2 {
+                        return Step::Done;
+                    }
+                    self.bits = vec![false; columns.len()];
+                    self.size = columns.len();
+                    self.next = columns.len() - 1;
+                    self.phase = Phase::Build;
+                }
+                Phase::Build => {
+                    if self.next == 0 {
+                        self.phase = Phase::Extract;
+                    } else {
+                        let j = self.next;
+                        self.next -= 1;
+                        let i = self.ancestor(j);
+                        self.join(i, j, columns.values(), direction);
+                    }
+                }
+                Phase::Extract => {
+                    self.size -= 1;
+                    if self.size < 2 {
+                        self.phase = Phase::Finish;
+                        continue;
+                    }
+                    self.queue.swap(0, self.size);
+
+                    // Walk down to the leftmost leaf of the root's tree.
+                    let mut x = 1;
+                    while 2 * x + (self.bits[x] as usize) < self.size {
+                        x = 2 * x + self.bits[x] as usize;
+                    }
+                    self.climbing = x;
+                    self.joining = None;
+                }
+                Phase::Finish => {
+                    self.phase = Phase::Done;
+                    self.joining = None;
+                    self.queue.swap(0, 1);
+                }
+                Phase::Done => return Step::Done,
+            }
+        }
+    }
+
+    fn heap_links(&self) -> Vec<HeapLink> {
+        if self.phase == Phase::Start || self.phase == Phase::Done {
+            return vec![];
+        }
+
+        let link = |parent, child| HeapLink { parent, child, active: self.joining == Some((parent, child)) };
+        let mut links = vec![];
+
+        if self.size > 1 {
+            links.push(link(0, 1));
+        }
+        for parent in 1..self.size {
+            let left = 2 * parent + self.bits[parent] as usize;
+            let right = 2 * parent + 1 - self.bits[parent] as usize;
+            links.extend([left, right].into_iter().filter(|&child| child < self.size).map(|child| link(parent, child)));
+        }
+
+        // The distinguished ancestor of a join is not always the direct
+        // parent, so show that relation too.
+        if let Some((parent, child)) = self.joining {
+            if !links.iter().any(|l| l.active) {
+                links.push(HeapLink { parent, child, active: true });
+            }
+        }
+
+        links
+    }
+}
+
+/// [Task]
+/// A piece of work for smoothsort to do on its forest of Leonardo
+/// heaps. Rectify moves the root of a tree leftwards past any larger
+/// roots, and Sift restores the heap property below a node.
+#[derive(Clone, Copy)]
+enum Task {
+    Rectify(usize),
+    Sift(usize, usize),
+}
+
+/// [Smoothsort]
+/// Dijkstra's smoothsort. The columns are split into a forest of
+/// Leonardo heaps, with orders kept left to right in orders, where a
+/// heap of order k holds leonardo[k] elements and has its root at its
+/// last index. Its children are the roots of heaps of order k - 1 and
+/// k - 2 directly before it. The roots are kept in order, so the
+/// element which belongs last is always the very last column.
+#[derive(Clone, Default)]
+pub struct SmoothSort {
+    queue: OpQueue,
+    phase: Phase,
+    leonardo: Vec<usize>,
+    orders: Vec<usize>,
+    tasks: Vec<Task>,
+    next: usize,
+    active: Vec<(usize, usize)>,
+}
+
+impl SmoothSort {
+    /// [Root]
+    /// The index of the root of the t-th heap in the forest.
+    fn root(&self, t: usize) -> usize {
+        self.orders[..=t].iter().map(|&k| self.leonardo[k]).sum::<usize>() - 1
+    }
+
+    /// [Children]
+    /// The roots and orders of the two sub-heaps of a heap of order k
+    /// rooted at r, as (left, left order, right, right order).
+    fn children(&self, r: usize, k: usize) -> (usize, usize, usize, usize) {
+        (r - 1 - self.leonardo[k - 2], k - 1, r - 1, k - 2)
+    }
+
+    fn work(&mut self, task: Task, columns: &[i32], direction: i32) {
+        match task {
+            Task::Rectify(t) => {
+                let r = self.root(t);
+                let k = self.orders[t];
+                self.active.clear();
+
+                if t == 0 {
+                    self.tasks.push(Task::Sift(r, k));
+                    return;
+                }
+
+                // The previous root only moves here if it belongs after
+                // this root and both of this root's children.
+                let previous = r - self.leonardo[k];
+                self.active.push((previous, r));
+                self.queue.compare(previous, r);
+                if !after(columns[previous], columns[r], direction) {
+                    self.tasks.push(Task::Sift(r, k));
+                    return;
+                }
+
+                if k >= 2 {
+                    let (left, _, right, _) = self.children(r, k);
+                    self.queue.compare(left, right);
+                    let biggest = if after(columns[right], columns[left], direction) { right } else { left };
+                    self.queue.compare(previous, biggest);
+                    if !after(columns[previous], columns[biggest], direction) {
+                        self.tasks.push(Task::Sift(r, k));
+                        return;
+                    }
+                }
+
+                self.queue.swap(previous, r);
+                self.tasks.push(Task::Rectify(t - 1));
+            }
+            Task::Sift(r, k) => {
+                self.active.clear();
+                if k < 2 {
+                    return;
+                }
+
+                let (left, left_order, right, right_order) = self.children(r, k);
+                self.active.extend([(r, left), (r, right)]);
+                self.queue.compare(left, right);
+                let (biggest, order) = if after(columns[right], columns[left], direction) {
+                    (right, right_order)
+                } else {
+                    (left, left_order)
+                };
+
+                self.queue.compare(biggest, r);
+                if after(columns[biggest], columns[r], direction) {
+                    self.queue.swap(biggest, r);
+                    self.tasks.push(Task::Sift(biggest, order));
+                }
+            }
+        }
+    }
+}
+
+impl SortAlgorithm for SmoothSort {
+    fn reset(&mut self) {
+        *self = SmoothSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            if let Some(step) = self.queue.pop(columns) {
+                return step;
+            }
+
+            if let Some(task) = self.tasks.pop() {
+                self.work(task, columns.values(), direction);
+                continue;
+            }
+
+            match self.phase {
+                Phase::Start => {
+                    if columns.len() < 2 {
+                        return Step::Done;
+                    }
+                    self.leonardo = vec![1, 1];
+                    while *self.leonardo.last().unwrap() < columns.len() {
+                        let k = self.leonardo.len();
+                        self.leonardo.push(self.leonardo[k - 1] + self.leonardo[k - 2] + 1);
+                    }
+                    self.phase = Phase::Build;
+                }
+                Phase::Build => {
+                    if self.next == columns.len() {
+                        self.phase = Phase::Extract;
+                        continue;
+                    }
+
+                    // Two heaps of neighbouring orders merge under the
+                    // new element, otherwise it becomes a heap alone.
+                    let n = self.orders.len();
+                    if n >= 2 && self.orders[n - 2] == self.orders[n - 1] + 1 {
+                        let k = self.orders[n - 2] + 1;
+                        self.orders.truncate(n - 2);
+                        self.orders.push(k);
+                    } else if self.orders.last() == Some(&1) {
+                        self.orders.push(0);
+                    } else {
+                        self.orders.push(1);
+                    }
+
+                    self.next += 1;
+                    self.tasks.push(Task::Rectify(self.orders.len() - 1));
+                }
+                Phase::Extract => {
+                    // The last element is in place, so drop it and
+                    // expose its two sub-heaps as heaps of the forest.
+                    self.next -= 1;
+                    if self.next == 0 {
+                        self.phase = Phase::Done;
+                        self.active.clear();
+                        continue;
+                    }
+
+                    let k = self.orders.pop().unwrap();
+                    if k >= 2 {
+                        self.orders.extend([k - 1, k - 2]);
+                        let n = self.orders.len();
+                        self.tasks.push(Task::Rectify(n - 1));
+                        self.tasks.push(Task::Rectify(n - 2));
+                    }
+                }
+                Phase::Finish | Phase::Done => return Step::Done,
+            }
+        }
+    }
+
+    fn heap_links(&self) -> Vec<HeapLink> {
+        let mut links = vec![];
+        let mut trees = vec![];
+        let mut start = 0;
+
+        for &k in &self.orders {
+            start += self.leonardo[k];
+            trees.push((start - 1, k));
+        }
+
+        // Neighbouring roots are linked, so the forest reads as a list.
+        for pair in trees.windows(2) {
+            links.push((pair[0].0, pair[1].0));
+        }
+
+        while let Some((r, k)) = trees.pop() {
+            if k >= 2 {
+                let (left, left_order, right, right_order) = self.children(r, k);
+                links.extend([(r, left), (r, right)]);
+                trees.extend([(left, left_order), (right, right_order)]);
+            }
+        }
+
+        links
+            .into_iter()
+            .map(|(parent, child)| HeapLink { parent, child, active: self.active.contains(&(parent, child)) })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{shuffled, sort, sorted};
+    use super::super::Trace;
+    use super::*;
+
+    fn heap_sorts() -> [Box<dyn SortAlgorithm>; 4] {
+        [Box::new(BinaryHeapSort::default()), Box::new(TernaryHeapSort::default()), Box::new(WeakHeapSort::default()), Box::new(SmoothSort::default())]
+    }
+
+    #[test]
+    fn heap_sorts_sort_every_length() {
+        // Smoothsort's heaps come in Leonardo numbers of elements, so
+        // every length gives it a different set of them.
+        for n in 0..=50 {
+            for columns in [shuffled(n), (0..n as i32).collect(), (0..n as i32).rev().collect()] {
+                for mut algorithm in heap_sorts() {
+                    assert_eq!(sort(algorithm.as_mut(), &columns, 1), sorted(&columns, 1), "{:?}", columns);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn heap_links_join_columns() {
+        for mut algorithm in heap_sorts() {
+            let mut columns = shuffled(60);
+            let mut trace = Trace::default();
+            while trace.step(algorithm.as_mut(), &mut columns, 1) != Step::Done {
+                let links = algorithm.heap_links();
+                assert!(links.iter().all(|link| link.parent != link.child && link.parent.max(link.child) < columns.len()));
+            }
+        }
+
+        // A d-ary heap links every node after the first to its parent.
+        let mut algorithm = TernaryHeapSort::default();
+        let mut columns = shuffled(60);
+        Trace::default().step(&mut algorithm, &mut columns, 1);
+        let links = algorithm.heap_links();
+        assert_eq!(links.len(), 59);
+        assert!(links.iter().all(|link| link.parent == (link.child - 1) / 3));
+    }
+}
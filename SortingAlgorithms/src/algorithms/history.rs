@@ -0,0 +1,169 @@
+/*****************************************************************/
+//! [History]
+/*****************************************************************/
+//!
+//! Lets a sort be wound backwards and forwards. Every so many steps,
+//! a snapshot is taken of the columns along with a clone of the
+//! algorithm, which holds all of its progress. Going back to any
+//! step restores the last snapshot before it and replays the steps
+//! in between, which lands on exactly the same state as before,
+//! since the algorithms are deterministic. Even the random ones
+//! carry their random number generator in the snapshot. Going
+//! forwards just carries on stepping, so the trace stays complete.
+//!
+//! Snapshots are cheap, but a sort like bogosort can run for ever,
+//! so once there are too many, every other one is dropped and the
+//! gap between them doubles.
+
+use super::{SortAlgorithm, Step, Trace};
+
+// The number of steps between snapshots to begin with.
+const INTERVAL: usize = 32;
+
+// The most snapshots kept before thinning them out.
+const MAX_SNAPSHOTS: usize = 256;
+
+/// [Snapshot]
+/// Everything needed to carry on from just after a step, which is
+/// the columns, the algorithm's progress and what the step was.
+#[derive(Clone)]
+struct Snapshot {
+    step: usize,
+    columns: Vec<i32>,
+    algorithm: Box<dyn SortAlgorithm>,
+    last_step: Step,
+}
+
+/// [History]
+/// The snapshots taken so far, in order of step, and the furthest
+/// step the sort has reached.
+#[derive(Clone, Default)]
+pub struct History {
+    snapshots: Vec<Snapshot>,
+    interval: usize,
+    end: usize,
+}
+
+impl History {
+    /// [Start]
+    /// Forgets everything, ready for the algorithm to start sorting
+    /// the given columns from scratch.
+    pub fn start(&mut self, algorithm: &dyn SortAlgorithm, columns: &[i32]) {
+        let first = Snapshot { step: 0, columns: columns.to_vec(), algorithm: algorithm.clone_box(), last_step: Step::Done };
+        *self = History { snapshots: vec![first], interval: INTERVAL, end: 0 };
+    }
+
+    /// [End]
+    /// The furthest step the sort has reached.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// [Step]
+    /// Asks the algorithm to take one step, recording it in the trace,
+    /// and takes a snapshot when one is due.
+    pub fn step(&mut self, algorithm: &mut Box<dyn SortAlgorithm>, columns: &mut [i32], trace: &mut Trace, direction: i32) -> Step {
+        let step = trace.step(algorithm.as_mut(), columns, direction);
+        let now = trace.steps();
+        self.end = self.end.max(now);
+
+        let due = now.is_multiple_of(self.interval) && self.snapshots.last().is_some_and(|last| last.step < now);
+        if due {
+            self.snapshots.push(Snapshot { step: now, columns: columns.to_vec(), algorithm: algorithm.clone(), last_step: step });
+            if self.snapshots.len() > MAX_SNAPSHOTS {
+                self.interval *= 2;
+                let interval = self.interval;
+                self.snapshots.retain(|snapshot| snapshot.step.is_multiple_of(interval));
+            }
+        }
+        step
+    }
+
+    /// [Seek]
+    /// Moves the sort to just after the given step, restoring the
+    /// columns, the algorithm and the trace as they were back then.
+    /// Returns the step which was landed on, or None if the sort was
+    /// already there.
+    pub fn seek(&mut self, target: usize, algorithm: &mut Box<dyn SortAlgorithm>, columns: &mut [i32], trace: &mut Trace, direction: i32) -> Option<Step> {
+        let target = target.min(self.end);
+        if target == trace.steps() {
+            return None;
+        }
+
+        let mut last_step = Step::Done;
+        if target < trace.steps() {
+            let snapshot = self.snapshots.iter().rev().find(|snapshot| snapshot.step <= target)?;
+            columns.copy_from_slice(&snapshot.columns);
+            *algorithm = snapshot.algorithm.clone();
+            trace.truncate(snapshot.step);
+            last_step = snapshot.last_step;
+        }
+
+        while trace.steps() < target {
+            last_step = self.step(algorithm, columns, trace, direction);
+            if last_step == Step::Done {
+                break;
+            }
+        }
+        Some(last_step)
+    }
+
+    /// [Branch]
+    /// Forgets everything after the given step, for when the sort is
+    /// about to go a different way than it did before, such as after
+    /// its setting changes.
+    pub fn branch(&mut self, step: usize) {
+        self.snapshots.retain(|snapshot| snapshot.step <= step);
+        self.end = step;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::registry;
+    use super::*;
+
+    #[test]
+    fn seeking_replays_the_same_sort() {
+        let registry = registry();
+        let columns: Vec<i32> = (0..200).map(|i| (i * 89 + 13) % 200).collect();
+        // Bubble sort runs for long enough that the snapshots are
+        // thinned out, and bogosort makes random choices.
+        for name in ["Pdqsort", "Timsort", "Bubble Sort", "Bogosort"] {
+            let columns = if name == "Bogosort" { &columns[..4] } else { &columns[..] };
+            let mut algorithm = registry.create(registry.find(name).unwrap());
+            let (mut current, mut trace, mut history) = (columns.to_vec(), Trace::default(), History::default());
+            trace.start(&current);
+            history.start(algorithm.as_ref(), &current);
+
+            // What the columns, the step and the operations were just
+            // after every step of the first run through. Finding out
+            // the sort is done doesn't count as a step.
+            let mut seen = vec![(current.clone(), Step::Done, trace.stats())];
+            loop {
+                let step = history.step(&mut algorithm, &mut current, &mut trace, 1);
+                if step == Step::Done {
+                    break;
+                }
+                seen.push((current.clone(), step, trace.stats()));
+            }
+            let end = history.end();
+            assert_eq!(end, seen.len() - 1, "{}", name);
+
+            for target in [end / 2, 1, end, 0, end - 1, end / 3, end / 3 + 40, end] {
+                let target = target.min(end);
+                history.seek(target, &mut algorithm, &mut current, &mut trace, 1);
+                assert_eq!(trace.steps(), target, "{}", name);
+                assert_eq!(current, seen[target].0, "{} at step {}", name, target);
+                assert_eq!(trace.stats(), seen[target].2, "{} at step {}", name, target);
+            }
+
+            // Carrying on from a seek finishes the same way.
+            history.seek(end / 2, &mut algorithm, &mut current, &mut trace, 1);
+            while trace.steps() < end {
+                let step = history.step(&mut algorithm, &mut current, &mut trace, 1);
+                assert_eq!((&current, step), (&seen[trace.steps()].0, seen[trace.steps()].1), "{}", name);
+            }
+        }
+    }
+}
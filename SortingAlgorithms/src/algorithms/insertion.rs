@@ -0,0 +1,157 @@
+/*****************************************************************/
+//! [Insertion Sort]
+/*****************************************************************/
+
+use super::{Array, SortAlgorithm, Step};
+
+/// [Insertion Sort]
+/// Grows a sorted prefix one element at a time, sinking each new
+/// element towards the front by swapping it with its neighbour until
+/// it is in order. The element being sunk is at pointer, and the
+/// prefix before next is already sorted.
+#[derive(Clone, Default)]
+pub struct InsertionSort {
+    next: usize,
+    pointer: usize,
+}
+
+impl SortAlgorithm for InsertionSort {
+    fn reset(&mut self) {
+        *self = InsertionSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if self.pointer == 0 {
+            if self.next + 1 >= columns.len() {
+                return Step::Done;
+            }
+            self.next += 1;
+            self.pointer = self.next;
+        }
+
+        let (i, j) = (self.pointer - 1, self.pointer);
+
+        if columns.compare(i, j, direction) {
+            self.pointer = 0;
+            Step::Compare(i, j)
+        } else {
+            columns.swap(i, j);
+            self.pointer -= 1;
+            Step::Swap(i, j)
+        }
+    }
+}
+
+/// [Binary Insertion Sort]
+/// Insertion sort which finds where the next element belongs with a
+/// binary search over the sorted prefix, then shifts the larger
+/// elements up one at a time to make room for it. The search keeps
+/// its bounds in low and high, and key holds the element being
+/// inserted while the prefix is shifted.
+#[derive(Clone, Default)]
+pub struct BinaryInsertionSort {
+    next: usize,
+    low: usize,
+    high: usize,
+    pointer: usize,
+    key: i32,
+    phase: Phase,
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Phase {
+    #[default]
+    Start,
+    Search,
+    Shift,
+}
+
+impl BinaryInsertionSort {
+    /// [Sorted Prefix]
+    /// A binary insertion sort for columns whose first sorted elements
+    /// are already in order, so only the rest need inserting.
+    pub fn sorted_prefix(sorted: usize) -> Self {
+        BinaryInsertionSort { next: sorted.saturating_sub(1), ..BinaryInsertionSort::default() }
+    }
+}
+
+impl SortAlgorithm for BinaryInsertionSort {
+    fn reset(&mut self) {
+        *self = BinaryInsertionSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            match self.phase {
+                Phase::Start => {
+                    if self.next + 1 >= columns.len() {
+                        return Step::Done;
+                    }
+                    self.next += 1;
+                    (self.low, self.high) = (0, self.next);
+                    self.phase = Phase::Search;
+                }
+                Phase::Search => {
+                    if self.low < self.high {
+                        // Find the first element the key must come
+                        // before, so that equal elements stay stable.
+                        let mid = (self.low + self.high) / 2;
+                        if columns.compare(mid, self.next, direction) {
+                            self.low = mid + 1;
+                        } else {
+                            self.high = mid;
+                        }
+                        return Step::Compare(mid, self.next);
+                    }
+
+                    if self.low == self.next {
+                        self.phase = Phase::Start;
+                    } else {
+                        self.key = columns.read(self.next);
+                        self.pointer = self.next;
+                        self.phase = Phase::Shift;
+                    }
+                }
+                Phase::Shift => {
+                    let i = self.pointer;
+                    if i > self.low {
+                        columns.copy(i - 1, i);
+                        self.pointer -= 1;
+                    } else {
+                        columns.write(i, self.key);
+                        self.phase = Phase::Start;
+                    }
+                    return Step::Write(i);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Trace;
+    use super::*;
+
+    /// [Steps]
+    /// Every step an algorithm takes to sort the columns.
+    fn steps(algorithm: &mut dyn SortAlgorithm, columns: &mut [i32]) -> Vec<Step> {
+        let mut trace = Trace::default();
+        std::iter::from_fn(|| Some(trace.step(algorithm, columns, 1)).filter(|&step| step != Step::Done)).collect()
+    }
+
+    #[test]
+    fn sorted_columns_are_only_compared() {
+        let mut columns: Vec<i32> = (0..64).collect();
+        let steps_taken = steps(&mut InsertionSort::default(), &mut columns);
+        assert_eq!(steps_taken.len(), 63);
+        assert!(steps_taken.iter().all(|step| matches!(step, Step::Compare(..))));
+
+        // The binary search makes at most six compares for each of the
+        // elements, and nothing has to be shifted.
+        let steps_taken = steps(&mut BinaryInsertionSort::default(), &mut columns);
+        assert!(steps_taken.iter().all(|step| matches!(step, Step::Compare(..))));
+        assert!(steps_taken.len() <= 63 * 6);
+        assert_eq!(columns, (0..64).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,763 @@
+/*****************************************************************/
+//! [Merge Sorts]
+/*****************************************************************/
+//!
+//! Merge sort is naturally recursive, but a stepper has to be able
+//! to stop after any single operation. So instead of recursing, the
+//! top-down variants keep an explicit stack of the ranges still left
+//! to sort, and the merges themselves are little state machines too.
+//!
+//! Timsort also lives here. It merges runs which it finds in the
+//! input rather than runs of a fixed width.
+
+use super::{Array, BinaryInsertionSort, Marker, Panel, SortAlgorithm, Step};
+
+/// [Frame]
+/// One entry of the explicit recursion stack. A range is first split
+/// into halves, and once both halves are sorted it is merged.
+#[derive(Clone, Copy)]
+struct Frame {
+    low: usize,
+    high: usize,
+    merge: bool,
+}
+
+/// [Split Stack]
+/// Simulates the recursion of a top-down merge sort. Every call to
+/// next hands back the next pair of sorted runs which need merging,
+/// as (low, mid, high).
+#[derive(Clone, Default)]
+struct SplitStack {
+    frames: Vec<Frame>,
+    started: bool,
+}
+
+impl SplitStack {
+    fn next(&mut self, len: usize) -> Option<(usize, usize, usize)> {
+        if !self.started {
+            self.started = true;
+            self.frames.push(Frame { low: 0, high: len, merge: false });
+        }
+
+        while let Some(frame) = self.frames.pop() {
+            let mid = frame.low + (frame.high - frame.low) / 2;
+
+            if frame.merge {
+                return Some((frame.low, mid, frame.high));
+            }
+
+            if frame.high - frame.low >= 2 {
+                // Pushed in reverse, so the left half is sorted first.
+                self.frames.push(Frame { merge: true, ..frame });
+                self.frames.push(Frame { low: mid, high: frame.high, merge: false });
+                self.frames.push(Frame { low: frame.low, high: mid, merge: false });
+            }
+        }
+
+        None
+    }
+}
+
+/// [Width Passes]
+/// The bottom-up equivalent of the split stack. Runs of width one are
+/// merged into runs of width two, then four, and so on.
+#[derive(Clone)]
+struct WidthPasses {
+    width: usize,
+    low: usize,
+}
+
+impl Default for WidthPasses {
+    fn default() -> Self {
+        WidthPasses { width: 1, low: 0 }
+    }
+}
+
+impl WidthPasses {
+    fn next(&mut self, len: usize) -> Option<(usize, usize, usize)> {
+        while self.width < len {
+            if self.low + self.width < len {
+                let low = self.low;
+                let mid = low + self.width;
+                let high = (low + 2 * self.width).min(len);
+                self.low = high;
+                return Some((low, mid, high));
+            }
+
+            self.width *= 2;
+            self.low = 0;
+        }
+
+        None
+    }
+}
+
+/// [Buffered Merge]
+/// Merges two adjacent sorted runs through an auxiliary buffer. The
+/// runs are first copied out into the buffer, one element per step,
+/// and then merged back into the columns. Slots of the buffer are
+/// emptied as their elements flow back, so that the renderer shows
+/// exactly which elements are still waiting.
+#[derive(Clone, Default)]
+struct BufferedMerge {
+    aux: Vec<Option<i32>>,
+    low: usize,
+    mid: usize,
+    high: usize,
+    left: usize,
+    right: usize,
+    out: usize,
+    copying: bool,
+    active: bool,
+}
+
+impl BufferedMerge {
+    fn start(&mut self, len: usize, (low, mid, high): (usize, usize, usize)) {
+        self.aux.resize(len, None);
+        (self.low, self.mid, self.high) = (low, mid, high);
+        (self.left, self.right, self.out) = (low, mid, low);
+        self.copying = true;
+        self.active = true;
+    }
+
+    /// [Step]
+    /// Performs one copy or one write of the merge, or returns None
+    /// once the merge is finished.
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Option<Step> {
+        if !self.active {
+            return None;
+        }
+
+        if self.copying {
+            let k = self.out;
+            let value = columns.read(k);
+            self.aux[k] = Some(value);
+            columns.aux_write(k, value);
+            self.out += 1;
+            if self.out == self.high {
+                self.out = self.low;
+                self.copying = false;
+            }
+            return Some(Step::AuxWrite(k));
+        }
+
+        if self.out == self.high {
+            self.active = false;
+            return None;
+        }
+
+        let take_left = if self.left == self.mid {
+            false
+        } else if self.right == self.high {
+            true
+        } else {
+            // Equal elements come from the left run, keeping it stable.
+            let (left, right) = (self.aux[self.left].unwrap(), self.aux[self.right].unwrap());
+            columns.compare_held((self.left, left), (self.right, right), direction)
+        };
+
+        let from = if take_left { &mut self.left } else { &mut self.right };
+        columns.write(self.out, self.aux[*from].take().unwrap());
+        *from += 1;
+        self.out += 1;
+
+        Some(Step::Write(self.out - 1))
+    }
+}
+
+/// [In-Place Merge]
+/// Merges two adjacent sorted runs without any extra memory. Whenever
+/// the head of the right run belongs before the head of the left run,
+/// it is swapped backwards into place one position at a time, which
+/// pushes the rest of the left run along by one.
+#[derive(Clone, Default)]
+struct InPlaceMerge {
+    left: usize,
+    mid: usize,
+    high: usize,
+    pointer: usize,
+    rotating: bool,
+    active: bool,
+}
+
+impl InPlaceMerge {
+    fn start(&mut self, (low, mid, high): (usize, usize, usize)) {
+        (self.left, self.mid, self.high) = (low, mid, high);
+        self.rotating = false;
+        self.active = true;
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Option<Step> {
+        if !self.active {
+            return None;
+        }
+
+        if self.rotating {
+            let i = self.pointer;
+            columns.swap(i - 1, i);
+            self.pointer -= 1;
+
+            if self.pointer == self.left {
+                self.rotating = false;
+                self.left += 1;
+                self.mid += 1;
+            }
+            return Some(Step::Swap(i - 1, i));
+        }
+
+        if self.left >= self.mid || self.mid >= self.high {
+            self.active = false;
+            return None;
+        }
+
+        let (i, j) = (self.left, self.mid);
+        if columns.compare(i, j, direction) {
+            self.left += 1;
+        } else {
+            self.pointer = j;
+            self.rotating = true;
+        }
+        Some(Step::Compare(i, j))
+    }
+}
+
+/// [Top-Down Merge Sort]
+/// Recursively halves the columns, then merges the sorted halves back
+/// together through the auxiliary buffer.
+#[derive(Clone, Default)]
+pub struct TopDownMergeSort {
+    stack: SplitStack,
+    merge: BufferedMerge,
+}
+
+impl SortAlgorithm for TopDownMergeSort {
+    fn reset(&mut self) {
+        *self = TopDownMergeSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            if let Some(step) = self.merge.step(columns, direction) {
+                return step;
+            }
+
+            match self.stack.next(columns.len()) {
+                Some(runs) => self.merge.start(columns.len(), runs),
+                None => return Step::Done,
+            }
+        }
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        vec![Panel::Aux(&self.merge.aux)]
+    }
+}
+
+/// [Bottom-Up Merge Sort]
+/// Treats every column as a sorted run of one, and merges neighbouring
+/// runs in passes of doubling width until a single run remains.
+#[derive(Clone, Default)]
+pub struct BottomUpMergeSort {
+    passes: WidthPasses,
+    merge: BufferedMerge,
+}
+
+impl SortAlgorithm for BottomUpMergeSort {
+    fn reset(&mut self) {
+        *self = BottomUpMergeSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            if let Some(step) = self.merge.step(columns, direction) {
+                return step;
+            }
+
+            match self.passes.next(columns.len()) {
+                Some(runs) => self.merge.start(columns.len(), runs),
+                None => return Step::Done,
+            }
+        }
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        vec![Panel::Aux(&self.merge.aux)]
+    }
+}
+
+/// [In-Place Merge Sort]
+/// The same recursion as the top-down merge sort, but merging by
+/// rotating elements within the columns instead of using a buffer.
+/// This trades the extra memory for quadratic worst case merging.
+#[derive(Clone, Default)]
+pub struct InPlaceMergeSort {
+    stack: SplitStack,
+    merge: InPlaceMerge,
+}
+
+impl SortAlgorithm for InPlaceMergeSort {
+    fn reset(&mut self) {
+        *self = InPlaceMergeSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            if let Some(step) = self.merge.step(columns, direction) {
+                return step;
+            }
+
+            match self.stack.next(columns.len()) {
+                Some(runs) => self.merge.start(runs),
+                None => return Step::Done,
+            }
+        }
+    }
+}
+
+/// [Min Gallop]
+/// How many times in a row one run has to win before Timsort starts
+/// galloping, and how long a gallop has to be to keep it going.
+const MIN_GALLOP: usize = 7;
+
+/// [Minimum Runs]
+/// The minimum run lengths Timsort can cycle through. Zero is
+/// Timsort's own choice, which for fewer than 64 columns is all of
+/// them. The fixed lengths are there to give it more runs to merge.
+const MIN_RUNS: [usize; 4] = [0, 32, 16, 8];
+
+/// [Gallop]
+/// Counts how many leading elements of a sorted sequence of len
+/// elements satisfy pred. Rather than checking them one by one, it
+/// probes 1, 3, 7, 15... elements ahead until pred fails, then binary
+/// searches between the last two probes.
+pub fn gallop(len: usize, mut pred: impl FnMut(usize) -> bool) -> usize {
+    let (mut low, mut probe) = (0, 0);
+    let mut high = loop {
+        if probe >= len {
+            break len;
+        }
+        if !pred(probe) {
+            break probe;
+        }
+        low = probe + 1;
+        probe = 2 * probe + 2;
+    };
+
+    while low < high {
+        let mid = (low + high) / 2;
+        if pred(mid) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// [Galloping Merge]
+/// Timsort's merge. Like the buffered merge, the left run is copied
+/// out to the buffer and merged back with the right run. But once
+/// one run has won min_gallop times in a row, the merge starts
+/// galloping. It searches for where the other run's next element
+/// belongs, and copies the whole stretch before it in one go. Copies
+/// still happen one write per step, with the length of the stretch
+/// left in from_left or from_right. Galloping stops once it stops
+/// paying off, and min_gallop adapts to how well it has been doing.
+#[derive(Clone, Default)]
+struct GallopingMerge {
+    aux: Vec<Option<i32>>,
+    low: usize,
+    mid: usize,
+    high: usize,
+    left: usize,
+    right: usize,
+    out: usize,
+    min_gallop: usize,
+    wins: (usize, usize),
+    from_left: usize,
+    from_right: usize,
+    found: usize,
+    galloping: bool,
+    searching_right: bool,
+    copying: bool,
+    active: bool,
+}
+
+impl GallopingMerge {
+    /// [Start]
+    /// Starts merging the runs low..mid and mid..high. Elements at the
+    /// start of the left run and the end of the right run which are
+    /// already in place are found by galloping, and left alone.
+    fn start(&mut self, columns: &mut Array, direction: i32, (low, mid, high): (usize, usize, usize)) {
+        let low = low + gallop(mid - low, |k| columns.compare(low + k, mid, direction));
+        let high = mid + gallop(high - mid, |k| !columns.compare(mid - 1, mid + k, direction));
+
+        (self.low, self.mid, self.high) = (low, mid, high);
+        (self.left, self.right, self.out) = (low, mid, low);
+        self.wins = (0, 0);
+        self.galloping = false;
+        self.copying = true;
+        self.active = low < mid && mid < high;
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Option<Step> {
+        if !self.active {
+            return None;
+        }
+
+        if self.copying {
+            let k = self.out;
+            let value = columns.read(k);
+            self.aux[k] = Some(value);
+            columns.aux_write(k, value);
+            self.out += 1;
+            if self.out == self.mid {
+                self.out = self.low;
+                self.copying = false;
+            }
+            return Some(Step::AuxWrite(k));
+        }
+
+        loop {
+            if self.from_left > 0 {
+                self.from_left -= 1;
+                columns.write(self.out, self.aux[self.left].take().unwrap());
+                self.left += 1;
+                self.out += 1;
+                return Some(Step::Write(self.out - 1));
+            }
+
+            if self.from_right > 0 {
+                self.from_right -= 1;
+                columns.copy(self.right, self.out);
+                self.right += 1;
+                self.out += 1;
+                return Some(Step::Write(self.out - 1));
+            }
+
+            // Once the left run is used up, the rest of the right run
+            // is already where it belongs.
+            if self.left == self.mid {
+                self.active = false;
+                return None;
+            }
+
+            if self.right == self.high {
+                self.from_left = self.mid - self.left;
+                continue;
+            }
+
+            let (aux, left, right) = (&self.aux, self.left, self.right);
+
+            if !self.galloping {
+                // Equal elements come from the left run, keeping it stable.
+                let head = columns.values()[right];
+                if columns.compare_held((left, aux[left].unwrap()), (right, head), direction) {
+                    self.wins = (self.wins.0 + 1, 0);
+                    self.from_left = 1;
+                } else {
+                    self.wins = (0, self.wins.1 + 1);
+                    self.from_right = 1;
+                }
+
+                if self.wins.0.max(self.wins.1) >= self.min_gallop {
+                    self.galloping = true;
+                    self.searching_right = false;
+                    self.wins = (0, 0);
+                }
+                continue;
+            }
+
+            if !self.searching_right {
+                // How much of the left run goes before the right's head.
+                let key = columns.values()[right];
+                self.found = gallop(self.mid - left, |k| columns.compare_held((left + k, aux[left + k].unwrap()), (right, key), direction));
+                self.from_left = self.found;
+            } else {
+                // How much of the right run goes before the left's head.
+                let key = aux[left].unwrap();
+                let found = gallop(self.high - right, |k| {
+                    let value = columns.values()[right + k];
+                    !columns.compare_held((left, key), (right + k, value), direction)
+                });
+                self.from_right = found;
+
+                if self.found < MIN_GALLOP && found < MIN_GALLOP {
+                    self.galloping = false;
+                    self.min_gallop += 1;
+                } else {
+                    self.min_gallop = self.min_gallop.saturating_sub(1).max(1);
+                }
+            }
+            self.searching_right = !self.searching_right;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum TimPhase {
+    #[default]
+    Start,
+    Scan,
+    Reverse,
+    Extend,
+    Collapse,
+    Merge,
+    Done,
+}
+
+/// [Timsort]
+/// Tim Peters' sort, as used by Python and Java. It scans the columns
+/// for runs which are already ascending, or strictly descending and
+/// so can simply be reversed. Runs shorter than the minimum run
+/// length are extended with a binary insertion sort. Each run found
+/// is pushed onto a stack of runs, as (start, end) pairs, and
+/// neighbouring runs are merged whenever their lengths stop shrinking
+/// quickly enough towards the top of the stack, which keeps merges
+/// balanced.
+#[derive(Clone, Default)]
+pub struct TimSort {
+    min_run: usize,
+    runs: Vec<(usize, usize)>,
+    phase: TimPhase,
+    start: usize,
+    end: usize,
+    pointer: usize,
+    descending: Option<bool>,
+    insertion: BinaryInsertionSort,
+    merge: GallopingMerge,
+    merging: usize,
+}
+
+impl TimSort {
+    /// [Min Run]
+    /// The minimum length of a run for the current setting. Timsort's
+    /// own choice keeps the number of runs at, or just under, a power
+    /// of two.
+    fn min_run(&self, len: usize) -> usize {
+        match MIN_RUNS[self.min_run] {
+            0 => {
+                let (mut n, mut odd) = (len, 0);
+                while n >= 64 {
+                    odd |= n & 1;
+                    n >>= 1;
+                }
+                n + odd
+            }
+            fixed => fixed,
+        }
+    }
+
+    /// [Next Merge]
+    /// The index of the run on the stack which should be merged with
+    /// the run above it, if any. Once every run has been found, all of
+    /// them are merged.
+    fn next_merge(&self, finished: bool) -> Option<usize> {
+        let n = self.runs.len();
+        if n < 2 {
+            return None;
+        }
+
+        let len = |k: usize| self.runs[k].1 - self.runs[k].0;
+        let i = n - 2;
+
+        if finished {
+            Some(if i > 0 && len(i - 1) < len(i + 1) { i - 1 } else { i })
+        } else if (i > 0 && len(i - 1) <= len(i) + len(i + 1)) || (i > 1 && len(i - 2) <= len(i - 1) + len(i)) {
+            Some(if len(i - 1) < len(i + 1) { i - 1 } else { i })
+        } else if len(i) <= len(i + 1) {
+            Some(i)
+        } else {
+            None
+        }
+    }
+
+    /// [Extend]
+    /// Moves on to extending the run just found up to the minimum run
+    /// length, with pointer holding where the extended run will end.
+    fn extend(&mut self, len: usize) {
+        self.pointer = (self.start + self.min_run(len)).min(len).max(self.end);
+        self.insertion = BinaryInsertionSort::sorted_prefix(self.end - self.start);
+        self.phase = TimPhase::Extend;
+    }
+}
+
+impl SortAlgorithm for TimSort {
+    fn reset(&mut self) {
+        *self = TimSort { min_run: self.min_run, ..TimSort::default() };
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            match self.phase {
+                TimPhase::Start => {
+                    if columns.len() < 2 {
+                        return Step::Done;
+                    }
+                    self.merge.aux = vec![None; columns.len()];
+                    self.merge.min_gallop = MIN_GALLOP;
+                    self.pointer = 1;
+                    self.phase = TimPhase::Scan;
+                }
+                TimPhase::Scan => {
+                    // The run carries on for as long as it keeps going
+                    // the same way as its first two elements.
+                    let p = self.pointer;
+                    if p < columns.len() {
+                        let descending = !columns.compare(p - 1, p, direction);
+                        if *self.descending.get_or_insert(descending) == descending {
+                            self.pointer += 1;
+                            return Step::Compare(p - 1, p);
+                        }
+                    }
+
+                    self.end = p;
+                    if self.descending == Some(true) {
+                        self.pointer = self.start;
+                        self.phase = TimPhase::Reverse;
+                    } else {
+                        self.extend(columns.len());
+                    }
+                    if p < columns.len() {
+                        return Step::Compare(p - 1, p);
+                    }
+                }
+                TimPhase::Reverse => {
+                    let (i, j) = (self.pointer, self.end - 1 - (self.pointer - self.start));
+                    if i < j {
+                        self.pointer += 1;
+                        columns.swap(i, j);
+                        return Step::Swap(i, j);
+                    }
+                    self.extend(columns.len());
+                }
+                TimPhase::Extend => {
+                    if self.end < self.pointer {
+                        match self.insertion.step(&mut columns.slice(self.start, self.pointer), direction) {
+                            Step::Done => self.end = self.pointer,
+                            step => return step.offset(self.start),
+                        }
+                    }
+
+                    self.runs.push((self.start, self.end));
+                    self.start = self.end;
+                    self.phase = TimPhase::Collapse;
+                }
+                TimPhase::Collapse => {
+                    let finished = self.start == columns.len();
+                    match self.next_merge(finished) {
+                        Some(k) => {
+                            let runs = (self.runs[k].0, self.runs[k].1, self.runs[k + 1].1);
+                            self.merge.start(columns, direction, runs);
+                            self.merging = k;
+                            self.phase = TimPhase::Merge;
+                        }
+                        None if finished => self.phase = TimPhase::Done,
+                        None => {
+                            self.pointer = self.start + 1;
+                            self.descending = None;
+                            self.phase = TimPhase::Scan;
+                        }
+                    }
+                }
+                TimPhase::Merge => {
+                    if let Some(step) = self.merge.step(columns, direction) {
+                        return step;
+                    }
+                    let k = self.merging;
+                    self.runs[k].1 = self.runs.remove(k + 1).1;
+                    self.phase = TimPhase::Collapse;
+                }
+                TimPhase::Done => return Step::Done,
+            }
+        }
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        vec![Panel::Aux(&self.merge.aux)]
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        let mut markers: Vec<Marker> = self.runs.iter().map(|&(start, _)| Marker::Boundary(start)).collect();
+        match self.phase {
+            TimPhase::Scan | TimPhase::Reverse | TimPhase::Extend => markers.push(Marker::Range(self.start, self.end.max(self.pointer))),
+            TimPhase::Merge => markers.push(Marker::Range(self.merge.low, self.merge.high)),
+            _ => {}
+        }
+        markers
+    }
+
+    fn option(&self) -> Option<String> {
+        match MIN_RUNS[self.min_run] {
+            0 => Some("minrun: auto".to_string()),
+            fixed => Some(format!("minrun: {}", fixed)),
+        }
+    }
+
+    /// [Next Option]
+    /// Switches the minimum run length, which applies from the next
+    /// run found.
+    fn next_option(&mut self) {
+        self.min_run = (self.min_run + 1) % MIN_RUNS.len();
+    }
+
+    fn status(&self) -> Option<String> {
+        let phase = match self.phase {
+            TimPhase::Start | TimPhase::Done => return None,
+            TimPhase::Scan | TimPhase::Collapse => "finding runs",
+            TimPhase::Reverse => "reversing run",
+            TimPhase::Extend => "binary insertion",
+            TimPhase::Merge if self.merge.galloping => "galloping",
+            TimPhase::Merge => "merging",
+        };
+        Some(format!("{} - {} runs", phase, self.runs.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{shuffled, sort, sorted};
+    use super::super::Trace;
+    use super::*;
+
+    #[test]
+    fn merge_sorts_sort_every_length() {
+        // Odd lengths leave runs of uneven size to merge, and the
+        // bottom-up sort a run at the end with nothing to merge with.
+        for n in 0..=40 {
+            for columns in [shuffled(n), (0..n as i32).collect(), (0..n as i32).rev().collect(), vec![3; n]] {
+                let algorithms: [Box<dyn SortAlgorithm>; 3] = [Box::new(TopDownMergeSort::default()), Box::new(BottomUpMergeSort::default()), Box::new(InPlaceMergeSort::default())];
+                for mut algorithm in algorithms {
+                    assert_eq!(sort(algorithm.as_mut(), &columns, 1), sorted(&columns, 1), "{:?}", columns);
+                }
+            }
+        }
+    }
+    #[test]
+    fn timsort_sorts_with_every_minimum_run() {
+        let columns = shuffled(300);
+        for setting in 0..MIN_RUNS.len() {
+            let mut algorithm = TimSort::default();
+            for _ in 0..setting {
+                algorithm.next_option();
+            }
+            assert_eq!(sort(&mut algorithm, &columns, 1), sorted(&columns, 1), "{:?}", algorithm.option());
+        }
+    }
+
+    #[test]
+    fn timsort_reverses_runs_and_gallops() {
+        // A falling run, then a rising one which belongs before it all.
+        let mut columns: Vec<i32> = (500..1000).rev().chain(0..500).collect();
+        let mut algorithm = TimSort::default();
+        let mut trace = Trace::default();
+        let mut phases = vec![];
+        while trace.step(&mut algorithm, &mut columns, 1) != Step::Done {
+            phases.extend(algorithm.status().and_then(|status| status.split(" - ").next().map(str::to_string)));
+        }
+        assert_eq!(columns, (0..1000).collect::<Vec<_>>());
+        for phase in ["finding runs", "reversing run", "galloping"] {
+            assert!(phases.iter().any(|seen| seen == phase), "never {}", phase);
+        }
+    }
+}
@@ -0,0 +1,537 @@
+/*****************************************************************/
+//! [Sorting Algorithms]
+/*****************************************************************/
+//!
+//! Every sorting algorithm in the visualizer lives in this module.
+//! Rather than being methods on the App, each algorithm is its own
+//! little resumable state machine which implements SortAlgorithm.
+//! The App only ever asks the current algorithm to take one more
+//! step, and draws whatever the step touched, which it learns from
+//! the trace of operations the step performed.
+//!
+//! New algorithms are made available by adding them to the
+//! Registry built in [registry], nothing else needs to change.
+/*****************************************************************/
+
+mod block;
+mod bubble;
+mod distribution;
+mod esoteric;
+mod gap;
+mod heap;
+mod history;
+mod insertion;
+mod merge;
+mod network;
+mod parallel;
+mod pile;
+mod quick;
+mod selection;
+mod trace;
+
+pub use self::block::{GrailSort, WikiSort};
+pub use self::bubble::{BubbleSort, CircleSort, CocktailShakerSort, ExchangeSort, GnomeSort, OddEvenSort};
+pub use self::distribution::{AmericanFlagSort, BucketSort, CountingSort, LsdRadixSort, MsdRadixSort, PigeonholeSort};
+pub use self::esoteric::{BeadSort, BogoSort, BozoSort, PancakeSort, SlowSort, StoogeSort};
+pub use self::gap::{CombSort, ShellSort};
+pub use self::heap::{BinaryHeapSort, SmoothSort, TernaryHeapSort, WeakHeapSort};
+pub use self::history::History;
+pub use self::insertion::{BinaryInsertionSort, InsertionSort};
+pub use self::merge::{BottomUpMergeSort, InPlaceMergeSort, TimSort, TopDownMergeSort};
+pub use self::quick::{DualPivotQuickSort, HoareQuickSort, IntroSort, LomutoQuickSort, PdqSort, ThreeWayQuickSort};
+pub use self::network::{Network, NetworkSort};
+pub use self::parallel::{BitonicSort, OddEvenMergeSort, ParallelMergeSort};
+pub use self::pile::{PatienceSort, StrandSort};
+pub use self::selection::{CycleSort, SelectionSort, TournamentSort};
+pub use self::trace::{Array, Event, Trace};
+
+use std::collections::VecDeque;
+
+/// [Step]
+/// Describes the single operation an algorithm performed when it was
+/// asked to step. The indices refer to positions in the columns
+/// vector, while the details of what was read and written are left
+/// to the trace. Reverse(low, high) reverses all of low..high in one
+/// go, and Round is a whole round of a parallel algorithm, described
+/// by its work.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    Compare(usize, usize),
+    Swap(usize, usize),
+    Reverse(usize, usize),
+    Round,
+    Read(usize),
+    Write(usize),
+    AuxWrite(usize),
+    Done,
+}
+
+impl Step {
+    /// [Offset]
+    /// The same step with its indices moved along by offset. Hybrid
+    /// algorithms use this when they hand a slice of the columns to
+    /// another algorithm, which only knows about indices in its slice.
+    pub fn offset(self, offset: usize) -> Step {
+        match self {
+            Step::Compare(i, j) => Step::Compare(i + offset, j + offset),
+            Step::Swap(i, j) => Step::Swap(i + offset, j + offset),
+            Step::Reverse(low, high) => Step::Reverse(low + offset, high + offset),
+            Step::Read(i) => Step::Read(i + offset),
+            Step::Write(i) => Step::Write(i + offset),
+            Step::AuxWrite(i) => Step::AuxWrite(i + offset),
+            Step::Round => Step::Round,
+            Step::Done => Step::Done,
+        }
+    }
+}
+
+/// [Panel]
+/// Extra state an algorithm would like drawn alongside the columns.
+pub enum Panel<'a> {
+    /// An auxiliary buffer the same length as the columns, drawn as a
+    /// second strip of bars. Empty slots are None.
+    Aux(&'a [Option<i32>]),
+    /// Counts or bucket sizes, drawn as a histogram. The active entry,
+    /// if any, is the one the last step changed.
+    Histogram(&'a [usize], Option<usize>),
+    /// A complete binary tree stored like a heap, with the children of
+    /// node k at 2k + 1 and 2k + 2, drawn level by level. Empty nodes
+    /// are None, and the active node is the one the last step decided.
+    Tree(&'a [Option<i32>], Option<usize>),
+    /// Stacks of values, drawn side by side from the left with their
+    /// last value on top. The active pile is the one last changed.
+    Piles(&'a [Vec<i32>], Option<usize>),
+    /// A comparator network, drawn as a wire diagram hanging beneath
+    /// the columns, with one wire per column. The number is how many
+    /// of its comparators have been run so far.
+    Network(&'a Network, usize),
+}
+
+impl Panel<'_> {
+    /// [Memory]
+    /// How many values the panel is holding outside the columns. Every
+    /// entry of a histogram is a count being kept, while the other
+    /// panels only count the slots which are filled.
+    pub fn memory(&self) -> usize {
+        match *self {
+            Panel::Aux(slots) | Panel::Tree(slots, _) => slots.iter().filter(|slot| slot.is_some()).count(),
+            Panel::Histogram(counts, _) => counts.len(),
+            Panel::Piles(piles, _) => piles.iter().map(|pile| pile.len()).sum(),
+            Panel::Network(..) => 0,
+        }
+    }
+}
+
+/// [Marker]
+/// Annotations an algorithm can ask to have drawn over the columns,
+/// such as the pivot and partition boundaries of a quicksort.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Marker {
+    /// The column at this index is a pivot.
+    Pivot(usize),
+    /// A boundary line just before the column at this index.
+    Boundary(usize),
+    /// The range of columns low..high currently being worked on.
+    Range(usize, usize),
+    /// An internal buffer at low..high, made of columns on loan.
+    Buffer(usize, usize),
+    /// The columns low..high being moved by a block swap or rotation.
+    Rotation(usize, usize),
+}
+
+/// [Heap Link]
+/// An edge of an implicit heap tree stored in the columns, from the
+/// index of a parent to the index of its child. Active links belong
+/// to the node which is currently being sifted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeapLink {
+    pub parent: usize,
+    pub child: usize,
+    pub active: bool,
+}
+
+/// [Bead]
+/// A bead on the abacus of a bead sort, above the column at position.
+/// It is the top bead of a column holding value, so it is drawn at the
+/// height of that value. Falling beads moved on the last step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bead {
+    pub position: usize,
+    pub value: i32,
+    pub falling: bool,
+}
+
+/// [Work]
+/// A pair of columns a worker compared, or moved one of, during the
+/// last round of a parallel algorithm. Work on a single column has
+/// both indices the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Work {
+    pub i: usize,
+    pub j: usize,
+    pub worker: usize,
+}
+
+/// [Sort Algorithm]
+/// A sorting algorithm which can be advanced one operation at a time.
+/// Implementors keep all of their progress in their own fields, so
+/// that they can be paused and resumed between any two steps, and
+/// cloned to be picked up again later from the same point.
+pub trait SortAlgorithm: CloneAlgorithm {
+    /// [Reset]
+    /// Forget all progress, so that the next step begins a brand new
+    /// sort of whatever the columns currently hold.
+    fn reset(&mut self);
+
+    /// [Step]
+    /// Perform exactly one operation on the columns and report what
+    /// it was. The direction is +1 for ascending and -1 for
+    /// descending. Once the columns are sorted, Step::Done is
+    /// returned for every following call.
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step;
+
+    /// [Panels]
+    /// Any extra state which should be drawn beneath the columns.
+    /// Most algorithms work purely within the columns, so by default
+    /// there is none.
+    fn panels(&self) -> Vec<Panel<'_>> {
+        vec![]
+    }
+
+    /// [Aux Memory]
+    /// How many values the algorithm is holding outside the columns
+    /// right now, or has set aside within them as a buffer. By default
+    /// this is whatever its panels hold.
+    fn aux_memory(&self) -> usize {
+        self.panels().iter().map(Panel::memory).sum()
+    }
+
+    /// [Markers]
+    /// Annotations to draw over the columns for the current step.
+    fn markers(&self) -> Vec<Marker> {
+        vec![]
+    }
+
+    /// [Heap Links]
+    /// The edges of the heap tree, for algorithms which arrange the
+    /// columns into an implicit heap.
+    fn heap_links(&self) -> Vec<HeapLink> {
+        vec![]
+    }
+
+    /// [Beads]
+    /// The beads of a bead sort, which are drawn instead of the
+    /// columns whenever there are any.
+    fn beads(&self) -> Vec<Bead> {
+        vec![]
+    }
+
+    /// [Work]
+    /// Who did what during the last round, for parallel algorithms
+    /// which perform a whole round per step.
+    fn work(&self) -> Vec<Work> {
+        vec![]
+    }
+
+    /// [Network]
+    /// The comparator network the algorithm runs, for the algorithms
+    /// which are one, so that it can be edited or saved.
+    fn network(&self) -> Option<&Network> {
+        None
+    }
+
+    /// [Set Network]
+    /// Replaces the comparator network the algorithm runs with one
+    /// which was drawn or loaded.
+    fn set_network(&mut self, _network: Network) {}
+
+    /// [Seed]
+    /// Starts the algorithm's random number generator over from the
+    /// given seed, for the algorithms which make random choices, so
+    /// that a run can be repeated exactly.
+    fn seed(&mut self, _seed: u64) {}
+
+    /// [Limit]
+    /// The most columns the algorithm will run on without asking
+    /// first, for the algorithms which could take practically forever.
+    fn limit(&self) -> Option<usize> {
+        None
+    }
+
+    /// [Refusal]
+    /// Why the algorithm won't sort the given values at all, for the
+    /// algorithms which need room for every value in their range,
+    /// rather than for every column. Unlike the limit, this can't be
+    /// overridden, since running anyway would mean running out of
+    /// memory.
+    fn refusal(&self, _values: &[i32]) -> Option<String> {
+        None
+    }
+
+    /// [Option]
+    /// A description of the algorithm's current setting, for the
+    /// algorithms which have one, such as the pivot rule.
+    fn option(&self) -> Option<String> {
+        None
+    }
+
+    /// [Next Option]
+    /// Cycles to the next setting. This takes effect as soon as the
+    /// algorithm can safely switch, without throwing away progress.
+    fn next_option(&mut self) {}
+
+    /// [Status]
+    /// A short description of what the algorithm is doing right now,
+    /// such as the gap in use.
+    fn status(&self) -> Option<String> {
+        None
+    }
+}
+
+/// [Clone Algorithm]
+/// Lets a boxed algorithm be cloned, so that its progress can be
+/// saved in the history. Every algorithm which is Clone gets this
+/// for free.
+pub trait CloneAlgorithm {
+    fn clone_box(&self) -> Box<dyn SortAlgorithm>;
+}
+
+impl<T: SortAlgorithm + Clone + 'static> CloneAlgorithm for T {
+    fn clone_box(&self) -> Box<dyn SortAlgorithm> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn SortAlgorithm> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+/// [Constructor]
+/// Creates a fresh instance of a registered algorithm.
+pub type Constructor = fn() -> Box<dyn SortAlgorithm>;
+
+/// [Registry]
+/// An ordered list of named algorithms. The order is the order that
+/// the left and right arrow keys cycle through them in.
+#[derive(Default)]
+pub struct Registry {
+    entries: Vec<(&'static str, Constructor)>,
+}
+
+impl Registry {
+    /// [Register]
+    /// Adds an algorithm to the end of the registry under a name.
+    pub fn register(&mut self, name: &'static str, constructor: Constructor) {
+        self.entries.push((name, constructor));
+    }
+
+    /// [Count]
+    /// The number of registered algorithms.
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// [Name]
+    /// The name the algorithm at index was registered with.
+    pub fn name(&self, index: usize) -> &'static str {
+        self.entries[index].0
+    }
+
+    /// [Find]
+    /// The index of the algorithm with the given name, ignoring case.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|(entry, _)| entry.eq_ignore_ascii_case(name))
+    }
+
+    /// [Create]
+    /// Builds a fresh instance of the algorithm at index.
+    pub fn create(&self, index: usize) -> Box<dyn SortAlgorithm> {
+        (self.entries[index].1)()
+    }
+}
+
+/// [Registry]
+/// Builds the registry of every algorithm the visualizer knows about.
+pub fn registry() -> Registry {
+    let mut registry = Registry::default();
+
+    registry.register("Bubble Sort", || Box::new(BubbleSort::default()));
+    registry.register("Cocktail Shaker Sort", || Box::new(CocktailShakerSort::default()));
+    registry.register("Odd-Even Sort", || Box::new(OddEvenSort::default()));
+    registry.register("Gnome Sort", || Box::new(GnomeSort::default()));
+    registry.register("Gnome Sort (Optimized)", || Box::new(GnomeSort::optimized()));
+    registry.register("Exchange Sort", || Box::new(ExchangeSort::default()));
+    registry.register("Circle Sort", || Box::new(CircleSort::default()));
+    registry.register("Selection Sort", || Box::new(SelectionSort::default()));
+    registry.register("Cycle Sort", || Box::new(CycleSort::default()));
+    registry.register("Tournament Sort", || Box::new(TournamentSort::default()));
+    registry.register("Insertion Sort", || Box::new(InsertionSort::default()));
+    registry.register("Binary Insertion Sort", || Box::new(BinaryInsertionSort::default()));
+    registry.register("Merge Sort (Top-Down)", || Box::new(TopDownMergeSort::default()));
+    registry.register("Merge Sort (Bottom-Up)", || Box::new(BottomUpMergeSort::default()));
+    registry.register("Merge Sort (In-Place)", || Box::new(InPlaceMergeSort::default()));
+    registry.register("Timsort", || Box::new(TimSort::default()));
+    registry.register("Patience Sort", || Box::new(PatienceSort::default()));
+    registry.register("Strand Sort", || Box::new(StrandSort::default()));
+    registry.register("WikiSort", || Box::new(WikiSort::default()));
+    registry.register("GrailSort", || Box::new(GrailSort::default()));
+    registry.register("Quicksort (Lomuto)", || Box::new(LomutoQuickSort::default()));
+    registry.register("Quicksort (Hoare)", || Box::new(HoareQuickSort::default()));
+    registry.register("Quicksort (Three-Way)", || Box::new(ThreeWayQuickSort::default()));
+    registry.register("Quicksort (Dual-Pivot)", || Box::new(DualPivotQuickSort::default()));
+    registry.register("Introsort", || Box::new(IntroSort::default()));
+    registry.register("Pdqsort", || Box::new(PdqSort::default()));
+    registry.register("Heapsort (Binary)", || Box::new(BinaryHeapSort::default()));
+    registry.register("Heapsort (Ternary)", || Box::new(TernaryHeapSort::default()));
+    registry.register("Weak-Heap Sort", || Box::new(WeakHeapSort::default()));
+    registry.register("Smoothsort", || Box::new(SmoothSort::default()));
+    registry.register("Shell Sort", || Box::new(ShellSort::default()));
+    registry.register("Comb Sort", || Box::new(CombSort::default()));
+    registry.register("Counting Sort", || Box::new(CountingSort::default()));
+    registry.register("Pigeonhole Sort", || Box::new(PigeonholeSort::default()));
+    registry.register("Bucket Sort", || Box::new(BucketSort::default()));
+    registry.register("Radix Sort (LSD)", || Box::new(LsdRadixSort::default()));
+    registry.register("Radix Sort (MSD)", || Box::new(MsdRadixSort::default()));
+    registry.register("American Flag Sort", || Box::new(AmericanFlagSort::default()));
+    registry.register("Sorting Network", || Box::new(NetworkSort::default()));
+    registry.register("Bitonic Sort", || Box::new(BitonicSort::default()));
+    registry.register("Odd-Even Merge Sort", || Box::new(OddEvenMergeSort::default()));
+    registry.register("Merge Sort (Parallel)", || Box::new(ParallelMergeSort::default()));
+    registry.register("Pancake Sort", || Box::new(PancakeSort::default()));
+    registry.register("Bead Sort", || Box::new(BeadSort::default()));
+    registry.register("Stooge Sort", || Box::new(StoogeSort::default()));
+    registry.register("Slowsort", || Box::new(SlowSort::default()));
+    registry.register("Bogosort", || Box::new(BogoSort::default()));
+    registry.register("Bozosort", || Box::new(BozoSort::default()));
+
+    registry
+}
+
+/// [Op Queue]
+/// Some algorithms are far easier to write by deciding a whole batch
+/// of operations at once, such as every comparison needed to sift a
+/// node down one level. Those operations are queued here, and then
+/// performed one per step. An algorithm must only decide its next
+/// batch once the queue has drained, so that its decisions always
+/// see the columns as they really are.
+#[derive(Clone, Default)]
+pub struct OpQueue {
+    ops: VecDeque<Step>,
+}
+
+impl OpQueue {
+    /// [Compare]
+    /// Queues a comparison of two columns.
+    pub fn compare(&mut self, i: usize, j: usize) {
+        self.ops.push_back(Step::Compare(i, j));
+    }
+
+    /// [Swap]
+    /// Queues a swap of two columns.
+    pub fn swap(&mut self, i: usize, j: usize) {
+        self.ops.push_back(Step::Swap(i, j));
+    }
+
+    /// [Clear]
+    /// Throws away every queued operation.
+    pub fn clear(&mut self) {
+        self.ops.clear();
+    }
+
+    /// [Pop]
+    /// Performs the next queued operation on the columns, if there is
+    /// one, and reports it. The comparison itself was already made
+    /// when it was queued, so it is only recorded.
+    pub fn pop(&mut self, columns: &mut Array) -> Option<Step> {
+        let step = self.ops.pop_front()?;
+        match step {
+            Step::Compare(i, j) => columns.record(Event::Compare(i, j)),
+            Step::Swap(i, j) => columns.swap(i, j),
+            _ => {}
+        }
+        Some(step)
+    }
+}
+
+/// [In Order]
+/// True when a may come before b in the given direction, which is +1
+/// for ascending and -1 for descending.
+pub fn in_order(a: i32, b: i32, direction: i32) -> bool {
+    direction * a <= direction * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The most steps a sort is given before it is taken to be stuck.
+    const MOST_STEPS: usize = 1_000_000;
+
+    /// [Sort]
+    /// Runs an algorithm on a copy of the columns until it is done, and
+    /// returns what it leaves behind.
+    pub(super) fn sort(algorithm: &mut dyn SortAlgorithm, columns: &[i32], direction: i32) -> Vec<i32> {
+        let mut columns = columns.to_vec();
+        let mut trace = Trace::default();
+        for _ in 0..MOST_STEPS {
+            if trace.step(algorithm, &mut columns, direction) == Step::Done {
+                return columns;
+            }
+        }
+        panic!("still sorting after {} steps", MOST_STEPS);
+    }
+
+    /// [Sorted]
+    /// The columns the way a sort in the given direction should leave
+    /// them.
+    pub(super) fn sorted(columns: &[i32], direction: i32) -> Vec<i32> {
+        let mut columns = columns.to_vec();
+        columns.sort_by_key(|&value| direction * value);
+        columns
+    }
+
+    /// [Shuffled]
+    /// n made up columns with plenty of repeats and some negatives,
+    /// always the same for the same n.
+    pub(super) fn shuffled(n: usize) -> Vec<i32> {
+        (0..n).map(|i| ((i * 7919 + 13) % 41) as i32 - 20).collect()
+    }
+
+    #[test]
+    fn registry_creates_what_it_names() {
+        let registry = registry();
+        assert_eq!(registry.name(0), "Bubble Sort");
+        for index in 0..registry.count() {
+            let mut algorithm = registry.create(index);
+            assert_eq!(Trace::default().step(algorithm.as_mut(), &mut [], 1), Step::Done, "{}", registry.name(index));
+        }
+    }
+
+    #[test]
+    fn every_algorithm_sorts() {
+        let registry = registry();
+        for index in 0..registry.count() {
+            // The hopeless ones are given well under their limit, so
+            // that the test doesn't take all day.
+            let columns = shuffled(registry.create(index).limit().map_or(100, |limit| limit / 2));
+            for direction in [1, -1] {
+                let mut algorithm = registry.create(index);
+                assert_eq!(sort(algorithm.as_mut(), &columns, direction), sorted(&columns, direction), "{} in direction {}", registry.name(index), direction);
+            }
+        }
+    }
+
+    #[test]
+    fn wide_ranges_are_refused() {
+        let registry = registry();
+        let named = |name| registry.create(registry.find(name).unwrap());
+        let columns = [i32::MIN, 0, i32::MAX];
+        for name in ["Counting Sort", "Pigeonhole Sort", "Bead Sort"] {
+            assert!(named(name).refusal(&columns).is_some(), "{}", name);
+        }
+        assert!(named("Counting Sort").refusal(&[1, 2, 3]).is_none());
+        assert!(named("Quicksort (Hoare)").refusal(&columns).is_none());
+    }
+}
@@ -0,0 +1,633 @@
+/*****************************************************************/
+//! [Sorting Networks]
+/*****************************************************************/
+//!
+//! A comparator network is a fixed list of compare-exchanges between
+//! wires, decided in advance, which never looks at the data to decide
+//! what to compare next. One which sorts every input is a sorting
+//! network. Networks are kept as layers of comparators, where no two
+//! comparators in a layer share a wire, so that a whole layer could
+//! happen at once.
+//!
+//! Networks can be built from Batcher's constructions, taken from the
+//! table of the smallest known networks for up to 16 wires, drawn by
+//! hand, or loaded from a text file. Whether a network really sorts
+//! is checked with the 0-1 principle: a comparator network sorts
+//! every input if and only if it sorts every input made of zeros and
+//! ones, so trying all 2^n of those proves it one way or the other.
+//!
+//! The text format has one layer per line, each comparator written as
+//! two wires joined by a colon, such as "0:1 2:3". The number of wires
+//! is given first on a line of its own, as "wires 4", and anything
+//! after a # is a comment.
+
+use super::{Array, Panel, SortAlgorithm, Step};
+
+/// [Max Verify Wires]
+/// Networks with more wires than this are too big to check every 0-1
+/// input of, at least while the visualizer waits.
+const MAX_VERIFY_WIRES: usize = 24;
+
+/// [Optimal]
+/// The smallest known sorting networks, indexed by number of wires.
+/// Up to 12 wires they are known to be as small as possible. The
+/// network for 15 wires is the 16 wire network with its last wire
+/// removed, so it is left empty here.
+const OPTIMAL: [&[&[(usize, usize)]]; 17] = [
+    &[],
+    &[],
+    // 2 wires, 1 comparator.
+    &[
+        &[(0, 1)],
+    ],
+    // 3 wires, 3 comparators.
+    &[
+        &[(0, 2)],
+        &[(0, 1)],
+        &[(1, 2)],
+    ],
+    // 4 wires, 5 comparators.
+    &[
+        &[(0, 1), (2, 3)],
+        &[(0, 2), (1, 3)],
+        &[(1, 2)],
+    ],
+    // 5 wires, 9 comparators.
+    &[
+        &[(0, 3), (1, 4)],
+        &[(0, 2), (1, 3)],
+        &[(0, 1), (2, 4)],
+        &[(1, 2), (3, 4)],
+        &[(2, 3)],
+    ],
+    // 6 wires, 12 comparators.
+    &[
+        &[(0, 5), (1, 3), (2, 4)],
+        &[(1, 2), (3, 4)],
+        &[(0, 3), (2, 5)],
+        &[(0, 1), (2, 3), (4, 5)],
+        &[(1, 2), (3, 4)],
+    ],
+    // 7 wires, 16 comparators.
+    &[
+        &[(0, 6), (2, 3), (4, 5)],
+        &[(0, 2), (1, 4), (3, 6)],
+        &[(0, 1), (2, 5), (3, 4)],
+        &[(1, 2), (4, 6)],
+        &[(2, 3), (4, 5)],
+        &[(1, 2), (3, 4), (5, 6)],
+    ],
+    // 8 wires, 19 comparators.
+    &[
+        &[(0, 2), (1, 3), (4, 6), (5, 7)],
+        &[(0, 4), (1, 5), (2, 6), (3, 7)],
+        &[(0, 1), (2, 3), (4, 5), (6, 7)],
+        &[(2, 4), (3, 5)],
+        &[(1, 4), (3, 6)],
+        &[(1, 2), (3, 4), (5, 6)],
+    ],
+    // 9 wires, 25 comparators.
+    &[
+        &[(0, 3), (1, 7), (2, 5), (4, 8)],
+        &[(0, 7), (2, 4), (3, 8), (5, 6)],
+        &[(0, 2), (1, 3), (4, 5), (7, 8)],
+        &[(1, 4), (3, 6), (5, 7)],
+        &[(0, 1), (2, 4), (3, 5), (6, 8)],
+        &[(2, 3), (4, 5), (6, 7)],
+        &[(1, 2), (3, 4), (5, 6)],
+    ],
+    // 10 wires, 29 comparators.
+    &[
+        &[(0, 8), (1, 9), (2, 7), (3, 5), (4, 6)],
+        &[(0, 2), (1, 4), (5, 8), (7, 9)],
+        &[(0, 3), (2, 4), (5, 7), (6, 9)],
+        &[(0, 1), (3, 6), (8, 9)],
+        &[(1, 5), (2, 3), (4, 8), (6, 7)],
+        &[(1, 2), (3, 5), (4, 6), (7, 8)],
+        &[(2, 3), (4, 5), (6, 7)],
+        &[(3, 4), (5, 6)],
+    ],
+    // 11 wires, 35 comparators.
+    &[
+        &[(0, 9), (1, 6), (2, 4), (3, 7), (5, 8)],
+        &[(0, 1), (3, 5), (4, 10), (6, 9), (7, 8)],
+        &[(1, 3), (2, 5), (4, 7), (8, 10)],
+        &[(0, 4), (1, 2), (3, 7), (5, 9), (6, 8)],
+        &[(0, 1), (2, 6), (4, 5), (7, 8), (9, 10)],
+        &[(2, 4), (3, 6), (5, 7), (8, 9)],
+        &[(1, 2), (3, 4), (5, 6), (7, 8)],
+        &[(2, 3), (4, 5), (6, 7)],
+    ],
+    // 12 wires, 39 comparators.
+    &[
+        &[(0, 8), (1, 7), (2, 6), (3, 11), (4, 10), (5, 9)],
+        &[(0, 1), (2, 5), (3, 4), (6, 9), (7, 8), (10, 11)],
+        &[(0, 2), (1, 6), (5, 10), (9, 11)],
+        &[(0, 3), (1, 2), (4, 6), (5, 7), (8, 11), (9, 10)],
+        &[(1, 4), (3, 5), (6, 8), (7, 10)],
+        &[(1, 3), (2, 5), (6, 9), (8, 10)],
+        &[(2, 3), (4, 5), (6, 7), (8, 9)],
+        &[(4, 6), (5, 7)],
+        &[(3, 4), (5, 6), (7, 8)],
+    ],
+    // 13 wires, 45 comparators.
+    &[
+        &[(0, 12), (1, 10), (2, 9), (3, 7), (5, 11), (6, 8)],
+        &[(1, 6), (2, 3), (4, 11), (7, 9), (8, 10)],
+        &[(0, 4), (1, 2), (3, 6), (7, 8), (9, 10), (11, 12)],
+        &[(4, 6), (5, 9), (8, 11), (10, 12)],
+        &[(0, 5), (3, 8), (4, 7), (6, 11), (9, 10)],
+        &[(0, 1), (2, 5), (6, 9), (7, 8), (10, 11)],
+        &[(1, 3), (2, 4), (5, 6), (9, 10)],
+        &[(1, 2), (3, 4), (5, 7), (6, 8)],
+        &[(2, 3), (4, 5), (6, 7), (8, 9)],
+        &[(3, 4), (5, 6)],
+    ],
+    // 14 wires, 51 comparators.
+    &[
+        &[(0, 1), (2, 3), (4, 5), (6, 7), (8, 9), (10, 11), (12, 13)],
+        &[(0, 2), (1, 3), (4, 8), (5, 9), (10, 12), (11, 13)],
+        &[(0, 4), (1, 2), (3, 7), (5, 8), (6, 10), (9, 13), (11, 12)],
+        &[(0, 6), (1, 5), (3, 9), (4, 10), (7, 13), (8, 12)],
+        &[(2, 10), (3, 11), (4, 6), (7, 9)],
+        &[(1, 3), (2, 8), (5, 11), (6, 7), (10, 12)],
+        &[(1, 4), (2, 6), (3, 5), (7, 11), (8, 10), (9, 12)],
+        &[(2, 4), (3, 6), (5, 8), (7, 10), (9, 11)],
+        &[(3, 4), (5, 6), (7, 8), (9, 10)],
+        &[(6, 7)],
+    ],
+    // 15 wires, made from the 16 wire network.
+    &[],
+    // 16 wires, 60 comparators.
+    &[
+        &[(0, 13), (1, 12), (2, 15), (3, 14), (4, 8), (5, 6), (7, 11), (9, 10)],
+        &[(0, 5), (1, 7), (2, 9), (3, 4), (6, 13), (8, 14), (10, 15), (11, 12)],
+        &[(0, 1), (2, 3), (4, 5), (6, 8), (7, 9), (10, 11), (12, 13), (14, 15)],
+        &[(0, 2), (1, 3), (4, 10), (5, 11), (6, 7), (8, 9), (12, 14), (13, 15)],
+        &[(1, 2), (3, 12), (4, 6), (5, 7), (8, 10), (9, 11), (13, 14)],
+        &[(1, 4), (2, 6), (5, 8), (7, 10), (9, 13), (11, 14)],
+        &[(2, 4), (3, 6), (9, 12), (11, 13)],
+        &[(3, 5), (6, 8), (7, 9), (10, 12)],
+        &[(3, 4), (5, 6), (7, 8), (9, 10), (11, 12)],
+        &[(6, 7), (8, 9)],
+    ],
+];
+
+/// [Verdict]
+/// The result of checking a network with the 0-1 principle. A network
+/// which fails does so on the given input, with bit w for wire w.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Verdict {
+    #[default]
+    Unchecked,
+    Sorts,
+    Fails(u64),
+    TooBig,
+}
+
+/// [Network]
+/// A comparator network on wires wires. Each comparator (i, j) has
+/// i < j, and leaves the value which belongs first on wire i.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Network {
+    wires: usize,
+    layers: Vec<Vec<(usize, usize)>>,
+}
+
+impl Network {
+    /// [New]
+    /// An empty network, with nothing on its wires.
+    pub fn new(wires: usize) -> Network {
+        Network { wires, layers: vec![] }
+    }
+
+    /// [From Layers]
+    /// A network with the given layers, dropping any comparators on
+    /// wires it doesn't have, and any layers that leaves empty.
+    fn from_layers(wires: usize, layers: Vec<Vec<(usize, usize)>>) -> Network {
+        let layers = layers
+            .into_iter()
+            .map(|layer| layer.into_iter().filter(|&(_, j)| j < wires).collect::<Vec<_>>())
+            .filter(|layer| !layer.is_empty())
+            .collect();
+        Network { wires, layers }
+    }
+
+    pub fn wires(&self) -> usize {
+        self.wires
+    }
+
+    pub fn layers(&self) -> &[Vec<(usize, usize)>] {
+        &self.layers
+    }
+
+    /// [Size]
+    /// How many comparators the network has.
+    pub fn size(&self) -> usize {
+        self.layers.iter().map(|layer| layer.len()).sum()
+    }
+
+    /// [Comparator]
+    /// The comparator at index, counting through the layers in order.
+    pub fn comparator(&self, mut index: usize) -> Option<(usize, usize)> {
+        for layer in &self.layers {
+            if index < layer.len() {
+                return Some(layer[index]);
+            }
+            index -= layer.len();
+        }
+        None
+    }
+
+    /// [Push]
+    /// Adds a comparator between wires a and b after everything else
+    /// on those wires. It joins the earliest layer it can, which is
+    /// just after the last layer using either wire.
+    pub fn push(&mut self, a: usize, b: usize) {
+        let (i, j) = (a.min(b), a.max(b));
+        if i == j || j >= self.wires {
+            return;
+        }
+
+        let layer = match self.layers.iter().rposition(|layer| layer.iter().any(|&(x, y)| x == i || x == j || y == i || y == j)) {
+            Some(last) => last + 1,
+            None => 0,
+        };
+        if layer == self.layers.len() {
+            self.layers.push(vec![]);
+        }
+        self.layers[layer].push((i, j));
+    }
+
+    /// [Pop]
+    /// Removes the comparator at the end of the last layer.
+    pub fn pop(&mut self) {
+        if let Some(layer) = self.layers.last_mut() {
+            layer.pop();
+            if layer.is_empty() {
+                self.layers.pop();
+            }
+        }
+    }
+
+    /// [Resize]
+    /// The same network on a different number of wires, losing any
+    /// comparators on wires which are no longer there.
+    pub fn resize(&self, wires: usize) -> Network {
+        Network::from_layers(wires, self.layers.clone())
+    }
+
+    /// [Bitonic]
+    /// Batcher's bitonic sorter. Sorted runs are merged in pairs, by
+    /// first comparing each element of the left run with its mirror
+    /// image in the right run, then halving the distance between
+    /// compared elements each layer until it reaches one.
+    ///
+    /// Like Batcher's other network, it is really only defined for a
+    /// power of two wires. It is built for the next power of two up,
+    /// as if the missing wires held values which belong at the very
+    /// end, so comparisons with them never swap and are left out.
+    pub fn bitonic(wires: usize) -> Network {
+        let size = wires.next_power_of_two();
+        let mut layers = vec![];
+
+        let mut block = 2;
+        while block <= size {
+            layers.push((0..size).filter(|&i| i % block < block / 2).map(|i| (i, i - i % block + block - 1 - i % block)).collect());
+
+            let mut gap = block / 4;
+            while gap > 0 {
+                layers.push((0..size).filter(|&i| i & gap == 0).map(|i| (i, i + gap)).collect());
+                gap /= 2;
+            }
+            block *= 2;
+        }
+        Network::from_layers(wires, layers)
+    }
+
+    /// [Odd-Even Merge]
+    /// Batcher's odd-even merge sort. Runs are merged in pairs by
+    /// merging their even and odd elements separately, which leaves at
+    /// most neighbouring pairs out of order, then fixing those up.
+    /// Unrolled, each layer compares elements gap apart, within blocks
+    /// of twice the size of the runs being merged.
+    pub fn odd_even_merge(wires: usize) -> Network {
+        let size = wires.next_power_of_two();
+        let mut layers = vec![];
+
+        let mut run = 1;
+        while run < size {
+            let mut gap = run;
+            while gap > 0 {
+                let mut layer = vec![];
+                for j in (gap % run..size - gap).step_by(2 * gap) {
+                    for i in 0..gap.min(size - j - gap) {
+                        if (i + j) / (2 * run) == (i + j + gap) / (2 * run) {
+                            layer.push((i + j, i + j + gap));
+                        }
+                    }
+                }
+                layers.push(layer);
+                gap /= 2;
+            }
+            run *= 2;
+        }
+        Network::from_layers(wires, layers)
+    }
+
+    /// [Optimal]
+    /// The smallest known sorting network on this many wires, if there
+    /// is one in the table.
+    pub fn optimal(wires: usize) -> Option<Network> {
+        match wires {
+            0 | 1 => Some(Network::new(wires)),
+            15 => Network::optimal(16).map(|network| network.resize(15)),
+            2..=16 => Some(Network::from_layers(wires, OPTIMAL[wires].iter().map(|layer| layer.to_vec()).collect())),
+            _ => None,
+        }
+    }
+
+    /// [Verify]
+    /// Checks every 0-1 input. The inputs are tried 64 at a time, with
+    /// each wire holding one bit of each of the 64 inputs, so that a
+    /// comparator is just an AND for the wire which gets the smaller
+    /// value and an OR for the wire which gets the larger. The first
+    /// six wires count through the 64 inputs, and the rest through the
+    /// batches.
+    pub fn verify(&self) -> Verdict {
+        let n = self.wires;
+        if n > MAX_VERIFY_WIRES {
+            return Verdict::TooBig;
+        }
+
+        const PATTERNS: [u64; 6] = [0xAAAA_AAAA_AAAA_AAAA, 0xCCCC_CCCC_CCCC_CCCC, 0xF0F0_F0F0_F0F0_F0F0, 0xFF00_FF00_FF00_FF00, 0xFFFF_0000_FFFF_0000, 0xFFFF_FFFF_0000_0000];
+        let valid = if n < 6 { (1u64 << (1 << n)) - 1 } else { u64::MAX };
+        let batches = 1u64 << n.saturating_sub(6);
+
+        let mut bits = vec![0u64; n];
+        for batch in 0..batches {
+            for (w, bit) in bits.iter_mut().enumerate() {
+                *bit = if w < 6 { PATTERNS[w] } else if batch >> (w - 6) & 1 == 1 { u64::MAX } else { 0 };
+            }
+
+            for layer in &self.layers {
+                for &(i, j) in layer {
+                    let (a, b) = (bits[i], bits[j]);
+                    bits[i] = a & b;
+                    bits[j] = a | b;
+                }
+            }
+
+            // A one just before a zero means that input isn't sorted.
+            let unsorted = bits.windows(2).fold(0, |unsorted, pair| unsorted | (pair[0] & !pair[1])) & valid;
+            if unsorted != 0 {
+                return Verdict::Fails(batch << 6 | unsorted.trailing_zeros() as u64);
+            }
+        }
+        Verdict::Sorts
+    }
+
+    /// [Text]
+    /// The network written out in the text format.
+    pub fn text(&self) -> String {
+        let mut text = format!("# {} comparators in {} layers\nwires {}\n", self.size(), self.layers.len(), self.wires);
+        for layer in &self.layers {
+            let comparators: Vec<String> = layer.iter().map(|(i, j)| format!("{}:{}", i, j)).collect();
+            text.push_str(&comparators.join(" "));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// [Parse]
+    /// Reads a network back from the text format. Comparators may be
+    /// written either way round, but must fit in the wires and may not
+    /// share a wire with another comparator in the same layer.
+    pub fn parse(text: &str) -> Result<Network, String> {
+        let mut wires = None;
+        let mut layers = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(count) = line.strip_prefix("wires") {
+                wires = Some(count.trim().parse::<usize>().map_err(|_| format!("line {}: bad number of wires", number + 1))?);
+                continue;
+            }
+
+            let wires = wires.ok_or_else(|| format!("line {}: the number of wires must come first", number + 1))?;
+            let mut layer: Vec<(usize, usize)> = vec![];
+            for comparator in line.split_whitespace() {
+                let bad = || format!("line {}: bad comparator {}", number + 1, comparator);
+                let (a, b) = comparator.split_once(':').ok_or_else(bad)?;
+                let (a, b) = (a.parse::<usize>().map_err(|_| bad())?, b.parse::<usize>().map_err(|_| bad())?);
+                let (i, j) = (a.min(b), a.max(b));
+
+                if i == j || j >= wires || layer.iter().any(|&(x, y)| x == i || x == j || y == i || y == j) {
+                    return Err(bad());
+                }
+                layer.push((i, j));
+            }
+            layers.push(layer);
+        }
+
+        let wires = wires.ok_or("no number of wires given")?;
+        Ok(Network { wires, layers })
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Source {
+    #[default]
+    OddEvenMerge,
+    Bitonic,
+    Optimal,
+    Custom,
+}
+
+impl Source {
+    fn name(&self) -> &'static str {
+        match self {
+            Source::OddEvenMerge => "Batcher odd-even merge",
+            Source::Bitonic => "bitonic",
+            Source::Optimal => "smallest known",
+            Source::Custom => "custom",
+        }
+    }
+}
+
+/// [Network Sort]
+/// Runs a comparator network over the columns, one comparator per
+/// step, layer by layer. The network is either built for however many
+/// columns there are, or is a custom network which was drawn or
+/// loaded. Custom networks are run as they are, so one which doesn't
+/// sort will happily finish without the columns sorted, which the
+/// verdict will already have said.
+#[derive(Clone, Default)]
+pub struct NetworkSort {
+    source: Source,
+    network: Network,
+    verdict: Verdict,
+    next: usize,
+    fallback: bool,
+    started: bool,
+}
+
+impl NetworkSort {
+    /// [Build]
+    /// Sets up the network to run on len columns.
+    fn build(&mut self, len: usize) {
+        self.fallback = false;
+        self.network = match self.source {
+            Source::OddEvenMerge => Network::odd_even_merge(len),
+            Source::Bitonic => Network::bitonic(len),
+            Source::Optimal => Network::optimal(len).unwrap_or_else(|| {
+                self.fallback = true;
+                Network::odd_even_merge(len)
+            }),
+            Source::Custom => self.network.resize(len),
+        };
+        self.verdict = self.network.verify();
+    }
+}
+
+impl SortAlgorithm for NetworkSort {
+    fn reset(&mut self) {
+        *self = NetworkSort { source: self.source, network: self.network.clone(), verdict: self.verdict, ..NetworkSort::default() };
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            if self.source != Source::Custom || self.network.wires() != columns.len() {
+                self.build(columns.len());
+            }
+        }
+
+        let Some((i, j)) = self.network.comparator(self.next) else {
+            return Step::Done;
+        };
+        self.next += 1;
+
+        if columns.compare(i, j, direction) {
+            Step::Compare(i, j)
+        } else {
+            columns.swap(i, j);
+            Step::Swap(i, j)
+        }
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        vec![Panel::Network(&self.network, self.next)]
+    }
+
+    fn network(&self) -> Option<&Network> {
+        Some(&self.network)
+    }
+
+    /// [Set Network]
+    /// Switches to a custom network, starting from scratch.
+    fn set_network(&mut self, network: Network) {
+        let verdict = network.verify();
+        *self = NetworkSort { source: Source::Custom, network, verdict, ..NetworkSort::default() };
+    }
+
+    fn option(&self) -> Option<String> {
+        Some(format!("network: {}", self.source.name()))
+    }
+
+    /// [Next Option]
+    /// Switches to the next way of building the network. A sort part
+    /// way through finishes on the network it started with, since a
+    /// new network would start over on columns which are already
+    /// partly sorted, so the change takes effect from the next reset.
+    /// Custom networks are only reached by drawing or loading one, so
+    /// they are skipped.
+    fn next_option(&mut self) {
+        self.source = match self.source {
+            Source::OddEvenMerge => Source::Bitonic,
+            Source::Bitonic => Source::Optimal,
+            Source::Optimal | Source::Custom => Source::OddEvenMerge,
+        };
+    }
+
+    fn status(&self) -> Option<String> {
+        if self.network.wires() == 0 {
+            return None;
+        }
+
+        let mut status = format!("{} comparators in {} layers - ", self.network.size(), self.network.layers().len());
+        if self.fallback {
+            status.push_str(&format!("none known for {} wires - ", self.network.wires()));
+        }
+        match self.verdict {
+            Verdict::Unchecked => {}
+            Verdict::Sorts => status.push_str("sorts every 0-1 input"),
+            Verdict::Fails(input) => {
+                let bits: String = (0..self.network.wires()).map(|w| if input >> w & 1 == 1 { '1' } else { '0' }).collect();
+                status.push_str(&format!("fails on 0-1 input {}", bits));
+            }
+            Verdict::TooBig => status.push_str("too many wires to verify"),
+        }
+        Some(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_networks_sort() {
+        for wires in 0..=20 {
+            assert_eq!(Network::bitonic(wires).verify(), Verdict::Sorts, "bitonic on {} wires", wires);
+            assert_eq!(Network::odd_even_merge(wires).verify(), Verdict::Sorts, "odd-even merge on {} wires", wires);
+        }
+    }
+
+    #[test]
+    fn optimal_networks_sort() {
+        for wires in 0..=16 {
+            let network = Network::optimal(wires).unwrap();
+            assert_eq!(network.verify(), Verdict::Sorts, "optimal on {} wires", wires);
+        }
+        assert_eq!(Network::optimal(14).unwrap().size(), 51);
+        assert_eq!(Network::optimal(16).unwrap().size(), 60);
+        assert_eq!(Network::optimal(17), None);
+    }
+
+    #[test]
+    fn broken_networks_fail() {
+        let mut network = Network::optimal(8).unwrap();
+        network.pop();
+        let Verdict::Fails(input) = network.verify() else {
+            panic!("a network missing its last comparator still sorts");
+        };
+
+        // The input it fails on really does come out unsorted.
+        let mut values: Vec<u64> = (0..8).map(|wire| input >> wire & 1).collect();
+        for layer in network.layers() {
+            for &(i, j) in layer {
+                if values[i] > values[j] {
+                    values.swap(i, j);
+                }
+            }
+        }
+        assert!(values.windows(2).any(|pair| pair[0] > pair[1]));
+
+        assert_eq!(Network::new(MAX_VERIFY_WIRES + 1).verify(), Verdict::TooBig);
+    }
+
+    #[test]
+    fn text_reads_back() {
+        for network in [Network::optimal(10).unwrap(), Network::bitonic(13), Network::new(3)] {
+            assert_eq!(Network::parse(&network.text()), Ok(network));
+        }
+        assert!(Network::parse("wires 4\n0:4").is_err());
+        assert!(Network::parse("wires 4\n0:1 1:2").is_err());
+        assert!(Network::parse("0:1").is_err());
+    }
+}
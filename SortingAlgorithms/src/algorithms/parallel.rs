@@ -0,0 +1,489 @@
+/*****************************************************************/
+//! [Parallel Sorts]
+/*****************************************************************/
+//!
+//! Sorts built from rounds of operations which don't depend on each
+//! other, so that every operation in a round can happen at the same
+//! time on a different worker. Each step performs a whole round, and
+//! reports which worker did what, so that the workers can be told
+//! apart by colour.
+//!
+//! Normally a round is simply performed one operation after another,
+//! which is all the visualizer needs. In real mode the round is
+//! actually run on rayon's thread pool, and timed with chrono against
+//! running the very same round on a single thread, so the status can
+//! show how much of a speedup the workers really managed. On a
+//! hundred columns the answer is usually none at all, since handing
+//! out the work costs more than doing it.
+
+use std::marker::PhantomData;
+
+use chrono::{TimeDelta, Utc};
+use rayon::prelude::*;
+
+use super::{Array, Event, in_order, Network, Panel, SortAlgorithm, Step, Work};
+
+/// [Workers]
+/// How many workers rayon has, which is one per CPU by default.
+fn workers() -> usize {
+    rayon::current_num_threads()
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Mode {
+    #[default]
+    Simulated,
+    Real,
+}
+
+impl Mode {
+    fn name(&self) -> &'static str {
+        match self {
+            Mode::Simulated => "simulated",
+            Mode::Real => "real",
+        }
+    }
+
+    fn next(&self) -> Mode {
+        match self {
+            Mode::Simulated => Mode::Real,
+            Mode::Real => Mode::Simulated,
+        }
+    }
+}
+
+/// [Timing]
+/// The wall-clock time spent on the rounds run in real mode, both
+/// single threaded and with rayon.
+#[derive(Clone, Copy, Default)]
+struct Timing {
+    sequential: TimeDelta,
+    parallel: TimeDelta,
+    rounds: usize,
+}
+
+impl Timing {
+    /// [Time]
+    /// Runs a round both ways, on a copy of the columns single threaded
+    /// and then on the columns themselves with rayon, and adds up how
+    /// long each took. Returns whatever the rayon run returned.
+    fn time<R>(&mut self, columns: &mut [i32], round: impl Fn(&mut [i32], bool) -> R) -> R {
+        let mut copy = columns.to_vec();
+
+        let start = Utc::now();
+        round(&mut copy, false);
+        self.sequential += Utc::now() - start;
+
+        let start = Utc::now();
+        let result = round(columns, true);
+        self.parallel += Utc::now() - start;
+
+        self.rounds += 1;
+        result
+    }
+
+    /// [Status]
+    /// The speedup so far, once there has been a real round.
+    fn status(&self) -> Option<String> {
+        if self.rounds == 0 {
+            return None;
+        }
+        let sequential = self.sequential.num_nanoseconds().unwrap_or(0) as f64;
+        let parallel = self.parallel.num_nanoseconds().unwrap_or(0).max(1) as f64;
+        Some(format!("speedup: {:.2}x over {} real rounds", sequential / parallel, self.rounds))
+    }
+}
+
+/// [Family]
+/// A family of sorting networks, with one for any number of columns.
+/// The layers of the network are the rounds.
+pub trait Family: Clone + Default {
+    fn network(len: usize) -> Network;
+}
+
+/// [Bitonic]
+/// Batcher's bitonic sorter.
+#[derive(Clone, Default)]
+pub struct Bitonic;
+
+impl Family for Bitonic {
+    fn network(len: usize) -> Network {
+        Network::bitonic(len)
+    }
+}
+
+/// [Odd-Even Merge]
+/// Batcher's odd-even merge sort.
+#[derive(Clone, Default)]
+pub struct OddEvenMerge;
+
+impl Family for OddEvenMerge {
+    fn network(len: usize) -> Network {
+        Network::odd_even_merge(len)
+    }
+}
+
+/// [Exchange]
+/// Performs a round of compare-exchanges, in parallel with rayon or
+/// on this thread. Each column works out its own new value from a
+/// snapshot of the round before, given the column it is paired with,
+/// which is what lets rayon hand the columns to any worker it likes.
+fn exchange(columns: &mut [i32], partners: &[Option<usize>], direction: i32, parallel: bool) {
+    let old = columns.to_vec();
+    let update = |(i, column): (usize, &mut i32)| {
+        if let Some(partner) = partners[i] {
+            let (first, second) = (i.min(partner), i.max(partner));
+            if !in_order(old[first], old[second], direction) {
+                *column = old[partner];
+            }
+        }
+    };
+
+    if parallel {
+        columns.par_iter_mut().enumerate().for_each(update);
+    } else {
+        columns.iter_mut().enumerate().for_each(update);
+    }
+}
+
+/// [Round Sort]
+/// Runs a sorting network one layer per step, as a round. The
+/// comparators of a round are shared out between the workers in even
+/// slices, the same way rayon would split them.
+#[derive(Clone, Default)]
+pub struct RoundSort<F: Family> {
+    family: PhantomData<F>,
+    network: Network,
+    round: usize,
+    work: Vec<Work>,
+    mode: Mode,
+    timing: Timing,
+    started: bool,
+}
+
+pub type BitonicSort = RoundSort<Bitonic>;
+pub type OddEvenMergeSort = RoundSort<OddEvenMerge>;
+
+impl<F: Family + 'static> SortAlgorithm for RoundSort<F> {
+    fn reset(&mut self) {
+        *self = RoundSort { mode: self.mode, ..RoundSort::default() };
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            self.network = F::network(columns.len());
+        }
+
+        let Some(round) = self.network.layers().get(self.round) else {
+            self.work.clear();
+            return Step::Done;
+        };
+
+        match self.mode {
+            Mode::Simulated => {
+                for &(i, j) in round {
+                    if !columns.compare(i, j, direction) {
+                        columns.swap(i, j);
+                    }
+                }
+            }
+            Mode::Real => {
+                let mut partners = vec![None; columns.len()];
+                for &(i, j) in round {
+                    partners[i] = Some(j);
+                    partners[j] = Some(i);
+                }
+                let old = columns.values().to_vec();
+                self.timing.time(columns.untraced(), |columns, parallel| exchange(columns, &partners, direction, parallel));
+
+                // The threads can't record their own work, so it is
+                // recorded afterwards, from the columns which changed.
+                for &(i, j) in round {
+                    columns.record(Event::Compare(i, j));
+                    if columns.values()[i] != old[i] {
+                        columns.record(Event::Swap(i, j));
+                    }
+                }
+            }
+        }
+
+        let workers = workers();
+        self.work = round.iter().enumerate().map(|(k, &(i, j))| Work { i, j, worker: k * workers / round.len() }).collect();
+        self.round += 1;
+        Step::Round
+    }
+
+    fn work(&self) -> Vec<Work> {
+        self.work.clone()
+    }
+
+    fn option(&self) -> Option<String> {
+        Some(format!("mode: {}", self.mode.name()))
+    }
+
+    fn next_option(&mut self) {
+        self.mode = self.mode.next();
+    }
+
+    fn status(&self) -> Option<String> {
+        if !self.started {
+            return None;
+        }
+        let round = format!("round {}/{} - workers: {}", self.round, self.network.layers().len(), workers());
+        match self.timing.status() {
+            Some(timing) => Some(format!("{} - {}", round, timing)),
+            None => Some(round),
+        }
+    }
+}
+
+/// [Merge]
+/// One of the merges of a level of the parallel merge sort, merging
+/// low..mid with mid..high into the same place in the auxiliary
+/// buffer. Left, right and out are how far along each part it is.
+#[derive(Clone, Copy)]
+struct Merge {
+    mid: usize,
+    high: usize,
+    left: usize,
+    right: usize,
+    out: usize,
+}
+
+/// [Merge Chunk]
+/// Merges the two runs of a chunk in one go, the first of which is
+/// width long, using a copy of the first run. The threads can't
+/// record into the trace, so the events are handed back instead, in
+/// the order the merge made them and indexed within the chunk.
+fn merge_chunk(chunk: &mut [i32], width: usize, direction: i32) -> Vec<Event> {
+    if chunk.len() <= width {
+        return vec![];
+    }
+
+    let mut events: Vec<Event> = (0..width).map(Event::Read).collect();
+    let left = chunk[..width].to_vec();
+    let (mut l, mut r, mut out) = (0, width, 0);
+    while l < left.len() {
+        // The copied run still counts as being where it came from.
+        let take_left = r == chunk.len() || {
+            events.push(Event::Compare(l, r));
+            in_order(left[l], chunk[r], direction)
+        };
+        if take_left {
+            chunk[out] = left[l];
+            l += 1;
+        } else {
+            chunk[out] = chunk[r];
+            r += 1;
+        }
+        events.push(Event::Write(out, chunk[out]));
+        out += 1;
+    }
+    events
+}
+
+/// [Parallel Merge Sort]
+/// Bottom-up merge sort, where all of the merges of a level happen at
+/// once, each on its own worker. Every step, each merge still going
+/// writes its next element into the auxiliary buffer, and once they
+/// have all finished, the level is copied back in a single round. In
+/// real mode a whole level is done in one step, with rayon handing a
+/// chunk of the columns to each worker. Each worker merges in place
+/// from a copy of its first run, so real mode records the same
+/// compares, but writes straight to the columns with no aux writes.
+#[derive(Clone, Default)]
+pub struct ParallelMergeSort {
+    aux: Vec<Option<i32>>,
+    merges: Vec<Merge>,
+    width: usize,
+    work: Vec<Work>,
+    mode: Mode,
+    timing: Timing,
+}
+
+impl ParallelMergeSort {
+    /// [Worker]
+    /// The worker doing the merge at index.
+    fn worker(&self, merge: usize) -> usize {
+        merge * workers() / self.merges.len().max(1)
+    }
+}
+
+impl SortAlgorithm for ParallelMergeSort {
+    fn reset(&mut self) {
+        *self = ParallelMergeSort { mode: self.mode, ..ParallelMergeSort::default() };
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        let len = columns.len();
+        if self.width == 0 {
+            self.width = 1;
+            self.aux = vec![None; len];
+        }
+
+        if self.width >= len {
+            self.work.clear();
+            return Step::Done;
+        }
+        let width = self.width;
+
+        if self.merges.is_empty() {
+            self.merges = (0..len)
+                .step_by(2 * width)
+                .map(|low| Merge { mid: (low + width).min(len), high: (low + 2 * width).min(len), left: low, right: (low + width).min(len), out: low })
+                .collect();
+
+            if self.mode == Mode::Real {
+                let chunks: Vec<Vec<Event>> = self.timing.time(columns.untraced(), |columns, parallel| {
+                    if parallel {
+                        columns.par_chunks_mut(2 * width).map(|chunk| merge_chunk(chunk, width, direction)).collect()
+                    } else {
+                        columns.chunks_mut(2 * width).map(|chunk| merge_chunk(chunk, width, direction)).collect()
+                    }
+                });
+
+                // Each chunk's events are recorded one chunk after
+                // another, though the workers made them all at once.
+                for (k, events) in chunks.into_iter().enumerate() {
+                    let low = k * 2 * width;
+                    let mut chunk = columns.slice(low, (low + 2 * width).min(len));
+                    for event in events {
+                        chunk.record(event);
+                    }
+                }
+
+                self.work = (0..len).map(|i| Work { i, j: i, worker: self.worker(i / (2 * width)) }).collect();
+                self.merges.clear();
+                self.width *= 2;
+                return Step::Round;
+            }
+        }
+
+        if self.merges.iter().all(|merge| merge.out == merge.high) {
+            // Copy the whole level back at once.
+            self.work = (0..len).map(|i| Work { i, j: i, worker: self.worker(i / (2 * width)) }).collect();
+            for (i, slot) in self.aux.iter_mut().enumerate() {
+                columns.write(i, slot.take().unwrap());
+            }
+            self.merges.clear();
+            self.width *= 2;
+            return Step::Round;
+        }
+
+        let mut work = vec![];
+        for (k, merge) in self.merges.iter_mut().enumerate() {
+            if merge.out == merge.high {
+                continue;
+            }
+
+            let take_left = merge.right == merge.high || (merge.left < merge.mid && columns.compare(merge.left, merge.right, direction));
+            let (i, j) = (merge.left.min(merge.mid - 1), merge.right.min(merge.high - 1));
+            let from = if take_left { merge.left } else { merge.right };
+            let value = columns.read(from);
+            self.aux[merge.out] = Some(value);
+            columns.aux_write(merge.out, value);
+            if take_left {
+                merge.left += 1;
+            } else {
+                merge.right += 1;
+            }
+            merge.out += 1;
+            work.push((k, i, j));
+        }
+
+        self.work = work.into_iter().map(|(k, i, j)| Work { i, j, worker: self.worker(k) }).collect();
+        Step::Round
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        vec![Panel::Aux(&self.aux)]
+    }
+
+    fn work(&self) -> Vec<Work> {
+        self.work.clone()
+    }
+
+    fn option(&self) -> Option<String> {
+        Some(format!("mode: {}", self.mode.name()))
+    }
+
+    fn next_option(&mut self) {
+        self.mode = self.mode.next();
+    }
+
+    fn status(&self) -> Option<String> {
+        if self.width == 0 {
+            return None;
+        }
+        let level = format!("width {} - workers: {}", self.width, workers());
+        match self.timing.status() {
+            Some(timing) => Some(format!("{} - {}", level, timing)),
+            None => Some(level),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{shuffled, sort, sorted};
+    use super::super::{Constructor, Trace};
+    use super::*;
+
+    #[test]
+    fn both_modes_sort_every_length() {
+        let sorts: [Constructor; 3] =
+            [|| Box::new(BitonicSort::default()), || Box::new(OddEvenMergeSort::default()), || Box::new(ParallelMergeSort::default())];
+        for create in sorts {
+            for len in 0..=40 {
+                let columns = shuffled(len);
+                let mut simulated = create();
+                let mut real = create();
+                real.next_option();
+                assert_eq!(real.option(), Some("mode: real".to_string()));
+                for direction in [1, -1] {
+                    simulated.reset();
+                    real.reset();
+                    assert_eq!(sort(simulated.as_mut(), &columns, direction), sorted(&columns, direction));
+                    assert_eq!(sort(real.as_mut(), &columns, direction), sorted(&columns, direction));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rounds_never_share_a_column() {
+        for len in [8, 13, 32] {
+            for network in [Bitonic::network(len), OddEvenMerge::network(len)] {
+                for round in network.layers() {
+                    let mut seen = vec![false; len];
+                    for &(i, j) in round {
+                        assert!(i < j);
+                        assert!(!seen[i] && !seen[j]);
+                        seen[i] = true;
+                        seen[j] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn real_merges_record_the_same_compares() {
+        let mut stats = vec![];
+        for real in [false, true] {
+            let mut algorithm = ParallelMergeSort::default();
+            if real {
+                algorithm.next_option();
+            }
+            let mut columns = shuffled(100);
+            let mut trace = Trace::default();
+            while trace.step(&mut algorithm, &mut columns, 1) != Step::Done {}
+            assert_eq!(columns, sorted(&columns, 1));
+            stats.push(trace.stats());
+        }
+        assert!(stats[0].compares > 0);
+        assert_eq!(stats[0].compares, stats[1].compares);
+        assert_eq!(stats[1].aux_writes, 0);
+    }
+}
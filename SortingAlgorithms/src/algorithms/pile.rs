@@ -0,0 +1,258 @@
+/*****************************************************************/
+//! [Pile Sorts]
+/*****************************************************************/
+//!
+//! Sorts which split the columns into sorted sequences on the side,
+//! and merge them back together. Patience sort deals the columns out
+//! into piles like a game of solitaire, and strand sort pulls out one
+//! increasing strand at a time. The side structures are drawn in a
+//! strip beneath the columns.
+
+use super::{Array, Marker, Panel, SortAlgorithm, Step};
+
+/// [Patience Sort]
+/// Deals the columns out one at a time onto piles, where each card
+/// goes on the leftmost pile whose top card it may sit on, or on a new
+/// pile at the right if there is none. Every pile then reads in order
+/// from the top down, and the tops read in order from left to right,
+/// so the right pile is found by binary search. The piles are then
+/// merged back into the columns, taking the best top card each time.
+/// Empty piles keep their place, so that the piles don't jump around.
+/// Origins are the columns the cards were dealt from, which is what
+/// comparisons between cards are recorded against.
+#[derive(Clone, Default)]
+pub struct PatienceSort {
+    piles: Vec<Vec<i32>>,
+    origins: Vec<Vec<usize>>,
+    pointer: usize,
+    out: usize,
+    active: Option<usize>,
+    merging: bool,
+}
+
+impl PatienceSort {
+    /// [Top]
+    /// The column the top card of a pile was dealt from, and the card,
+    /// unless the pile is empty.
+    fn top(&self, pile: usize) -> Option<(usize, i32)> {
+        Some((*self.origins[pile].last()?, *self.piles[pile].last()?))
+    }
+}
+
+impl SortAlgorithm for PatienceSort {
+    fn reset(&mut self) {
+        *self = PatienceSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.merging && self.pointer < columns.len() {
+            let i = self.pointer;
+            let card = columns.read(i);
+
+            // The card goes to the right of every pile whose top card
+            // must come before it.
+            let (mut low, mut high) = (0, self.piles.len());
+            while low < high {
+                let mid = (low + high) / 2;
+                if columns.compare_held((i, card), self.top(mid).unwrap(), direction) {
+                    high = mid;
+                } else {
+                    low = mid + 1;
+                }
+            }
+
+            let pile = low;
+            if pile == self.piles.len() {
+                self.piles.push(vec![]);
+                self.origins.push(vec![]);
+            }
+            self.piles[pile].push(card);
+            self.origins[pile].push(i);
+            self.active = Some(pile);
+            self.pointer += 1;
+            return Step::Read(i);
+        }
+        self.merging = true;
+
+        if self.out >= columns.len() {
+            self.active = None;
+            return Step::Done;
+        }
+
+        // A later pile only takes over if its top must come first.
+        let mut best: Option<usize> = None;
+        for p in 0..self.piles.len() {
+            let Some(top) = self.top(p) else {
+                continue;
+            };
+            if best.is_none_or(|best| !columns.compare_held(self.top(best).unwrap(), top, direction)) {
+                best = Some(p);
+            }
+        }
+
+        let pile = best.unwrap();
+        let i = self.out;
+        self.origins[pile].pop();
+        columns.write(i, self.piles[pile].pop().unwrap());
+        self.active = Some(pile);
+        self.out += 1;
+        Step::Write(i)
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        vec![Panel::Piles(&self.piles, self.active)]
+    }
+
+    fn status(&self) -> Option<String> {
+        Some(format!("piles: {}", self.piles.len()))
+    }
+}
+
+/// [Strand Sort]
+/// Pulls a strand out of the columns which have not been sorted yet,
+/// by taking the first of them and then every one after it which may
+/// follow the last one taken. The strand goes into the auxiliary
+/// buffer, and whatever is left over is packed to the left. The
+/// strand is then merged with the sorted result at the end of the
+/// columns, into the space the strand left behind, and the next
+/// strand is pulled out of what remains. The unsorted columns are
+/// 0..remaining, and the rest are the result. Origins are the columns
+/// the values in the strand were taken from, which is what
+/// comparisons with them are recorded against.
+#[derive(Clone, Default)]
+pub struct StrandSort {
+    strand: Vec<Option<i32>>,
+    origins: Vec<usize>,
+    length: usize,
+    remaining: usize,
+    pointer: usize,
+    write: usize,
+    taken: usize,
+    result: usize,
+    merging: bool,
+    strands: usize,
+    started: bool,
+}
+
+impl SortAlgorithm for StrandSort {
+    fn reset(&mut self) {
+        *self = StrandSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            self.strand = vec![None; columns.len()];
+            self.origins = vec![0; columns.len()];
+            self.remaining = columns.len();
+        }
+
+        loop {
+            if self.merging {
+                if self.taken == self.length {
+                    // The rest of the result is already in place.
+                    self.merging = false;
+                    self.length = 0;
+                    self.pointer = 0;
+                    self.write = 0;
+                    continue;
+                }
+
+                let (out, taken, result) = (self.write, self.taken, self.result);
+                let strand = self.strand[taken].unwrap();
+                let next = if result < columns.len() { Some(columns.read(result)) } else { None };
+                match next {
+                    Some(next) if !columns.compare_held((self.origins[taken], strand), (result, next), direction) => {
+                        columns.write(out, next);
+                        self.result += 1;
+                    }
+                    _ => {
+                        columns.write(out, self.strand[taken].take().unwrap());
+                        self.taken += 1;
+                    }
+                }
+                self.write += 1;
+                return Step::Write(out);
+            }
+
+            if self.remaining == 0 {
+                return Step::Done;
+            }
+
+            if self.pointer == self.remaining {
+                // The strand is complete, so merge it into the result,
+                // starting from the first column the strand left free.
+                self.remaining = self.write;
+                self.result = self.remaining + self.length;
+                self.taken = 0;
+                self.strands += 1;
+                self.merging = true;
+                continue;
+            }
+
+            let i = self.pointer;
+            let value = columns.read(i);
+            self.pointer += 1;
+
+            let last = self.length.checked_sub(1).map(|last| (self.origins[last], self.strand[last].unwrap()));
+            if last.is_none_or(|last| columns.compare_held(last, (i, value), direction)) {
+                self.strand[self.length] = Some(value);
+                self.origins[self.length] = i;
+                columns.aux_write(self.length, value);
+                self.length += 1;
+                return Step::AuxWrite(self.length - 1);
+            }
+
+            let w = self.write;
+            if w != i {
+                columns.write(w, value);
+            }
+            self.write += 1;
+            return if w == i { Step::Read(i) } else { Step::Write(w) };
+        }
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        vec![Panel::Aux(&self.strand)]
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        if self.merging {
+            vec![Marker::Boundary(self.remaining), Marker::Range(self.remaining, self.strand.len())]
+        } else {
+            vec![Marker::Boundary(self.remaining)]
+        }
+    }
+
+    fn status(&self) -> Option<String> {
+        Some(format!("strands: {}", self.strands))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::sort;
+    use super::*;
+
+    #[test]
+    fn piles_and_strands_follow_the_runs() {
+        let rising: Vec<i32> = (0..30).collect();
+        let falling: Vec<i32> = (0..30).rev().collect();
+
+        // Rising cards each need a new pile, but make up one strand.
+        let mut patience = PatienceSort::default();
+        assert_eq!(sort(&mut patience, &rising, 1), rising);
+        assert_eq!(patience.status(), Some("piles: 30".to_string()));
+        let mut strand = StrandSort::default();
+        assert_eq!(sort(&mut strand, &rising, 1), rising);
+        assert_eq!(strand.status(), Some("strands: 1".to_string()));
+
+        // Falling cards all go on one pile, but each is a strand.
+        let mut patience = PatienceSort::default();
+        assert_eq!(sort(&mut patience, &falling, 1), rising);
+        assert_eq!(patience.status(), Some("piles: 1".to_string()));
+        let mut strand = StrandSort::default();
+        assert_eq!(sort(&mut strand, &falling, 1), rising);
+        assert_eq!(strand.status(), Some("strands: 30".to_string()));
+    }
+}
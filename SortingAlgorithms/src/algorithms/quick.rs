@@ -0,0 +1,1316 @@
+/*****************************************************************/
+//! [Quicksorts]
+/*****************************************************************/
+//!
+//! All of the quicksorts share the same driver, which keeps an
+//! explicit stack of the ranges still left to partition and picks
+//! pivots with the currently selected PivotRule. What differs is the
+//! partition scheme, which is its own state machine implementing the
+//! Scheme trait.
+//!
+//! Introsort and pdqsort are the hybrids used in practice. They are
+//! quicksorts at heart, but hand small ranges to insertion sort and
+//! hopeless ones to heapsort, running those on just that slice of
+//! the columns.
+
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{Array, in_order, BinaryHeapSort, HeapLink, InsertionSort, Marker, OpQueue, SortAlgorithm, Step};
+
+/// [Before]
+/// True when a must strictly come before b in the given direction.
+fn before(a: i32, b: i32, direction: i32) -> bool {
+    !in_order(b, a, direction)
+}
+
+/// [Pivot Rule]
+/// How the pivot of a range is chosen.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PivotRule {
+    First,
+    Last,
+    Middle,
+    Random,
+    MedianOfThree,
+    Ninther,
+}
+
+impl PivotRule {
+    const ALL: [PivotRule; 6] = [
+        PivotRule::First,
+        PivotRule::Last,
+        PivotRule::Middle,
+        PivotRule::Random,
+        PivotRule::MedianOfThree,
+        PivotRule::Ninther,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            PivotRule::First => "first",
+            PivotRule::Last => "last",
+            PivotRule::Middle => "middle",
+            PivotRule::Random => "random",
+            PivotRule::MedianOfThree => "median of three",
+            PivotRule::Ninther => "ninther",
+        }
+    }
+}
+
+/// [Pivot Chooser]
+/// Picks pivots according to its rule. The median rules have to look
+/// at the columns to decide, and the comparisons they make are queued
+/// up in pending so that they can be shown one step at a time before
+/// partitioning begins.
+#[derive(Clone)]
+pub struct PivotChooser {
+    rule: PivotRule,
+    rng: StdRng,
+    pending: OpQueue,
+}
+
+impl Default for PivotChooser {
+    fn default() -> Self {
+        PivotChooser {
+            rule: PivotRule::MedianOfThree,
+            rng: StdRng::from_entropy(),
+            pending: OpQueue::default(),
+        }
+    }
+}
+
+impl PivotChooser {
+    /// [Seed]
+    /// Starts the random pivots over from the given seed.
+    fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// [Choose]
+    /// Picks the index of a pivot from the range low..high.
+    fn choose(&mut self, columns: &[i32], direction: i32, low: usize, high: usize) -> usize {
+        let mid = low + (high - low) / 2;
+
+        match self.rule {
+            PivotRule::First => low,
+            PivotRule::Last => high - 1,
+            PivotRule::Middle => mid,
+            PivotRule::Random => self.rng.gen_range(low..high),
+            PivotRule::MedianOfThree => self.median_of_three(columns, direction, [low, mid, high - 1]),
+            PivotRule::Ninther if high - low < 9 => self.median_of_three(columns, direction, [low, mid, high - 1]),
+            PivotRule::Ninther => {
+                // Tukey's ninther, the median of three medians of three.
+                let e = (high - low) / 8;
+                let a = self.median_of_three(columns, direction, [low, low + e, low + 2 * e]);
+                let b = self.median_of_three(columns, direction, [mid - e, mid, mid + e]);
+                let c = self.median_of_three(columns, direction, [high - 1 - 2 * e, high - 1 - e, high - 1]);
+                self.median_of_three(columns, direction, [a, b, c])
+            }
+        }
+    }
+
+    fn median_of_three(&mut self, columns: &[i32], direction: i32, [a, b, c]: [usize; 3]) -> usize {
+        self.pending.compare(a, b);
+        let (a, b) = if in_order(columns[a], columns[b], direction) { (a, b) } else { (b, a) };
+
+        self.pending.compare(b, c);
+        if in_order(columns[b], columns[c], direction) {
+            return b;
+        }
+
+        self.pending.compare(a, c);
+        if in_order(columns[a], columns[c], direction) { c } else { a }
+    }
+}
+
+/// [Progress]
+/// What a partition scheme did when it was stepped.
+pub enum Progress {
+    /// Performed one operation on the columns.
+    Step(Step),
+    /// Finished partitioning, leaving these ranges still to be sorted.
+    Split(Vec<(usize, usize)>),
+}
+
+/// [Scheme]
+/// A partitioning scheme, advanced one operation at a time.
+pub trait Scheme: Clone + Default {
+    /// [Begin]
+    /// Starts partitioning the range low..high, which holds at least
+    /// two columns, choosing pivots with the chooser.
+    fn begin(&mut self, columns: &[i32], direction: i32, low: usize, high: usize, chooser: &mut PivotChooser);
+
+    /// [Step]
+    /// Performs one operation of the partition.
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Progress;
+
+    /// [Markers]
+    /// The pivot and partition boundaries of the partition in progress.
+    fn markers(&self) -> Vec<Marker>;
+}
+
+/// [Quick Sort]
+/// The quicksort driver shared by every partition scheme. Ranges are
+/// kept on an explicit stack, as (low, high) pairs with high
+/// exclusive, and the left part of each partition is sorted first.
+#[derive(Clone, Default)]
+pub struct QuickSort<S: Scheme> {
+    ranges: Vec<(usize, usize)>,
+    chooser: PivotChooser,
+    scheme: S,
+    started: bool,
+    partitioning: bool,
+}
+
+pub type LomutoQuickSort = QuickSort<Lomuto>;
+pub type HoareQuickSort = QuickSort<Hoare>;
+pub type ThreeWayQuickSort = QuickSort<ThreeWay>;
+pub type DualPivotQuickSort = QuickSort<DualPivot>;
+
+impl<S: Scheme + 'static> SortAlgorithm for QuickSort<S> {
+    fn reset(&mut self) {
+        self.ranges.clear();
+        self.chooser.pending.clear();
+        self.started = false;
+        self.partitioning = false;
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.chooser.seed(seed);
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            self.ranges.push((0, columns.len()));
+        }
+
+        loop {
+            if let Some(step) = self.chooser.pending.pop(columns) {
+                return step;
+            }
+
+            if self.partitioning {
+                match self.scheme.step(columns, direction) {
+                    Progress::Step(step) => return step,
+                    Progress::Split(parts) => {
+                        self.ranges.extend(parts.into_iter().rev());
+                        self.partitioning = false;
+                    }
+                }
+            }
+
+            match self.ranges.pop() {
+                Some((low, high)) if high > low + 1 => {
+                    self.scheme.begin(columns.values(), direction, low, high, &mut self.chooser);
+                    self.partitioning = true;
+                }
+                Some(_) => {}
+                None => return Step::Done,
+            }
+        }
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        if self.partitioning {
+            self.scheme.markers()
+        } else {
+            vec![]
+        }
+    }
+
+    fn option(&self) -> Option<String> {
+        Some(format!("pivot: {}", self.chooser.rule.name()))
+    }
+
+    fn next_option(&mut self) {
+        let index = PivotRule::ALL.iter().position(|&rule| rule == self.chooser.rule).unwrap();
+        self.chooser.rule = PivotRule::ALL[(index + 1) % PivotRule::ALL.len()];
+    }
+}
+
+/// [Lomuto]
+/// The pivot is moved to the end of the range, and a single scan
+/// moves everything which belongs before it to the front. The front
+/// part ends at store, and the scan is at pointer.
+#[derive(Clone, Default)]
+pub struct Lomuto {
+    low: usize,
+    high: usize,
+    pivot: usize,
+    store: usize,
+    pointer: usize,
+    placed: bool,
+}
+
+impl Scheme for Lomuto {
+    fn begin(&mut self, columns: &[i32], direction: i32, low: usize, high: usize, chooser: &mut PivotChooser) {
+        (self.low, self.high) = (low, high);
+        self.pivot = chooser.choose(columns, direction, low, high);
+        (self.store, self.pointer) = (low, low);
+        self.placed = false;
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Progress {
+        let last = self.high - 1;
+
+        if !self.placed {
+            self.placed = true;
+            if self.pivot != last {
+                columns.swap(self.pivot, last);
+                return Progress::Step(Step::Swap(self.pivot, last));
+            }
+        }
+
+        if self.pointer < last {
+            let (i, j) = (self.store, self.pointer);
+            self.pointer += 1;
+
+            if !columns.compare(last, j, direction) {
+                self.store += 1;
+                if i != j {
+                    columns.swap(i, j);
+                    return Progress::Step(Step::Swap(i, j));
+                }
+            }
+            return Progress::Step(Step::Compare(j, last));
+        }
+
+        // Finally the pivot moves between the two parts.
+        let i = self.store;
+        if self.pointer == last {
+            self.pointer += 1;
+            if i != last {
+                columns.swap(i, last);
+                return Progress::Step(Step::Swap(i, last));
+            }
+        }
+
+        Progress::Split(vec![(self.low, i), (i + 1, self.high)])
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        let pivot = if self.placed { self.high - 1 } else { self.pivot };
+        vec![
+            Marker::Range(self.low, self.high),
+            Marker::Pivot(pivot),
+            Marker::Boundary(self.store),
+            Marker::Boundary(self.pointer),
+        ]
+    }
+}
+
+/// [Hoare]
+/// The pivot is moved to the front of the range, then two pointers
+/// scan towards each other from either end, swapping pairs of
+/// elements which are both on the wrong side. The partition ends
+/// where the pointers cross.
+#[derive(Clone, Default)]
+pub struct Hoare {
+    low: usize,
+    high: usize,
+    pivot: usize,
+    value: i32,
+    left: usize,
+    right: usize,
+    phase: HoarePhase,
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum HoarePhase {
+    #[default]
+    Place,
+    ScanLeft,
+    ScanRight,
+    Swap,
+}
+
+impl Scheme for Hoare {
+    fn begin(&mut self, columns: &[i32], direction: i32, low: usize, high: usize, chooser: &mut PivotChooser) {
+        (self.low, self.high) = (low, high);
+        self.pivot = chooser.choose(columns, direction, low, high);
+        (self.left, self.right) = (low, high - 1);
+        self.phase = HoarePhase::Place;
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Progress {
+        loop {
+            match self.phase {
+                HoarePhase::Place => {
+                    self.phase = HoarePhase::ScanLeft;
+                    let pivot = self.pivot;
+                    self.pivot = self.low;
+                    self.value = columns.read(pivot);
+                    if pivot != self.low {
+                        columns.swap(pivot, self.low);
+                        return Progress::Step(Step::Swap(pivot, self.low));
+                    }
+                }
+                HoarePhase::ScanLeft => {
+                    let i = self.left;
+                    if before(columns.read(i), self.value, direction) {
+                        self.left += 1;
+                    } else {
+                        self.phase = HoarePhase::ScanRight;
+                    }
+                    return Progress::Step(Step::Compare(i, self.pivot));
+                }
+                HoarePhase::ScanRight => {
+                    let j = self.right;
+                    if before(self.value, columns.read(j), direction) {
+                        self.right -= 1;
+                    } else if self.left >= j {
+                        return Progress::Split(vec![(self.low, j + 1), (j + 1, self.high)]);
+                    } else {
+                        self.phase = HoarePhase::Swap;
+                    }
+                    return Progress::Step(Step::Compare(j, self.pivot));
+                }
+                HoarePhase::Swap => {
+                    let (i, j) = (self.left, self.right);
+                    columns.swap(i, j);
+
+                    // Keep following the pivot, so it stays highlighted.
+                    if self.pivot == i {
+                        self.pivot = j;
+                    } else if self.pivot == j {
+                        self.pivot = i;
+                    }
+
+                    self.left += 1;
+                    self.right -= 1;
+                    self.phase = HoarePhase::ScanLeft;
+                    return Progress::Step(Step::Swap(i, j));
+                }
+            }
+        }
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        vec![
+            Marker::Range(self.low, self.high),
+            Marker::Pivot(self.pivot),
+            Marker::Boundary(self.left),
+            Marker::Boundary(self.right + 1),
+        ]
+    }
+}
+
+/// [Three-Way]
+/// Dijkstra's Dutch national flag partition. The range is split into
+/// elements before the pivot (low..less), equal to the pivot
+/// (less..pointer), not yet seen (pointer..greater) and after the
+/// pivot (greater..high). Runs of equal elements are never touched
+/// again, which makes this ideal for inputs with many duplicates.
+#[derive(Clone, Default)]
+pub struct ThreeWay {
+    low: usize,
+    high: usize,
+    pivot: usize,
+    less: usize,
+    pointer: usize,
+    greater: usize,
+    placed: bool,
+}
+
+impl Scheme for ThreeWay {
+    fn begin(&mut self, columns: &[i32], direction: i32, low: usize, high: usize, chooser: &mut PivotChooser) {
+        (self.low, self.high) = (low, high);
+        self.pivot = chooser.choose(columns, direction, low, high);
+        (self.less, self.pointer, self.greater) = (low, low + 1, high);
+        self.placed = false;
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Progress {
+        if !self.placed {
+            self.placed = true;
+            if self.pivot != self.low {
+                columns.swap(self.pivot, self.low);
+                return Progress::Step(Step::Swap(self.pivot, self.low));
+            }
+        }
+
+        if self.pointer >= self.greater {
+            return Progress::Split(vec![(self.low, self.less), (self.greater, self.high)]);
+        }
+
+        // The first element of the equal run is always the pivot.
+        let (i, value) = (self.pointer, columns.read(self.less));
+        let current = columns.read(i);
+
+        if before(current, value, direction) {
+            let j = self.less;
+            columns.swap(i, j);
+            self.less += 1;
+            self.pointer += 1;
+            Progress::Step(Step::Swap(i, j))
+        } else if before(value, current, direction) {
+            self.greater -= 1;
+            let j = self.greater;
+            columns.swap(i, j);
+            Progress::Step(Step::Swap(i, j))
+        } else {
+            self.pointer += 1;
+            Progress::Step(Step::Compare(i, self.less))
+        }
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        let pivot = if self.placed { self.less } else { self.pivot };
+        vec![
+            Marker::Range(self.low, self.high),
+            Marker::Pivot(pivot),
+            Marker::Boundary(self.less),
+            Marker::Boundary(self.pointer),
+            Marker::Boundary(self.greater),
+        ]
+    }
+}
+
+/// [Dual Pivot]
+/// Yaroslavskiy's dual-pivot partition, as used by Java. Two pivots
+/// sit at either end of the range and split it into three parts:
+/// before the first pivot (ending at less), between the pivots, and
+/// after the second pivot (starting after greater). The element
+/// being classified is at pointer.
+#[derive(Clone, Default)]
+pub struct DualPivot {
+    low: usize,
+    high: usize,
+    pivots: (usize, usize),
+    less: usize,
+    pointer: usize,
+    greater: usize,
+    phase: DualPhase,
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum DualPhase {
+    #[default]
+    PlaceFirst,
+    PlaceSecond,
+    Order,
+    First,
+    Second,
+    ScanGreater,
+    SwapGreater,
+    Recheck,
+    FinishFirst,
+    FinishSecond,
+    Split,
+}
+
+impl Scheme for DualPivot {
+    fn begin(&mut self, columns: &[i32], direction: i32, low: usize, high: usize, chooser: &mut PivotChooser) {
+        (self.low, self.high) = (low, high);
+
+        // One pivot is chosen from each half of the range.
+        let mid = low + (high - low) / 2;
+        let first = chooser.choose(columns, direction, low, mid);
+        let second = chooser.choose(columns, direction, mid, high);
+        self.pivots = (first, second);
+
+        (self.less, self.pointer, self.greater) = (low + 1, low + 1, high - 2);
+        self.phase = DualPhase::PlaceFirst;
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Progress {
+        let (left, right) = (self.low, self.high - 1);
+
+        loop {
+            match self.phase {
+                DualPhase::PlaceFirst => {
+                    self.phase = DualPhase::PlaceSecond;
+                    let first = self.pivots.0;
+                    if first != left {
+                        columns.swap(first, left);
+                        return Progress::Step(Step::Swap(first, left));
+                    }
+                }
+                DualPhase::PlaceSecond => {
+                    self.phase = DualPhase::Order;
+                    let second = self.pivots.1;
+                    if second != right {
+                        columns.swap(second, right);
+                        return Progress::Step(Step::Swap(second, right));
+                    }
+                }
+                DualPhase::Order => {
+                    self.phase = DualPhase::First;
+                    if !columns.compare(left, right, direction) {
+                        columns.swap(left, right);
+                        return Progress::Step(Step::Swap(left, right));
+                    }
+                    return Progress::Step(Step::Compare(left, right));
+                }
+                DualPhase::First => {
+                    let k = self.pointer;
+                    if k > self.greater {
+                        self.phase = DualPhase::FinishFirst;
+                        continue;
+                    }
+
+                    if !columns.compare(left, k, direction) {
+                        let l = self.less;
+                        self.less += 1;
+                        self.pointer += 1;
+                        if k != l {
+                            columns.swap(k, l);
+                            return Progress::Step(Step::Swap(k, l));
+                        }
+                    } else {
+                        self.phase = DualPhase::Second;
+                    }
+                    return Progress::Step(Step::Compare(k, left));
+                }
+                DualPhase::Second => {
+                    let k = self.pointer;
+                    if columns.compare(right, k, direction) {
+                        self.phase = DualPhase::ScanGreater;
+                    } else {
+                        self.pointer += 1;
+                        self.phase = DualPhase::First;
+                    }
+                    return Progress::Step(Step::Compare(k, right));
+                }
+                DualPhase::ScanGreater => {
+                    let g = self.greater;
+                    if self.pointer >= g {
+                        self.phase = DualPhase::SwapGreater;
+                        continue;
+                    }
+
+                    if !columns.compare(g, right, direction) {
+                        self.greater -= 1;
+                    } else {
+                        self.phase = DualPhase::SwapGreater;
+                    }
+                    return Progress::Step(Step::Compare(g, right));
+                }
+                DualPhase::SwapGreater => {
+                    let (k, g) = (self.pointer, self.greater);
+                    self.greater -= 1;
+                    self.phase = DualPhase::Recheck;
+                    if k != g {
+                        columns.swap(k, g);
+                        return Progress::Step(Step::Swap(k, g));
+                    }
+                }
+                DualPhase::Recheck => {
+                    let (k, l) = (self.pointer, self.less);
+                    self.pointer += 1;
+                    self.phase = DualPhase::First;
+
+                    if !columns.compare(left, k, direction) {
+                        self.less += 1;
+                        if k != l {
+                            columns.swap(k, l);
+                            return Progress::Step(Step::Swap(k, l));
+                        }
+                    }
+                    return Progress::Step(Step::Compare(k, left));
+                }
+                DualPhase::FinishFirst => {
+                    // Both pivots move into their final places.
+                    self.less -= 1;
+                    self.greater += 1;
+                    self.phase = DualPhase::FinishSecond;
+                    let l = self.less;
+                    if l != left {
+                        columns.swap(left, l);
+                        return Progress::Step(Step::Swap(left, l));
+                    }
+                }
+                DualPhase::FinishSecond => {
+                    self.phase = DualPhase::Split;
+                    let g = self.greater;
+                    if g != right {
+                        columns.swap(right, g);
+                        return Progress::Step(Step::Swap(right, g));
+                    }
+                }
+                DualPhase::Split => {
+                    let (l, g) = (self.less, self.greater);
+                    return Progress::Split(vec![(self.low, l), (l + 1, g), (g + 1, self.high)]);
+                }
+            }
+        }
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        let (first, second) = match self.phase {
+            DualPhase::PlaceFirst | DualPhase::PlaceSecond => self.pivots,
+            DualPhase::FinishSecond | DualPhase::Split => (self.less, self.high - 1),
+            _ => (self.low, self.high - 1),
+        };
+        vec![
+            Marker::Range(self.low, self.high),
+            Marker::Pivot(first),
+            Marker::Pivot(second),
+            Marker::Boundary(self.less),
+            Marker::Boundary(self.pointer),
+            Marker::Boundary(self.greater + 1),
+        ]
+    }
+}
+
+/// [Introsort Cutoff]
+/// Introsort leaves ranges of at most this many columns to insertion
+/// sort.
+const INTROSORT_CUTOFF: usize = 16;
+
+/// [Pdqsort Cutoff]
+/// Pdqsort leaves ranges of fewer than this many columns to insertion
+/// sort, and only breaks patterns in parts at least this long.
+const PDQSORT_CUTOFF: usize = 24;
+
+/// [Partial Insertion Limit]
+/// How many swaps pdqsort's partial insertion sort makes before it
+/// decides the range was not nearly sorted after all.
+const PARTIAL_INSERTION_LIMIT: usize = 8;
+
+/// [Partial Insertion Sort]
+/// Insertion sort which gives up once it has made too many swaps, and
+/// remembers that it failed.
+#[derive(Clone, Default)]
+struct PartialInsertionSort {
+    sort: InsertionSort,
+    swaps: usize,
+    failed: bool,
+}
+
+impl PartialInsertionSort {
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if self.swaps > PARTIAL_INSERTION_LIMIT {
+            self.failed = true;
+            return Step::Done;
+        }
+
+        let step = self.sort.step(columns, direction);
+        if let Step::Swap(..) = step {
+            self.swaps += 1;
+        }
+        step
+    }
+}
+
+/// [Fallback]
+/// The simpler algorithms a hybrid quicksort can hand a range to.
+#[derive(Clone)]
+enum Fallback {
+    Insertion(InsertionSort),
+    Heap(BinaryHeapSort),
+    Partial(PartialInsertionSort),
+}
+
+/// [Handoff]
+/// A fallback algorithm sorting just the columns low..high.
+#[derive(Clone)]
+struct Handoff {
+    low: usize,
+    high: usize,
+    fallback: Fallback,
+}
+
+impl Handoff {
+    fn new(low: usize, high: usize, fallback: Fallback) -> Self {
+        Handoff { low, high, fallback }
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        let slice = &mut columns.slice(self.low, self.high);
+        let step = match &mut self.fallback {
+            Fallback::Insertion(sort) => sort.step(slice, direction),
+            Fallback::Heap(sort) => sort.step(slice, direction),
+            Fallback::Partial(sort) => sort.step(slice, direction),
+        };
+        step.offset(self.low)
+    }
+
+    fn failed(&self) -> bool {
+        matches!(&self.fallback, Fallback::Partial(sort) if sort.failed)
+    }
+
+    fn phase(&self) -> &'static str {
+        match self.fallback {
+            Fallback::Insertion(_) => "insertion cutoff",
+            Fallback::Heap(_) => "heapsort fallback",
+            Fallback::Partial(_) => "partial insertion sort",
+        }
+    }
+
+    fn heap_links(&self) -> Vec<HeapLink> {
+        let Fallback::Heap(sort) = &self.fallback else {
+            return vec![];
+        };
+
+        sort.heap_links()
+            .into_iter()
+            .map(|link| HeapLink { parent: link.parent + self.low, child: link.child + self.low, ..link })
+            .collect()
+    }
+}
+
+/// [Introsort]
+/// Musser's introspective sort. It is a Hoare quicksort which counts
+/// how deep its recursion has gone, as the third entry of each range.
+/// Once a range is deeper than twice the log of the length, the pivots
+/// have clearly been going badly, and the range is heapsorted
+/// instead. Small ranges are finished off by insertion sort.
+#[derive(Clone, Default)]
+pub struct IntroSort {
+    ranges: Vec<(usize, usize, usize)>,
+    chooser: PivotChooser,
+    scheme: Hoare,
+    handoff: Option<Handoff>,
+    depth: usize,
+    limit: usize,
+    started: bool,
+    partitioning: bool,
+}
+
+impl SortAlgorithm for IntroSort {
+    fn reset(&mut self) {
+        self.ranges.clear();
+        self.chooser.pending.clear();
+        self.handoff = None;
+        self.started = false;
+        self.partitioning = false;
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.chooser.seed(seed);
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            self.limit = 2 * columns.len().max(1).ilog2() as usize;
+            self.ranges.push((0, columns.len(), 0));
+        }
+
+        loop {
+            if let Some(step) = self.chooser.pending.pop(columns) {
+                return step;
+            }
+
+            if let Some(handoff) = &mut self.handoff {
+                match handoff.step(columns, direction) {
+                    Step::Done => self.handoff = None,
+                    step => return step,
+                }
+            }
+
+            if self.partitioning {
+                match self.scheme.step(columns, direction) {
+                    Progress::Step(step) => return step,
+                    Progress::Split(parts) => {
+                        let depth = self.depth + 1;
+                        self.ranges.extend(parts.into_iter().rev().map(|(low, high)| (low, high, depth)));
+                        self.partitioning = false;
+                    }
+                }
+            }
+
+            match self.ranges.pop() {
+                Some((low, high, depth)) if high > low + 1 => {
+                    self.depth = depth;
+                    if high - low <= INTROSORT_CUTOFF {
+                        self.handoff = Some(Handoff::new(low, high, Fallback::Insertion(InsertionSort::default())));
+                    } else if depth >= self.limit {
+                        self.handoff = Some(Handoff::new(low, high, Fallback::Heap(BinaryHeapSort::default())));
+                    } else {
+                        self.scheme.begin(columns.values(), direction, low, high, &mut self.chooser);
+                        self.partitioning = true;
+                    }
+                }
+                Some(_) => {}
+                None => return Step::Done,
+            }
+        }
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        match &self.handoff {
+            Some(handoff) => vec![Marker::Range(handoff.low, handoff.high)],
+            None if self.partitioning => self.scheme.markers(),
+            None => vec![],
+        }
+    }
+
+    fn heap_links(&self) -> Vec<HeapLink> {
+        self.handoff.as_ref().map(Handoff::heap_links).unwrap_or_default()
+    }
+
+    fn option(&self) -> Option<String> {
+        Some(format!("pivot: {}", self.chooser.rule.name()))
+    }
+
+    fn next_option(&mut self) {
+        let index = PivotRule::ALL.iter().position(|&rule| rule == self.chooser.rule).unwrap();
+        self.chooser.rule = PivotRule::ALL[(index + 1) % PivotRule::ALL.len()];
+    }
+
+    fn status(&self) -> Option<String> {
+        if !self.started {
+            return None;
+        }
+
+        let phase = match &self.handoff {
+            Some(handoff) => handoff.phase(),
+            None => "quicksort",
+        };
+        Some(format!("{} - depth {}/{}", phase, self.depth, self.limit))
+    }
+}
+
+/// [Pdq Partition]
+/// The partitions used by pdqsort, with the pivot kept at begin until
+/// the very end. The right partition puts elements equal to the pivot
+/// after it, leaving before it everything in begin + 1..first and
+/// after it everything in last..end. It also notes whether the range
+/// was already partitioned, needing no swaps at all. The left
+/// partition puts equal elements before the pivot instead, with
+/// everything in begin..=first before it. Each batch scans inwards
+/// from both ends and queues a single swap.
+#[derive(Clone, Default)]
+struct PdqPartition {
+    begin: usize,
+    end: usize,
+    first: usize,
+    last: usize,
+    left: bool,
+    started: bool,
+    partitioned: bool,
+}
+
+impl PdqPartition {
+    fn begin(&mut self, begin: usize, end: usize, left: bool) {
+        (self.begin, self.end, self.left) = (begin, end, left);
+        self.first = if left { begin } else { begin + 1 };
+        self.last = end;
+        self.started = false;
+    }
+
+    /// [Next]
+    /// Queues the next batch of the partition. Once the partition is
+    /// finished, this returns where the pivot ends up and whether the
+    /// range was already partitioned.
+    fn next(&mut self, columns: &[i32], direction: i32, queue: &mut OpQueue) -> Option<(usize, bool)> {
+        let (begin, pivot) = (self.begin, columns[self.begin]);
+
+        let (i, j) = if self.left {
+            while self.last > self.first + 1 {
+                queue.compare(self.last - 1, begin);
+                if !before(pivot, columns[self.last - 1], direction) {
+                    break;
+                }
+                self.last -= 1;
+            }
+            while self.first + 1 < self.last {
+                queue.compare(self.first + 1, begin);
+                if before(pivot, columns[self.first + 1], direction) {
+                    break;
+                }
+                self.first += 1;
+            }
+            (self.first + 1, self.last)
+        } else {
+            while self.first < self.last {
+                queue.compare(self.first, begin);
+                if !before(columns[self.first], pivot, direction) {
+                    break;
+                }
+                self.first += 1;
+            }
+            while self.last > self.first {
+                queue.compare(self.last - 1, begin);
+                if before(columns[self.last - 1], pivot, direction) {
+                    break;
+                }
+                self.last -= 1;
+            }
+            (self.first, self.last)
+        };
+
+        if !self.started {
+            self.started = true;
+            self.partitioned = i >= j;
+        }
+
+        if i < j {
+            queue.swap(i, j - 1);
+            self.first += 1;
+            self.last -= 1;
+            return None;
+        }
+
+        // The pointers have met, so the pivot moves between the parts.
+        let pos = i - 1;
+        if pos != begin {
+            queue.swap(begin, pos);
+        }
+        Some((pos, self.partitioned))
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        let first = if self.left { self.first + 1 } else { self.first };
+        vec![
+            Marker::Range(self.begin, self.end),
+            Marker::Pivot(self.begin),
+            Marker::Boundary(first),
+            Marker::Boundary(self.last),
+        ]
+    }
+}
+
+/// [Pdq Range]
+/// A range still left for pdqsort to sort, with how many more badly
+/// unbalanced partitions it may suffer before giving up on quicksort,
+/// and whether it is the leftmost range, with nothing before it.
+#[derive(Clone, Copy, Default)]
+struct PdqRange {
+    low: usize,
+    high: usize,
+    bad: usize,
+    leftmost: bool,
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum PdqPhase {
+    #[default]
+    Next,
+    Pivoted,
+    Partitioning,
+    Partitioned(usize, bool),
+    TryLeft(usize),
+    TryRight(usize),
+}
+
+/// [Pdqsort]
+/// Peters' pattern-defeating quicksort, which is introsort taught to
+/// spot the patterns that real inputs have.
+///
+/// Ranges which look nearly sorted, because partitioning them needed
+/// no swaps, get a partial insertion sort. That finishes the job
+/// outright if the guess was right. Runs of equal elements are
+/// spotted when the pivot equals the element just before the range,
+/// which was an earlier pivot. Those ranges get a left partition, and
+/// the equal elements are never looked at again. A badly unbalanced
+/// partition uses up one of the range's allowed bad partitions and
+/// swaps a few elements around to break up whatever pattern caused
+/// it. Once the allowance runs out, the range is heapsorted.
+#[derive(Clone)]
+pub struct PdqSort {
+    ranges: Vec<PdqRange>,
+    range: PdqRange,
+    chooser: PivotChooser,
+    queue: OpQueue,
+    partition: PdqPartition,
+    handoff: Option<Handoff>,
+    failed: bool,
+    phase: PdqPhase,
+    label: &'static str,
+    started: bool,
+}
+
+impl Default for PdqSort {
+    fn default() -> Self {
+        PdqSort {
+            ranges: vec![],
+            range: PdqRange::default(),
+            chooser: PivotChooser::default(),
+            queue: OpQueue::default(),
+            partition: PdqPartition::default(),
+            handoff: None,
+            failed: false,
+            phase: PdqPhase::Next,
+            label: "partitioning",
+            started: false,
+        }
+    }
+}
+
+impl PdqSort {
+    /// [Split]
+    /// Queues both parts of a partitioned range to be sorted, the left
+    /// part first.
+    fn split(&mut self, pos: usize) {
+        let range = self.range;
+        self.ranges.push(PdqRange { low: pos + 1, leftmost: false, ..range });
+        self.ranges.push(PdqRange { high: pos, ..range });
+    }
+
+    /// [Break Patterns]
+    /// Queues swaps which move a few elements of the part low..high,
+    /// either side of the pivot at pos, a quarter of the way in from
+    /// either end.
+    fn break_patterns(&mut self, low: usize, high: usize) {
+        let size = high - low;
+        if size < PDQSORT_CUTOFF {
+            return;
+        }
+
+        let quarter = size / 4;
+        self.queue.swap(low, low + quarter);
+        self.queue.swap(high - 1, high - quarter);
+
+        if size > 128 {
+            self.queue.swap(low + 1, low + quarter + 1);
+            self.queue.swap(low + 2, low + quarter + 2);
+            self.queue.swap(high - 2, high - quarter - 1);
+            self.queue.swap(high - 3, high - quarter - 2);
+        }
+    }
+}
+
+impl SortAlgorithm for PdqSort {
+    fn reset(&mut self) {
+        *self = PdqSort::default();
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.chooser.seed(seed);
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            let bad = columns.len().max(1).ilog2() as usize;
+            self.ranges.push(PdqRange { low: 0, high: columns.len(), bad, leftmost: true });
+        }
+
+        loop {
+            if let Some(step) = self.chooser.pending.pop(columns) {
+                return step;
+            }
+
+            if let Some(step) = self.queue.pop(columns) {
+                return step;
+            }
+
+            if let Some(handoff) = &mut self.handoff {
+                match handoff.step(columns, direction) {
+                    Step::Done => {
+                        self.failed = handoff.failed();
+                        self.handoff = None;
+                    }
+                    step => return step,
+                }
+            }
+
+            let PdqRange { low, high, .. } = self.range;
+
+            match self.phase {
+                PdqPhase::Next => {
+                    let Some(range) = self.ranges.pop() else {
+                        return Step::Done;
+                    };
+                    self.range = range;
+
+                    let size = range.high - range.low;
+                    if size < 2 {
+                        continue;
+                    }
+                    if size < PDQSORT_CUTOFF {
+                        let fallback = Fallback::Insertion(InsertionSort::default());
+                        self.handoff = Some(Handoff::new(range.low, range.high, fallback));
+                        continue;
+                    }
+
+                    // The pivot is moved to the front of the range.
+                    self.chooser.rule = if size > 128 { PivotRule::Ninther } else { PivotRule::MedianOfThree };
+                    let pivot = self.chooser.choose(columns.values(), direction, range.low, range.high);
+                    if pivot != range.low {
+                        self.queue.swap(range.low, pivot);
+                    }
+                    self.label = "choosing pivot";
+                    self.phase = PdqPhase::Pivoted;
+                }
+                PdqPhase::Pivoted => {
+                    // A pivot equal to the previous pivot, just before
+                    // this range, means a run of equal elements.
+                    let equal = !self.range.leftmost && {
+                        self.queue.compare(low - 1, low);
+                        in_order(columns.values()[low], columns.values()[low - 1], direction)
+                    };
+                    self.partition.begin(low, high, equal);
+                    self.label = if equal { "equal elements" } else { "partitioning" };
+                    self.phase = PdqPhase::Partitioning;
+                }
+                PdqPhase::Partitioning => {
+                    if let Some((pos, partitioned)) = self.partition.next(columns.values(), direction, &mut self.queue) {
+                        if self.partition.left {
+                            // Everything before the pivot equals it, so
+                            // only the part after it needs sorting.
+                            self.ranges.push(PdqRange { low: pos + 1, leftmost: false, ..self.range });
+                            self.phase = PdqPhase::Next;
+                        } else {
+                            self.phase = PdqPhase::Partitioned(pos, partitioned);
+                        }
+                    }
+                }
+                PdqPhase::Partitioned(pos, partitioned) => {
+                    let size = high - low;
+                    let (left, right) = (pos - low, high - pos - 1);
+                    self.phase = PdqPhase::Next;
+
+                    if left < size / 8 || right < size / 8 {
+                        self.range.bad -= 1;
+                        if self.range.bad == 0 {
+                            self.handoff = Some(Handoff::new(low, high, Fallback::Heap(BinaryHeapSort::default())));
+                            continue;
+                        }
+                        self.label = "breaking patterns";
+                        self.break_patterns(low, pos);
+                        self.break_patterns(pos + 1, high);
+                    } else if partitioned {
+                        let fallback = Fallback::Partial(PartialInsertionSort::default());
+                        self.handoff = Some(Handoff::new(low, pos, fallback));
+                        self.phase = PdqPhase::TryLeft(pos);
+                        continue;
+                    }
+                    self.split(pos);
+                }
+                PdqPhase::TryLeft(pos) => {
+                    if self.failed {
+                        self.split(pos);
+                        self.phase = PdqPhase::Next;
+                    } else {
+                        let fallback = Fallback::Partial(PartialInsertionSort::default());
+                        self.handoff = Some(Handoff::new(pos + 1, high, fallback));
+                        self.phase = PdqPhase::TryRight(pos);
+                    }
+                }
+                PdqPhase::TryRight(pos) => {
+                    // If both sides were nearly sorted, the range is done.
+                    if self.failed {
+                        self.split(pos);
+                    }
+                    self.phase = PdqPhase::Next;
+                }
+            }
+        }
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        match &self.handoff {
+            Some(handoff) => vec![Marker::Range(handoff.low, handoff.high)],
+            None if self.phase == PdqPhase::Partitioning => self.partition.markers(),
+            None => vec![Marker::Range(self.range.low, self.range.high)],
+        }
+    }
+
+    fn heap_links(&self) -> Vec<HeapLink> {
+        self.handoff.as_ref().map(Handoff::heap_links).unwrap_or_default()
+    }
+
+    fn status(&self) -> Option<String> {
+        if !self.started {
+            return None;
+        }
+
+        let phase = match &self.handoff {
+            Some(handoff) => handoff.phase(),
+            None => self.label,
+        };
+        Some(format!("{} - bad partitions left {}", phase, self.range.bad))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{shuffled, sort, sorted};
+    use super::super::Trace;
+    use super::*;
+
+    #[test]
+    fn every_scheme_sorts_with_every_pivot_rule() {
+        let inputs = [shuffled(200), (0..200).collect(), (0..200).rev().collect(), vec![7; 200]];
+        let schemes: [fn() -> Box<dyn SortAlgorithm>; 4] = [
+            || Box::new(LomutoQuickSort::default()),
+            || Box::new(HoareQuickSort::default()),
+            || Box::new(ThreeWayQuickSort::default()),
+            || Box::new(DualPivotQuickSort::default()),
+        ];
+        for create in schemes {
+            for rule in PivotRule::ALL {
+                for columns in &inputs {
+                    for direction in [1, -1] {
+                        let mut algorithm = create();
+                        while algorithm.option() != Some(format!("pivot: {}", rule.name())) {
+                            algorithm.next_option();
+                        }
+                        assert_eq!(sort(algorithm.as_mut(), columns, direction), sorted(columns, direction), "{:?}", algorithm.option());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pivot_rules_cycle() {
+        let mut algorithm = LomutoQuickSort::default();
+        let first = algorithm.option();
+        for _ in 0..PivotRule::ALL.len() {
+            algorithm.next_option();
+        }
+        assert_eq!(algorithm.option(), first);
+    }
+    /// [Phases Of]
+    /// Sorts the columns, returning every phase the status went
+    /// through along the way.
+    fn phases_of(algorithm: &mut dyn SortAlgorithm, columns: &mut [i32]) -> Vec<String> {
+        let mut trace = Trace::default();
+        let mut phases = vec![];
+        while trace.step(algorithm, columns, 1) != Step::Done {
+            phases.extend(algorithm.status().and_then(|status| status.split(" - ").next().map(str::to_string)));
+        }
+        phases
+    }
+
+    #[test]
+    fn introsort_falls_back_to_heapsort() {
+        // Always taking the first element as the pivot of sorted columns
+        // only ever splits one off, so the depth limit is soon reached.
+        let mut columns: Vec<i32> = (0..300).collect();
+        let mut algorithm = IntroSort::default();
+        while algorithm.option() != Some("pivot: first".to_string()) {
+            algorithm.next_option();
+        }
+        let phases = phases_of(&mut algorithm, &mut columns);
+        assert_eq!(columns, (0..300).collect::<Vec<_>>());
+        assert!(phases.iter().any(|phase| phase == "heapsort fallback"));
+
+        // Shuffled columns split evenly enough to get down to the small
+        // ranges left to insertion sort.
+        let mut columns = shuffled(300);
+        let phases = phases_of(&mut IntroSort::default(), &mut columns);
+        assert_eq!(columns, sorted(&columns, 1));
+        assert!(phases.iter().any(|phase| phase == "insertion cutoff"));
+        assert!(!phases.iter().any(|phase| phase == "heapsort fallback"));
+    }
+
+    #[test]
+    fn pdqsort_sorts_patterns() {
+        let n = 400;
+        let patterns: [Vec<i32>; 6] = [
+            shuffled(n),
+            (0..n as i32).collect(),
+            (0..n as i32).rev().collect(),
+            (0..n as i32).map(|i| i.min(n as i32 - i)).collect(),
+            (0..n as i32).map(|i| i % 17).collect(),
+            vec![5; n],
+        ];
+        for columns in patterns {
+            let mut sorting = columns.clone();
+            let phases = phases_of(&mut PdqSort::default(), &mut sorting);
+            assert_eq!(sorting, sorted(&columns, 1));
+            if columns.iter().all(|&value| value == 5) {
+                assert!(phases.iter().any(|phase| phase == "equal elements"));
+            }
+        }
+    }
+}
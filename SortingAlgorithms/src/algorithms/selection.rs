@@ -0,0 +1,336 @@
+/*****************************************************************/
+//! [Selection Sorts]
+/*****************************************************************/
+//!
+//! Sorts which repeatedly select the element that belongs next.
+//! Selection sort scans for it, cycle sort works out exactly where
+//! each element belongs so that it is written only once, and
+//! tournament sort keeps a tree of matches so that finding the next
+//! winner only needs a replay of one path.
+
+use super::{Array, Marker, Panel, SortAlgorithm, Step};
+
+/// [Selection Sort]
+/// Scans the unsorted tail for the element which belongs next, and
+/// swaps it into place at the end of the sorted prefix. The sorted
+/// prefix ends at sorted, the scan is at pointer, and best is the
+/// index of the most suitable element found so far in this scan.
+#[derive(Clone, Default)]
+pub struct SelectionSort {
+    sorted: usize,
+    pointer: usize,
+    best: usize,
+}
+
+impl SortAlgorithm for SelectionSort {
+    fn reset(&mut self) {
+        *self = SelectionSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            if self.sorted + 1 >= columns.len() {
+                return Step::Done;
+            }
+
+            // Starting a new scan just past the sorted prefix.
+            if self.pointer <= self.sorted {
+                self.best = self.sorted;
+                self.pointer = self.sorted + 1;
+            }
+
+            if self.pointer < columns.len() {
+                let (i, best) = (self.pointer, self.best);
+                if !columns.compare(best, i, direction) {
+                    self.best = i;
+                }
+                self.pointer += 1;
+                return Step::Compare(i, best);
+            }
+
+            // The scan is over, so move the best element into place.
+            let (i, best) = (self.sorted, self.best);
+            self.sorted += 1;
+            self.pointer = 0;
+
+            if best != i {
+                columns.swap(i, best);
+                return Step::Swap(i, best);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum CyclePhase {
+    #[default]
+    Count,
+    Skip,
+}
+
+/// [Cycle Sort]
+/// Writes every element straight to its final position, so that it
+/// makes the fewest writes possible, which is what it is for. The
+/// element at start is held, and its final position is found by
+/// counting the elements after start which belong before it. The held
+/// element is then swapped there, which picks up the element that was
+/// in the way, and the count starts again for that one. Once the held
+/// element belongs at start, the cycle is closed. Elements equal to
+/// the held one are skipped over, so that duplicates work.
+#[derive(Clone, Default)]
+pub struct CycleSort {
+    start: usize,
+    pointer: usize,
+    position: usize,
+    phase: CyclePhase,
+    writes: usize,
+}
+
+impl SortAlgorithm for CycleSort {
+    fn reset(&mut self) {
+        *self = CycleSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            let start = self.start;
+            if start + 1 >= columns.len() {
+                return Step::Done;
+            }
+
+            // Starting to count for a newly held element.
+            if self.pointer <= start {
+                self.pointer = start + 1;
+                self.position = start;
+                self.phase = CyclePhase::Count;
+            }
+
+            match self.phase {
+                CyclePhase::Count if self.pointer < columns.len() => {
+                    let i = self.pointer;
+                    if !columns.compare(start, i, direction) {
+                        self.position += 1;
+                    }
+                    self.pointer += 1;
+                    return Step::Compare(start, i);
+                }
+                CyclePhase::Count => {
+                    if self.position == start {
+                        // The cycle is closed.
+                        self.start += 1;
+                        self.pointer = 0;
+                        continue;
+                    }
+                    self.phase = CyclePhase::Skip;
+                }
+                CyclePhase::Skip => {
+                    let position = self.position;
+                    if columns.compare(start, position, direction) && columns.compare(position, start, direction) {
+                        self.position += 1;
+                        return Step::Compare(start, position);
+                    }
+
+                    columns.swap(start, position);
+                    self.writes += 1;
+                    self.pointer = 0;
+                    return Step::Swap(start, position);
+                }
+            }
+        }
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        vec![Marker::Pivot(self.start), Marker::Boundary(self.start)]
+    }
+
+    fn status(&self) -> Option<String> {
+        Some(format!("writes: {}", self.writes))
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum TournamentPhase {
+    #[default]
+    Start,
+    Load,
+    Build,
+    Extract,
+    Replay,
+    Copy,
+    Done,
+}
+
+/// [Tournament Sort]
+/// Holds a knockout tournament between the columns. The tree is a
+/// complete binary tree stored like a heap, with a leaf for every
+/// column, padded out to a power of two, and each match is won by
+/// the element which belongs first. The winner at the root is written
+/// out to the auxiliary buffer, its leaf is emptied, and only the
+/// matches along its path are replayed to find the next winner. The
+/// columns themselves are never touched until the whole buffer is
+/// copied back. Origin is the column each node's value came from.
+#[derive(Clone, Default)]
+pub struct TournamentSort {
+    tree: Vec<Option<i32>>,
+    origin: Vec<usize>,
+    aux: Vec<Option<i32>>,
+    leaves: usize,
+    node: usize,
+    out: usize,
+    phase: TournamentPhase,
+}
+
+impl TournamentSort {
+    /// [Play]
+    /// Decides the match at node between the winners of its two
+    /// children. A player with no opponent wins without a comparison.
+    /// The columns still hold every player, so the match compares the
+    /// columns the two came from.
+    fn play(&mut self, node: usize, columns: &mut Array, direction: i32) -> Option<Step> {
+        let (left, right) = (2 * node + 1, 2 * node + 2);
+        let (winner, step) = match (self.tree[left], self.tree[right]) {
+            (Some(_), Some(_)) => {
+                let winner = if columns.compare(self.origin[left], self.origin[right], direction) { left } else { right };
+                (winner, Some(Step::Compare(self.origin[left], self.origin[right])))
+            }
+            (Some(_), None) => (left, None),
+            _ => (right, None),
+        };
+
+        self.tree[node] = self.tree[winner];
+        self.origin[node] = self.origin[winner];
+        step
+    }
+}
+
+impl SortAlgorithm for TournamentSort {
+    fn reset(&mut self) {
+        *self = TournamentSort::default();
+    }
+
+    fn step(&mut self, columns: &mut Array, direction: i32) -> Step {
+        loop {
+            match self.phase {
+                TournamentPhase::Start => {
+                    if columns.len() < 2 {
+                        return Step::Done;
+                    }
+                    self.leaves = columns.len().next_power_of_two();
+                    self.tree = vec![None; 2 * self.leaves - 1];
+                    self.origin = vec![0; 2 * self.leaves - 1];
+                    self.aux = vec![None; columns.len()];
+                    self.out = 0;
+                    self.phase = TournamentPhase::Load;
+                }
+                // The players are read into their leaves one at a time.
+                TournamentPhase::Load => {
+                    if self.out >= columns.len() {
+                        self.node = self.leaves - 1;
+                        self.out = 0;
+                        self.phase = TournamentPhase::Build;
+                        continue;
+                    }
+                    let i = self.out;
+                    self.tree[self.leaves - 1 + i] = Some(columns.read(i));
+                    self.origin[self.leaves - 1 + i] = i;
+                    self.out += 1;
+                    return Step::Read(i);
+                }
+                TournamentPhase::Build => {
+                    if self.node == 0 {
+                        self.phase = TournamentPhase::Extract;
+                        continue;
+                    }
+                    self.node -= 1;
+                    if let Some(step) = self.play(self.node, columns, direction) {
+                        return step;
+                    }
+                }
+                TournamentPhase::Extract => {
+                    let Some(value) = self.tree[0] else {
+                        self.out = 0;
+                        self.phase = TournamentPhase::Copy;
+                        continue;
+                    };
+
+                    // Knock the winner out, and replay its path.
+                    self.aux[self.out] = Some(value);
+                    columns.aux_write(self.out, value);
+                    self.node = self.leaves - 1 + self.origin[0];
+                    self.tree[self.node] = None;
+                    self.out += 1;
+                    self.phase = TournamentPhase::Replay;
+                    return Step::AuxWrite(self.out - 1);
+                }
+                TournamentPhase::Replay => {
+                    if self.node == 0 {
+                        self.phase = TournamentPhase::Extract;
+                        continue;
+                    }
+                    self.node = (self.node - 1) / 2;
+                    if let Some(step) = self.play(self.node, columns, direction) {
+                        return step;
+                    }
+                }
+                TournamentPhase::Copy => {
+                    if self.out >= columns.len() {
+                        self.phase = TournamentPhase::Done;
+                        continue;
+                    }
+                    let i = self.out;
+                    columns.write(i, self.aux[i].take().unwrap());
+                    self.out += 1;
+                    return Step::Write(i);
+                }
+                TournamentPhase::Done => return Step::Done,
+            }
+        }
+    }
+
+    fn panels(&self) -> Vec<Panel<'_>> {
+        let active = match self.phase {
+            TournamentPhase::Load => Some(self.leaves - 1 + self.out.saturating_sub(1)),
+            TournamentPhase::Build | TournamentPhase::Replay => Some(self.node),
+            _ => None,
+        };
+        vec![Panel::Tree(&self.tree, active), Panel::Aux(&self.aux)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{shuffled, sorted};
+    use super::super::Trace;
+    use super::*;
+
+    #[test]
+    fn cycle_sort_puts_each_column_in_place_once() {
+        let mut columns = shuffled(80);
+        let mut algorithm = CycleSort::default();
+        let mut trace = Trace::default();
+        let mut placed = vec![];
+        loop {
+            match trace.step(&mut algorithm, &mut columns, 1) {
+                Step::Done => break,
+                Step::Swap(_, position) => placed.push(position),
+                _ => {}
+            }
+        }
+        assert_eq!(columns, sorted(&columns, 1));
+        let writes = placed.len();
+        placed.sort();
+        placed.dedup();
+        assert_eq!(placed.len(), writes, "a column was written twice");
+    }
+
+    #[test]
+    fn tournament_sort_loads_a_leaf_a_step() {
+        let mut columns = shuffled(50);
+        let mut algorithm = TournamentSort::default();
+        let mut trace = Trace::default();
+        let loads: Vec<Step> = (0..50).map(|_| trace.step(&mut algorithm, &mut columns, 1)).collect();
+        assert_eq!(loads, (0..50).map(Step::Read).collect::<Vec<_>>());
+        while trace.step(&mut algorithm, &mut columns, 1) != Step::Done {}
+        assert_eq!(columns, sorted(&columns, 1));
+    }
+}
@@ -0,0 +1,346 @@
+/*****************************************************************/
+//! [Operation Trace]
+/*****************************************************************/
+//!
+//! Algorithms never get their hands on the columns directly. Every
+//! step is given an Array instead, which does the reading, comparing
+//! and writing on the algorithm's behalf, and records each operation
+//! as an Event in the Trace. The trace is then the one place where
+//! everything that happened to the columns is written down, so the
+//! highlights, the statistics and the exported trace all agree.
+//!
+//! Algorithms which decide a whole batch of operations up front may
+//! peek at the values without recording anything, since the
+//! operations themselves are recorded once they are performed.
+
+use super::{in_order, SortAlgorithm, Step};
+
+/// [Event]
+/// A single operation on the columns. Writes carry the value written,
+/// and AuxWrite is a write of a value into an algorithm's own buffer
+/// at the given index, which touches no column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Compare(usize, usize),
+    Swap(usize, usize),
+    Write(usize, i32),
+    Read(usize),
+    AuxWrite(usize, i32),
+}
+
+impl Event {
+    /// [Touches]
+    /// True if the column at index was read or written by this event.
+    pub fn touches(&self, index: usize) -> bool {
+        match *self {
+            Event::Compare(i, j) | Event::Swap(i, j) => index == i || index == j,
+            Event::Write(i, _) | Event::Read(i) => index == i,
+            Event::AuxWrite(..) => false,
+        }
+    }
+
+    /// [Offset]
+    /// The same event with its column indices moved along by offset.
+    fn offset(self, offset: usize) -> Event {
+        match self {
+            Event::Compare(i, j) => Event::Compare(i + offset, j + offset),
+            Event::Swap(i, j) => Event::Swap(i + offset, j + offset),
+            Event::Write(i, value) => Event::Write(i + offset, value),
+            Event::Read(i) => Event::Read(i + offset),
+            Event::AuxWrite(..) => self,
+        }
+    }
+
+    /// [Text]
+    /// The event as a line of the exported trace.
+    fn text(&self) -> String {
+        match *self {
+            Event::Compare(i, j) => format!("compare {} {}", i, j),
+            Event::Swap(i, j) => format!("swap {} {}", i, j),
+            Event::Write(i, value) => format!("write {} {}", i, value),
+            Event::Read(i) => format!("read {}", i),
+            Event::AuxWrite(i, value) => format!("aux {} {}", i, value),
+        }
+    }
+}
+
+/// [Stats]
+/// How many of each kind of event the trace holds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub compares: usize,
+    pub swaps: usize,
+    pub writes: usize,
+    pub reads: usize,
+    pub aux_writes: usize,
+}
+
+impl Stats {
+    /// [Total]
+    /// How many events there are of every kind put together.
+    pub fn total(&self) -> usize {
+        self.compares + self.swaps + self.writes + self.reads + self.aux_writes
+    }
+
+    /// [Count]
+    /// The count kept for the kind of event given.
+    fn count(&mut self, event: &Event) -> &mut usize {
+        match event {
+            Event::Compare(..) => &mut self.compares,
+            Event::Swap(..) => &mut self.swaps,
+            Event::Write(..) => &mut self.writes,
+            Event::Read(..) => &mut self.reads,
+            Event::AuxWrite(..) => &mut self.aux_writes,
+        }
+    }
+}
+
+/// [Trace]
+/// Every event since the sort started, along with the columns it
+/// started from, where each step's events begin, and the most
+/// auxiliary memory the algorithm had held by the end of each step.
+#[derive(Clone, Default)]
+pub struct Trace {
+    start: Vec<i32>,
+    events: Vec<Event>,
+    steps: Vec<usize>,
+    peaks: Vec<usize>,
+    stats: Stats,
+}
+
+impl Trace {
+    /// [Start]
+    /// Forgets everything, ready for a sort of the given columns.
+    pub fn start(&mut self, columns: &[i32]) {
+        *self = Trace { start: columns.to_vec(), ..Trace::default() };
+    }
+
+    /// [Step]
+    /// Asks the algorithm to take one step on the columns, recording
+    /// whatever it does. Steps which do nothing, such as the steps
+    /// after a sort is done, are not counted.
+    pub fn step(&mut self, algorithm: &mut dyn SortAlgorithm, columns: &mut [i32], direction: i32) -> Step {
+        self.steps.push(self.events.len());
+        let step = algorithm.step(&mut Array::new(columns, self), direction);
+        if self.steps.last() == Some(&self.events.len()) {
+            self.steps.pop();
+        } else {
+            self.peaks.push(self.peak_memory().max(algorithm.aux_memory()));
+        }
+        step
+    }
+
+    /// [Record]
+    /// Adds an event to the end of the trace.
+    fn record(&mut self, event: Event) {
+        *self.stats.count(&event) += 1;
+        self.events.push(event);
+    }
+
+    /// [Truncate]
+    /// Forgets every step after the first steps, as if they had never
+    /// happened.
+    pub fn truncate(&mut self, steps: usize) {
+        let Some(&first) = self.steps.get(steps) else {
+            return;
+        };
+        for event in self.events.drain(first..) {
+            *self.stats.count(&event) -= 1;
+        }
+        self.steps.truncate(steps);
+        self.peaks.truncate(steps);
+    }
+
+    /// [Last]
+    /// The events of the last step which did anything.
+    pub fn last(&self) -> &[Event] {
+        let first = self.steps.last().copied().unwrap_or(self.events.len());
+        &self.events[first..]
+    }
+
+    /// [Steps]
+    /// The number of steps which did anything.
+    pub fn steps(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// [Stats]
+    /// How many of each kind of event there have been.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// [Peak Memory]
+    /// The most values the algorithm has held outside the columns at
+    /// the end of any step so far.
+    pub fn peak_memory(&self) -> usize {
+        self.peaks.last().copied().unwrap_or(0)
+    }
+
+    /// [Text]
+    /// The whole trace as text, starting with the columns it began
+    /// from, followed by one line per event numbered by its step.
+    pub fn text(&self) -> String {
+        let start: Vec<String> = self.start.iter().map(|value| value.to_string()).collect();
+        let mut text = format!("columns {}\n", start.join(" "));
+        for (step, &first) in self.steps.iter().enumerate() {
+            let end = self.steps.get(step + 1).copied().unwrap_or(self.events.len());
+            for event in &self.events[first..end] {
+                text.push_str(&format!("{} {}\n", step, event.text()));
+            }
+        }
+        text
+    }
+}
+
+/// [Array]
+/// The columns as an algorithm sees them during a step. Every
+/// operation is recorded in the trace. A slice of the array is an
+/// Array too, whose events are recorded at their real indices.
+pub struct Array<'a> {
+    columns: &'a mut [i32],
+    offset: usize,
+    trace: &'a mut Trace,
+}
+
+impl<'a> Array<'a> {
+    /// [New]
+    /// Wraps the columns, recording into the trace.
+    pub fn new(columns: &'a mut [i32], trace: &'a mut Trace) -> Array<'a> {
+        Array { columns, offset: 0, trace }
+    }
+
+    /// [Len]
+    /// The number of columns.
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// [Is Empty]
+    /// True when there are no columns at all.
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// [Values]
+    /// The values of the columns, without recording a read. Only for
+    /// deciding operations which are recorded when they happen.
+    pub fn values(&self) -> &[i32] {
+        self.columns
+    }
+
+    /// [Untraced]
+    /// The columns themselves, for work which can't be done one
+    /// operation at a time through the array, such as a round shared
+    /// out between threads. Whatever it does must be recorded too.
+    pub fn untraced(&mut self) -> &mut [i32] {
+        self.columns
+    }
+
+    /// [Record]
+    /// Records an event which was performed some other way, such as a
+    /// queued comparison or a round run on several threads at once.
+    pub fn record(&mut self, event: Event) {
+        self.trace.record(event.offset(self.offset));
+    }
+
+    /// [Read]
+    /// The value of the column at i.
+    pub fn read(&mut self, i: usize) -> i32 {
+        self.record(Event::Read(i));
+        self.columns[i]
+    }
+
+    /// [Compare]
+    /// True when the column at i may come before the column at j in
+    /// the given direction.
+    pub fn compare(&mut self, i: usize, j: usize, direction: i32) -> bool {
+        self.record(Event::Compare(i, j));
+        in_order(self.columns[i], self.columns[j], direction)
+    }
+
+    /// [Compare Held]
+    /// Like compare, for values which are held in the algorithm's own
+    /// buffer rather than in the columns. Each value is given with the
+    /// column it came from, or is still in, which is what the compare
+    /// is recorded against.
+    pub fn compare_held(&mut self, (i, a): (usize, i32), (j, b): (usize, i32), direction: i32) -> bool {
+        self.record(Event::Compare(i, j));
+        in_order(a, b, direction)
+    }
+
+    /// [Swap]
+    /// Swaps the columns at i and j.
+    pub fn swap(&mut self, i: usize, j: usize) {
+        self.record(Event::Swap(i, j));
+        self.columns.swap(i, j);
+    }
+
+    /// [Write]
+    /// Sets the column at i to value.
+    pub fn write(&mut self, i: usize, value: i32) {
+        self.record(Event::Write(i, value));
+        self.columns[i] = value;
+    }
+
+    /// [Copy]
+    /// Copies the column at from over the column at to, which is a
+    /// read followed by a write.
+    pub fn copy(&mut self, from: usize, to: usize) {
+        let value = self.read(from);
+        self.write(to, value);
+    }
+
+    /// [Reverse]
+    /// Reverses the columns low..high, by swapping from both ends.
+    pub fn reverse(&mut self, low: usize, high: usize) {
+        for k in 0..(high - low) / 2 {
+            self.swap(low + k, high - 1 - k);
+        }
+    }
+
+    /// [Aux Write]
+    /// Records that value was written to index of the algorithm's own
+    /// buffer.
+    pub fn aux_write(&mut self, index: usize, value: i32) {
+        self.record(Event::AuxWrite(index, value));
+    }
+
+    /// [Slice]
+    /// The columns low..high as an array of their own, for handing to
+    /// another algorithm.
+    pub fn slice(&mut self, low: usize, high: usize) -> Array<'_> {
+        Array { columns: &mut self.columns[low..high], offset: self.offset + low, trace: self.trace }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::BubbleSort;
+    use super::*;
+
+    #[test]
+    fn slices_record_at_their_real_indices() {
+        let mut columns = vec![3, 2, 1, 0];
+        let mut trace = Trace::default();
+        trace.start(&columns);
+        let mut array = Array::new(&mut columns, &mut trace);
+        let mut slice = array.slice(2, 4);
+        slice.swap(0, 1);
+        slice.copy(0, 1);
+        assert_eq!(columns, [3, 2, 0, 0]);
+        assert_eq!(trace.events, [Event::Swap(2, 3), Event::Read(2), Event::Write(3, 0)]);
+        assert_eq!(trace.stats(), Stats { swaps: 1, reads: 1, writes: 1, ..Stats::default() });
+    }
+
+    #[test]
+    fn steps_are_numbered_in_the_text() {
+        let mut columns = vec![2, 1];
+        let mut trace = Trace::default();
+        trace.start(&columns);
+        let mut algorithm = BubbleSort::default();
+        while trace.step(&mut algorithm, &mut columns, 1) != Step::Done {}
+        assert!(trace.text().starts_with("columns 2 1\n0 compare 0 1\n"));
+        assert_eq!(trace.steps(), trace.text().lines().last().unwrap().split(' ').next().unwrap().parse::<usize>().unwrap() + 1);
+    }
+}
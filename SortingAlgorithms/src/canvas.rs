@@ -0,0 +1,232 @@
+/*****************************************************************/
+//! [Software Canvas]
+/*****************************************************************/
+//!
+//! A drawing backend which needs no window and no graphics card. It
+//! fills triangles into a plain buffer of RGBA pixels, so the same
+//! drawing code which draws to the OpenGL window can draw frames for
+//! saving to files instead.
+//!
+//! Piston's graphics hand over every shape as triangles whose corners
+//! have already been moved into the -1 to 1 square by the transform,
+//! so all the canvas has to do is move them back to pixels and fill
+//! them in. A pixel is filled when its centre is inside the triangle.
+//! Pixels whose centres lie exactly on an edge belong to just one of
+//! the two triangles sharing it, so that rectangles, which are drawn
+//! as two triangles, never blend their diagonal twice.
+
+use std::fs::File;
+use std::io::BufWriter;
+
+use graphics::draw_state::DrawState;
+use graphics::{Context, Graphics, ImageSize};
+
+/// [Canvas]
+/// A picture width by height pixels, stored a row at a time from the
+/// top, with four bytes of red, green, blue and alpha per pixel.
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    /// [New]
+    /// A transparent canvas of the given size.
+    pub fn new(width: u32, height: u32) -> Canvas {
+        Canvas { width, height, pixels: vec![0; width as usize * height as usize * 4] }
+    }
+
+    /// [Width]
+    /// How many pixels wide the canvas is.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// [Height]
+    /// How many pixels tall the canvas is.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// [Pixels]
+    /// The red, green, blue and alpha bytes of every pixel, a row at a
+    /// time from the top.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// [Context]
+    /// The context to draw on the canvas with, where coordinates run
+    /// from the top left corner to width, height at the bottom right,
+    /// like in the window. Drawing is stretched to fit the canvas, so
+    /// it can be any size.
+    pub fn context(&self, width: f64, height: f64) -> Context {
+        Context::new_abs(width, height)
+    }
+
+    /// [Save PNG]
+    /// Writes the canvas to a PNG file.
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|error| error.to_string())?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+        writer.write_image_data(&self.pixels).map_err(|error| error.to_string())
+    }
+
+    /// [Blend]
+    /// Paints a colour over the pixel at x, y, letting whatever is
+    /// underneath show through as much as the colour is transparent.
+    fn blend(&mut self, x: usize, y: usize, colour: [f32; 4]) {
+        let index = (y * self.width as usize + x) * 4;
+        let pixel = &mut self.pixels[index..index + 4];
+        let alpha = colour[3].clamp(0.0, 1.0);
+        for (channel, &value) in pixel.iter_mut().zip(&colour[..3]) {
+            let under = *channel as f32 / 255.0;
+            *channel = ((value.clamp(0.0, 1.0) * alpha + under * (1.0 - alpha)) * 255.0).round() as u8;
+        }
+        let under = pixel[3] as f32 / 255.0;
+        pixel[3] = ((alpha + under * (1.0 - alpha)) * 255.0).round() as u8;
+    }
+
+    /// [Fill Triangle]
+    /// Fills the pixels whose centres are inside the triangle with the
+    /// given corners, which are in the -1 to 1 square.
+    fn fill_triangle(&mut self, corners: &[[f32; 2]], colour: [f32; 4]) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let mut points: Vec<[f32; 2]> = corners.iter().map(|&[x, y]| [(x + 1.0) * 0.5 * width, (1.0 - y) * 0.5 * height]).collect();
+
+        // Edges are walked one way round, so that the inside of every
+        // edge is on the same side.
+        if edge(points[0], points[1], points[2]) < 0.0 {
+            points.swap(1, 2);
+        }
+        let [a, b, c] = [points[0], points[1], points[2]];
+        if edge(a, b, c) == 0.0 {
+            return;
+        }
+
+        let left = a[0].min(b[0]).min(c[0]).floor().max(0.0) as usize;
+        let right = (a[0].max(b[0]).max(c[0]).ceil().min(width) as usize).min(self.width as usize);
+        let top = a[1].min(b[1]).min(c[1]).floor().max(0.0) as usize;
+        let bottom = (a[1].max(b[1]).max(c[1]).ceil().min(height) as usize).min(self.height as usize);
+
+        for y in top..bottom {
+            for x in left..right {
+                let centre = [x as f32 + 0.5, y as f32 + 0.5];
+                if inside(a, b, centre) && inside(b, c, centre) && inside(c, a, centre) {
+                    self.blend(x, y, colour);
+                }
+            }
+        }
+    }
+}
+
+/// [Edge]
+/// Which side of the line from a to b the point p is on, as a number
+/// which is positive on one side, negative on the other and zero on
+/// the line itself.
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// [Inside]
+/// True if p is on the inside of the edge from a to b. A point right
+/// on the edge only counts for one of the two directions the edge
+/// can be walked in, which is how a shared edge picks its triangle.
+fn inside(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> bool {
+    let side = edge(a, b, p);
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    side > 0.0 || (side == 0.0 && (dy > 0.0 || (dy == 0.0 && dx < 0.0)))
+}
+
+/// [No Texture]
+/// The canvas has no use for images, so its texture type is empty.
+pub struct NoTexture;
+
+impl ImageSize for NoTexture {
+    fn get_size(&self) -> (u32, u32) {
+        (0, 0)
+    }
+}
+
+/// [Canvas]
+/// Drawing to the canvas. The draw state is ignored, as nothing drawn
+/// uses clipping or blend modes, and textured triangles draw nothing.
+impl Graphics for Canvas {
+    type Texture = NoTexture;
+
+    fn clear_color(&mut self, color: [f32; 4]) {
+        let pixel = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+        for chunk in self.pixels.chunks_exact_mut(4) {
+            chunk.copy_from_slice(&pixel);
+        }
+    }
+
+    fn clear_stencil(&mut self, _value: u8) {}
+
+    fn tri_list<F>(&mut self, _draw_state: &DrawState, color: &[f32; 4], mut f: F)
+    where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]])),
+    {
+        f(&mut |vertices: &[[f32; 2]]| {
+            for corners in vertices.chunks_exact(3) {
+                self.fill_triangle(corners, *color);
+            }
+        });
+    }
+
+    // Triangles with a colour per corner are filled with the average of
+    // their corners' colours.
+    fn tri_list_c<F>(&mut self, _draw_state: &DrawState, mut f: F)
+    where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 4]])),
+    {
+        f(&mut |vertices: &[[f32; 2]], colors: &[[f32; 4]]| {
+            for (corners, colours) in vertices.chunks_exact(3).zip(colors.chunks_exact(3)) {
+                let average = [0, 1, 2, 3].map(|k| (colours[0][k] + colours[1][k] + colours[2][k]) / 3.0);
+                self.fill_triangle(corners, average);
+            }
+        });
+    }
+
+    fn tri_list_uv<F>(&mut self, _draw_state: &DrawState, _color: &[f32; 4], _texture: &NoTexture, _f: F)
+    where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 2]])),
+    {
+    }
+
+    fn tri_list_uv_c<F>(&mut self, _draw_state: &DrawState, _texture: &NoTexture, _f: F)
+    where
+        F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 2]], &[[f32; 4]])),
+    {
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rectangles_fill_whole_pixels_and_save() {
+        let mut canvas = Canvas::new(8, 6);
+        let context = canvas.context(8.0, 6.0);
+        graphics::rectangle([1.0, 0.0, 0.0, 1.0], [2.0, 1.0, 4.0, 3.0], context.transform, &mut canvas);
+        let red = |x: usize, y: usize| canvas.pixels[(y * 8 + x) * 4..][..4] == [255, 0, 0, 255];
+        let filled: usize = (0..6).map(|y| (0..8).filter(|&x| red(x, y)).count()).sum();
+        assert_eq!(filled, 12);
+        assert!(red(2, 1) && red(5, 3) && !red(1, 1) && !red(6, 3) && !red(2, 4));
+
+        let path = std::env::temp_dir().join(format!("sorting-canvas-{}.png", std::process::id()));
+        canvas.save_png(path.to_str().unwrap()).unwrap();
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (8, 6));
+        assert_eq!(pixels, canvas.pixels);
+    }
+}
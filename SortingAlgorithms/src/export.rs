@@ -0,0 +1,303 @@
+/*****************************************************************/
+//! [Animation Export]
+/*****************************************************************/
+//!
+//! Encodes frames drawn on the software canvas as an animated GIF or
+//! an animated PNG, so that a whole sort can be turned into a clip
+//! for slides and documents without recording the screen. Long sorts
+//! make for enormous GIFs, so frames can also be written out raw as a
+//! YUV4MPEG2 video at a fixed frame rate, to a file or piped straight
+//! into any video encoder.
+//!
+//! GIFs can only hold 256 colours a frame, so every frame has to be
+//! reduced to a palette first. An adaptive palette is picked for each
+//! frame on its own, which looks best, while the fixed palettes are
+//! shared by the whole animation and are much quicker. Animated PNGs
+//! keep every colour, so they ignore the palette.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::canvas::Canvas;
+use crate::options::Options;
+
+/// [Format]
+/// The kinds of file an animation can be saved as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Gif,
+    Apng,
+    Y4m,
+}
+
+impl Format {
+    /// [Of]
+    /// The format for a path, going by its extension. A path of - is
+    /// standard output, which is always a video.
+    pub fn of(path: &str) -> Result<Format, String> {
+        let extension = path.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase()).unwrap_or_default();
+        match extension.as_str() {
+            _ if path == "-" => Ok(Format::Y4m),
+            "gif" => Ok(Format::Gif),
+            "png" | "apng" => Ok(Format::Apng),
+            "y4m" => Ok(Format::Y4m),
+            _ => Err(format!("can't tell what kind of animation {} is, it should end in .gif, .png, .apng or .y4m, or be -", path)),
+        }
+    }
+}
+
+/// [Palette]
+/// How the colours of a GIF are chosen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Palette {
+    /// The best 256 colours for each frame.
+    Adaptive,
+    /// The 216 web safe colours, six levels each of red, green and blue.
+    Web,
+    /// 256 shades of grey.
+    Grey,
+}
+
+impl Palette {
+    /// [From Name]
+    /// The palette with the given name, if there is one.
+    pub fn from_name(name: &str) -> Option<Palette> {
+        match name {
+            "adaptive" => Some(Palette::Adaptive),
+            "web" => Some(Palette::Web),
+            "grey" | "gray" => Some(Palette::Grey),
+            _ => None,
+        }
+    }
+
+    /// [Colours]
+    /// The colours of a fixed palette as red, green, blue bytes, which
+    /// is empty for the adaptive palette.
+    fn colours(self) -> Vec<u8> {
+        match self {
+            Palette::Adaptive => vec![],
+            Palette::Web => (0..216u32).flat_map(|index| [index / 36, index / 6 % 6, index % 6].map(|level| (level * 51) as u8)).collect(),
+            Palette::Grey => (0..=255u8).flat_map(|shade| [shade; 3]).collect(),
+        }
+    }
+
+    /// [Index]
+    /// The index of the colour of a fixed palette nearest to a pixel.
+    fn index(self, [r, g, b]: [u8; 3]) -> u8 {
+        let level = |channel: u8| (channel as u32 * 5 + 127) / 255;
+        match self {
+            Palette::Adaptive => 0,
+            Palette::Web => (level(r) * 36 + level(g) * 6 + level(b)) as u8,
+            Palette::Grey => ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8,
+        }
+    }
+}
+
+/// [Animation]
+/// An animation being written, a frame at a time, along with how long
+/// its frames are shown for, in milliseconds.
+pub struct Animation {
+    writer: Writer,
+    delay: u32,
+    hold: u32,
+}
+
+/// [Writer]
+/// Whatever the frames of an animation are being written with.
+enum Writer {
+    Gif(gif::Encoder<BufWriter<File>>, Palette),
+    Apng(png::Writer<BufWriter<File>>),
+    Y4m(BufWriter<Box<dyn Write>>, u32),
+}
+
+impl Animation {
+    /// [Create]
+    /// Starts an animation of the given size at path, in the format
+    /// its path asks for. Animated PNGs need to know how many frames
+    /// there will be up front. GIFs and animated PNGs loop forever,
+    /// while videos play once at the frame rate in the options.
+    pub fn create(path: &str, width: u32, height: u32, frames: u32, options: &Options) -> Result<Animation, String> {
+        let writer = match Format::of(path)? {
+            Format::Gif => {
+                let file = BufWriter::new(File::create(path).map_err(|error| error.to_string())?);
+                let (width, height) = (u16::try_from(width).map_err(|_| "too wide for a GIF")?, u16::try_from(height).map_err(|_| "too tall for a GIF")?);
+                let mut encoder = gif::Encoder::new(file, width, height, &options.palette.colours()).map_err(|error| error.to_string())?;
+                encoder.set_repeat(gif::Repeat::Infinite).map_err(|error| error.to_string())?;
+                Writer::Gif(encoder, options.palette)
+            }
+            Format::Apng => {
+                let file = BufWriter::new(File::create(path).map_err(|error| error.to_string())?);
+                let mut encoder = png::Encoder::new(file, width, height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_animated(frames, 0).map_err(|error| error.to_string())?;
+                Writer::Apng(encoder.write_header().map_err(|error| error.to_string())?)
+            }
+            Format::Y4m => {
+                let out: Box<dyn Write> = if path == "-" { Box::new(std::io::stdout()) } else { Box::new(File::create(path).map_err(|error| error.to_string())?) };
+                let mut out = BufWriter::new(out);
+                writeln!(out, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C420jpeg", width, height, options.fps).map_err(|error| error.to_string())?;
+                Writer::Y4m(out, options.fps)
+            }
+        };
+        Ok(Animation { writer, delay: options.delay, hold: options.hold })
+    }
+
+    /// [Frame]
+    /// Adds the canvas as the next frame. The last frame is held for
+    /// longer, so that the sorted columns can be seen. GIFs only count
+    /// time in hundredths of a second, so their delays are rounded to
+    /// the nearest of those. Videos show every other frame once, and
+    /// repeat the last frame for as long as it is held.
+    pub fn frame(&mut self, canvas: &Canvas, last: bool) -> Result<(), String> {
+        let delay = if last { self.hold } else { self.delay };
+        match &mut self.writer {
+            Writer::Gif(encoder, palette) => {
+                let (width, height) = (canvas.width() as u16, canvas.height() as u16);
+                let mut frame = match palette {
+                    Palette::Adaptive => gif::Frame::from_rgba_speed(width, height, &mut canvas.pixels().to_vec(), 10),
+                    _ => {
+                        let indices: Vec<u8> = canvas.pixels().chunks_exact(4).map(|pixel| palette.index([pixel[0], pixel[1], pixel[2]])).collect();
+                        gif::Frame::from_indexed_pixels(width, height, indices, None)
+                    }
+                };
+                frame.delay = ((delay + 5) / 10).clamp(1, u16::MAX as u32) as u16;
+                encoder.write_frame(&frame).map_err(|error| error.to_string())
+            }
+            Writer::Apng(writer) => {
+                writer.set_frame_delay(delay.min(u16::MAX as u32) as u16, 1000).map_err(|error| error.to_string())?;
+                writer.write_image_data(canvas.pixels()).map_err(|error| error.to_string())
+            }
+            Writer::Y4m(out, fps) => {
+                let repeats = if last { (self.hold as u64 * *fps as u64).div_ceil(1000).max(1) } else { 1 };
+                let planes = yuv420(canvas);
+                for _ in 0..repeats {
+                    out.write_all(b"FRAME\n").and_then(|()| out.write_all(&planes)).map_err(|error| error.to_string())?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// [Finish]
+    /// Writes the end of the animation, once every frame is in.
+    pub fn finish(self) -> Result<(), String> {
+        match self.writer {
+            Writer::Gif(encoder, _) => encoder.into_inner().map(|_| ()).map_err(|error| error.to_string()),
+            Writer::Apng(writer) => writer.finish().map_err(|error| error.to_string()),
+            Writer::Y4m(mut out, _) => out.flush().map_err(|error| error.to_string()),
+        }
+    }
+}
+
+/// [YUV 4:2:0]
+/// The canvas as the three planes of a video frame. The brightness
+/// plane is full size, while each colour plane has one sample for
+/// every two by two block of pixels, rounding up at odd edges. The
+/// colours are converted the way standard definition video expects,
+/// with brightness from 16 to 235.
+fn yuv420(canvas: &Canvas) -> Vec<u8> {
+    let (width, height) = (canvas.width() as usize, canvas.height() as usize);
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    let pixels = canvas.pixels();
+    let rgb = |x: usize, y: usize| {
+        let index = (y * width + x) * 4;
+        [0, 1, 2].map(|k| pixels[index + k] as f32 / 255.0)
+    };
+
+    let mut planes = Vec::with_capacity(width * height + chroma_width * chroma_height * 2);
+    for y in 0..height {
+        for x in 0..width {
+            let [r, g, b] = rgb(x, y);
+            planes.push((16.0 + 65.481 * r + 128.553 * g + 24.966 * b).round() as u8);
+        }
+    }
+
+    let mut blues = Vec::with_capacity(chroma_width * chroma_height);
+    let mut reds = Vec::with_capacity(chroma_width * chroma_height);
+    for y in 0..chroma_height {
+        for x in 0..chroma_width {
+            let block: Vec<[f32; 3]> = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                .iter()
+                .map(|&(dx, dy)| rgb((2 * x + dx).min(width - 1), (2 * y + dy).min(height - 1)))
+                .collect();
+            let [r, g, b] = [0, 1, 2].map(|k| block.iter().map(|colour| colour[k]).sum::<f32>() / 4.0);
+            blues.push((128.0 - 37.797 * r - 74.203 * g + 112.0 * b).round() as u8);
+            reds.push((128.0 + 112.0 * r - 93.786 * g - 18.214 * b).round() as u8);
+        }
+    }
+    planes.extend(blues);
+    planes.extend(reds);
+    planes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// [Export]
+    /// Saves two frames of a small canvas, with the second one last,
+    /// and returns the bytes of the file.
+    fn export(extension: &str, palette: Palette) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("sorting-export-{}-{:?}.{}", std::process::id(), palette, extension));
+        let path = path.to_str().unwrap();
+        let options = Options { palette, hold: 100, ..Options::default() };
+        let canvas = Canvas::new(5, 3);
+        let mut animation = Animation::create(path, 5, 3, 2, &options).unwrap();
+        animation.frame(&canvas, false).unwrap();
+        animation.frame(&canvas, true).unwrap();
+        animation.finish().unwrap();
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn formats_go_by_extension() {
+        assert_eq!(Format::of("sort.GIF"), Ok(Format::Gif));
+        assert_eq!(Format::of("sort.png"), Ok(Format::Apng));
+        assert_eq!(Format::of("sort.apng"), Ok(Format::Apng));
+        assert_eq!(Format::of("sort.y4m"), Ok(Format::Y4m));
+        assert_eq!(Format::of("-"), Ok(Format::Y4m));
+        assert!(Format::of("sort.mp4").is_err());
+        assert!(Format::of("sort").is_err());
+    }
+
+    #[test]
+    fn gif_reads_back() {
+        for palette in [Palette::Adaptive, Palette::Web, Palette::Grey] {
+            let bytes = export("gif", palette);
+            assert_eq!(&bytes[..6], b"GIF89a");
+            let mut decoder = gif::DecodeOptions::new().read_info(bytes.as_slice()).unwrap();
+            assert_eq!((decoder.width(), decoder.height()), (5, 3));
+            let mut delays = vec![];
+            while let Some(frame) = decoder.read_next_frame().unwrap() {
+                delays.push(frame.delay);
+            }
+            assert_eq!(delays, [4, 10], "{:?}", palette);
+        }
+    }
+
+    #[test]
+    fn apng_reads_back() {
+        let bytes = export("png", Palette::Adaptive);
+        let reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let info = reader.info();
+        assert_eq!((info.width, info.height), (5, 3));
+        assert_eq!(info.animation_control.map(|control| (control.num_frames, control.num_plays)), Some((2, 0)));
+    }
+
+    #[test]
+    fn y4m_has_a_header_and_whole_frames() {
+        let bytes = export("y4m", Palette::Adaptive);
+        let header = b"YUV4MPEG2 W5 H3 F30:1 Ip A1:1 C420jpeg\n";
+        assert_eq!(&bytes[..header.len()], header);
+
+        // Five by three pixels, with two colour planes of three by two.
+        let frame = b"FRAME\n".len() + 5 * 3 + 2 * 3 * 2;
+        let frames = &bytes[header.len()..];
+        // The last frame is held for 100ms, which is three frames at 30fps.
+        assert_eq!(frames.len(), 4 * frame);
+        assert!(frames.chunks(frame).all(|frame| frame.starts_with(b"FRAME\n")));
+    }
+}
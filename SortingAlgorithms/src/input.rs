@@ -0,0 +1,387 @@
+/*****************************************************************/
+//! [Input Distributions]
+/*****************************************************************/
+//!
+//! The columns a sort starts from. A uniform shuffle is the usual
+//! choice, but the adaptive sorts only show what they can do on
+//! input which already has some structure to it, such as runs,
+//! duplicates or a nearly sorted order. Every distribution makes
+//! values from 1 up to the number of columns, so most of them are
+//! permutations, while a few deliberately repeat values. The values
+//! can then be stretched onto any other domain, which leaves gaps
+//! when it is wider than the number of columns, and repeats values
+//! when it is narrower.
+//!
+//! All of the randomness comes from a generator seeded by the caller,
+//! so the same seed always gives the same columns. Shuffles are done
+//! by Fisher-Yates, either all at once, or one swap per step so that
+//! the shuffle can be watched before the sort begins.
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::algorithms::{Array, Marker, SortAlgorithm, Step};
+
+/// [Input]
+/// A way of generating the columns for a sort.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    /// Every order equally likely.
+    Uniform,
+    /// Sorted, but back to front.
+    Reversed,
+    /// Sorted, apart from a few random pairs swapped.
+    NearlySorted,
+    /// Sorted, apart from a tenth of the values added at random on the end.
+    RandomTail,
+    /// Only a handful of distinct values, in random order.
+    FewUnique,
+    /// Several sorted runs, each climbing from small to large.
+    Sawtooth,
+    /// Climbing to the middle, then falling away again.
+    OrganPipe,
+    /// Values bunched around the middle, in random order.
+    Gaussian,
+    /// About the square root of N sorted runs of random values.
+    SqrtRuns,
+    /// The small half and the large half, sorted and woven together.
+    Interleaved,
+    /// Smooth random hills and valleys.
+    Perlin,
+}
+
+/// [Inputs]
+/// Every distribution, in the order of their number keys after
+/// Uniform, which is on R.
+pub const INPUTS: [Input; 11] = [
+    Input::Uniform,
+    Input::Reversed,
+    Input::NearlySorted,
+    Input::RandomTail,
+    Input::FewUnique,
+    Input::Sawtooth,
+    Input::OrganPipe,
+    Input::Gaussian,
+    Input::SqrtRuns,
+    Input::Interleaved,
+    Input::Perlin,
+];
+
+// The number of teeth on a sawtooth.
+const TEETH: usize = 4;
+
+// The number of distinct values for few unique.
+const UNIQUE: usize = 5;
+
+// The number of octaves of Perlin noise added together, each with
+// twice the detail and half the height of the last.
+const OCTAVES: usize = 4;
+
+impl Input {
+    /// [Name]
+    /// The name the distribution is chosen by, on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Input::Uniform => "uniform",
+            Input::Reversed => "reversed",
+            Input::NearlySorted => "nearly-sorted",
+            Input::RandomTail => "random-tail",
+            Input::FewUnique => "few-unique",
+            Input::Sawtooth => "sawtooth",
+            Input::OrganPipe => "organ-pipe",
+            Input::Gaussian => "gaussian",
+            Input::SqrtRuns => "sqrt-runs",
+            Input::Interleaved => "interleaved",
+            Input::Perlin => "perlin",
+        }
+    }
+
+    /// [From Name]
+    /// The distribution with the given name, if there is one.
+    pub fn from_name(name: &str) -> Option<Input> {
+        INPUTS.into_iter().find(|input| input.name() == name)
+    }
+
+    /// [Generate]
+    /// Makes n columns following the distribution.
+    pub fn generate(self, n: usize, rng: &mut impl Rng) -> Vec<i32> {
+        let sorted: Vec<i32> = (1..=n as i32).collect();
+        match self {
+            Input::Uniform => {
+                let mut columns = sorted;
+                shuffle(&mut columns, rng);
+                columns
+            }
+            Input::Reversed => sorted.into_iter().rev().collect(),
+            // One swap for every twenty columns, and at least one.
+            Input::NearlySorted => {
+                let mut columns = sorted;
+                for _ in 0..(n / 20).max(1) {
+                    let (i, j) = (rng.gen_range(0..n.max(1)), rng.gen_range(0..n.max(1)));
+                    columns.swap(i, j);
+                }
+                columns
+            }
+            Input::RandomTail => {
+                let mut columns = sorted;
+                shuffle(&mut columns, rng);
+                let head = n - (n / 10).max(1).min(n);
+                columns[..head].sort_unstable();
+                columns
+            }
+            Input::FewUnique => {
+                let mut columns: Vec<i32> = (0..n).map(|i| ((i % UNIQUE + 1) * n / UNIQUE).max(1) as i32).collect();
+                shuffle(&mut columns, rng);
+                columns
+            }
+            Input::Sawtooth => {
+                let tooth = n.div_ceil(TEETH).max(1);
+                rank((0..n).map(|i| (i % tooth) as f64).collect())
+            }
+            Input::OrganPipe => rank((0..n).map(|i| i.min(n - 1 - i) as f64).collect()),
+            // Box-Muller turns pairs of uniform values into normally
+            // distributed ones, centred on the middle value and
+            // spread so that nearly all of them fit.
+            Input::Gaussian => (0..n)
+                .map(|_| {
+                    let (u, v): (f64, f64) = (rng.gen_range(f64::EPSILON..1.0), rng.gen());
+                    let normal = (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos();
+                    let value = (n as f64 + 1.0) / 2.0 + normal * n as f64 / 6.0;
+                    value.round().clamp(1.0, n as f64) as i32
+                })
+                .collect(),
+            Input::SqrtRuns => {
+                let mut columns = sorted;
+                shuffle(&mut columns, rng);
+                let run = ((n as f64).sqrt().ceil() as usize).max(1);
+                for chunk in columns.chunks_mut(run) {
+                    chunk.sort_unstable();
+                }
+                columns
+            }
+            Input::Interleaved => rank((0..n).map(|i| (if i % 2 == 0 { i / 2 } else { n + i / 2 }) as f64).collect()),
+            Input::Perlin => {
+                let positions: Vec<f64> = (0..n).map(|i| i as f64 / n as f64).collect();
+                let mut heights = vec![0.0; n];
+                for octave in 0..OCTAVES {
+                    let lattice = 4 << octave;
+                    let gradients: Vec<f64> = (0..=lattice).map(|_| rng.gen_range(-1.0..1.0)).collect();
+                    for (height, &x) in heights.iter_mut().zip(&positions) {
+                        *height += perlin(&gradients, x * lattice as f64) / (1 << octave) as f64;
+                    }
+                }
+                scale(&heights, n)
+            }
+        }
+    }
+}
+
+/// [Domain]
+/// The range of values the columns can hold, from min up to max.
+/// Bars are drawn to scale against it, so that the smallest value is
+/// the shortest bar and the largest reaches the full height.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Domain {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl Domain {
+    /// [New]
+    /// The values from min up to max, whichever way round they come.
+    pub fn new(min: i32, max: i32) -> Domain {
+        Domain { min: min.min(max), max: min.max(max) }
+    }
+
+    /// [Parse]
+    /// A domain written as MIN..MAX, such as -50..50.
+    pub fn parse(text: &str) -> Option<Domain> {
+        let (min, max) = text.split_once("..")?;
+        Some(Domain::new(min.trim().parse().ok()?, max.trim().parse().ok()?))
+    }
+
+    /// [Levels]
+    /// How many different values fit in the domain.
+    pub fn levels(self) -> f64 {
+        (self.max as f64 - self.min as f64) + 1.0
+    }
+
+    /// [Fraction]
+    /// How far up the domain a value is, where min is one level above
+    /// nothing and max is the whole way.
+    pub fn fraction(self, value: i32) -> f64 {
+        (value as f64 - self.min as f64 + 1.0) / self.levels()
+    }
+
+    /// [Stretch]
+    /// Moves values made from 1 up to n onto the domain, keeping them
+    /// in the same order. The offsets can be wider than an i32 when the
+    /// domain is, so they are added on in i64.
+    pub fn stretch(self, values: &[i32], n: usize) -> Vec<i32> {
+        let scale = if n > 1 { (self.levels() - 1.0) / (n - 1) as f64 } else { 0.0 };
+        values
+            .iter()
+            .map(|&value| (self.min as i64 + ((value - 1) as f64 * scale).round() as i64).min(self.max as i64) as i32)
+            .collect()
+    }
+}
+
+/// [Shuffle]
+/// Fisher-Yates, which swaps each column from the last down with one
+/// picked at random from those up to and including itself, making
+/// every order equally likely.
+fn shuffle(columns: &mut [i32], rng: &mut impl Rng) {
+    for i in (1..columns.len()).rev() {
+        let j = rng.gen_range(0..=i);
+        columns.swap(i, j);
+    }
+}
+
+/// [Shuffle]
+/// The same Fisher-Yates shuffle done one swap per step, so that it can
+/// be shown before a sort. It runs like an algorithm, so its swaps are
+/// traced and highlighted like any other. Given the same generator, it
+/// ends up in exactly the same order as a shuffle done all at once.
+/// Next is the column about to be swapped, and the columns after it
+/// have been shuffled already.
+#[derive(Clone)]
+pub struct Shuffle {
+    rng: StdRng,
+    next: Option<usize>,
+    started: bool,
+}
+
+impl Shuffle {
+    /// [New]
+    /// A shuffle drawing its swaps from rng.
+    pub fn new(rng: StdRng) -> Shuffle {
+        Shuffle { rng, next: None, started: false }
+    }
+}
+
+impl SortAlgorithm for Shuffle {
+    fn reset(&mut self) {
+        self.next = None;
+        self.started = false;
+    }
+
+    fn step(&mut self, columns: &mut Array, _direction: i32) -> Step {
+        if !self.started {
+            self.started = true;
+            self.next = columns.len().checked_sub(1).filter(|&i| i > 0);
+        }
+
+        let Some(i) = self.next else {
+            return Step::Done;
+        };
+        let j = self.rng.gen_range(0..=i);
+        columns.swap(i, j);
+        self.next = if i > 1 { Some(i - 1) } else { None };
+        Step::Swap(i, j)
+    }
+
+    fn markers(&self) -> Vec<Marker> {
+        match self.next {
+            Some(i) => vec![Marker::Range(0, i + 1)],
+            None => vec![],
+        }
+    }
+
+    fn status(&self) -> Option<String> {
+        Some("shuffling".to_string())
+    }
+}
+
+/// [Rank]
+/// Turns a shape into a permutation of 1 to n which rises and falls
+/// in the same places, with ties going to the leftmost column first.
+fn rank(shape: Vec<f64>) -> Vec<i32> {
+    let mut order: Vec<usize> = (0..shape.len()).collect();
+    order.sort_by(|&a, &b| shape[a].total_cmp(&shape[b]));
+    let mut columns = vec![0; shape.len()];
+    for (value, &i) in order.iter().enumerate() {
+        columns[i] = value as i32 + 1;
+    }
+    columns
+}
+
+/// [Scale]
+/// Stretches the heights to fill the values 1 to n, keeping any
+/// repeats which come from rounding.
+fn scale(heights: &[f64], n: usize) -> Vec<i32> {
+    let low = heights.iter().copied().fold(f64::INFINITY, f64::min);
+    let high = heights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = (high - low).max(f64::EPSILON);
+    heights.iter().map(|&height| 1 + ((height - low) / range * (n - 1) as f64).round() as i32).collect()
+}
+
+/// [Perlin]
+/// One dimensional gradient noise at x, given a random slope at each
+/// whole number. The noise is zero at every whole number and blends
+/// smoothly between the slopes on either side.
+fn perlin(gradients: &[f64], x: f64) -> f64 {
+    let cell = (x.floor() as usize).min(gradients.len() - 2);
+    let t = x - cell as f64;
+    let fade = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let (left, right) = (gradients[cell] * t, gradients[cell + 1] * (t - 1.0));
+    left + (right - left) * fade
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::algorithms::Trace;
+    use super::*;
+
+    #[test]
+    fn names_read_back() {
+        for input in INPUTS {
+            assert_eq!(Input::from_name(input.name()), Some(input));
+        }
+        assert_eq!(Input::from_name("sorted"), None);
+    }
+
+    #[test]
+    fn values_stay_between_one_and_n() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for input in INPUTS {
+            for n in [1, 2, 7, 100, 333] {
+                let columns = input.generate(n, &mut rng);
+                assert_eq!(columns.len(), n, "{}", input.name());
+                assert!(columns.iter().all(|&value| (1..=n as i32).contains(&value)), "{} on {}", input.name(), n);
+            }
+        }
+
+        // Only the ones which mean to repeat values do.
+        for input in [Input::Uniform, Input::Reversed, Input::NearlySorted, Input::RandomTail, Input::Sawtooth, Input::OrganPipe, Input::SqrtRuns, Input::Interleaved] {
+            let mut columns = input.generate(100, &mut rng);
+            columns.sort_unstable();
+            assert_eq!(columns, (1..=100).collect::<Vec<_>>(), "{}", input.name());
+        }
+    }
+
+    #[test]
+    fn shown_shuffles_match_uniform_input() {
+        for (seed, n) in [(1, 0), (2, 1), (3, 2), (4, 100), (5, 1000)] {
+            let mut columns: Vec<i32> = (1..=n).collect();
+            let mut shuffle = Shuffle::new(StdRng::seed_from_u64(seed));
+            let mut trace = Trace::default();
+            while trace.step(&mut shuffle, &mut columns, 1) != Step::Done {}
+            assert_eq!(columns, Input::Uniform.generate(n as usize, &mut StdRng::seed_from_u64(seed)));
+        }
+    }
+
+    #[test]
+    fn domains_stretch_to_both_ends() {
+        assert_eq!(Domain::parse(" 50 .. -50"), Some(Domain { min: -50, max: 50 }));
+        assert_eq!(Domain::parse("-50"), None);
+
+        let values: Vec<i32> = (1..=5).collect();
+        assert_eq!(Domain::new(-50, 50).stretch(&values, 5), [-50, -25, 0, 25, 50]);
+        let widest = Domain::new(i32::MIN, i32::MAX).stretch(&values, 5);
+        assert_eq!((widest[0], widest[4]), (i32::MIN, i32::MAX));
+        assert!(widest.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(Domain::new(3, 9).stretch(&[1], 1), [3]);
+    }
+}
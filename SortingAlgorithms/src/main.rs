@@ -29,15 +29,32 @@ extern crate rand;
 extern crate chrono;
 extern crate rayon;
 
+mod algorithms;
+mod canvas;
+mod export;
+mod font;
+mod input;
+mod midi;
+mod options;
+mod sound;
+
 // Import necessary functions from external libraries.
 use glutin_window::GlutinWindow as Window;
 use opengl_graphics::{GlGraphics, OpenGL};
 use piston::event_loop::{EventSettings, Events};
 use piston::input::{RenderArgs, RenderEvent, UpdateArgs, UpdateEvent};
-use piston::window::WindowSettings;
+use piston::window::{AdvancedWindow, WindowSettings};
 use piston::GenericEvent;
 use graphics::rectangle::centered;
-use rand::prelude::*;
+use algorithms::{Event, History, Marker, Network, Panel, Registry, SortAlgorithm, Step, Trace};
+use canvas::Canvas;
+use export::{Animation, Format};
+use input::{Domain, Input, Shuffle, INPUTS};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use midi::Score;
+use options::{Options, Pace};
+use sound::Sonifier;
 
 // All metrics pre-defined as constants
 // so that they can be used to define
@@ -47,22 +64,66 @@ const SCREEN_HEIGHT: f64 = 640.0;
 
 const NUM_COLS: i32 = 100;
 
+// The timeline scrubber runs along the bottom of the window.
+const TIMELINE_HEIGHT: f64 = 16.0;
+
+// The size of a pixel of the statistics text, and its distance from
+// the corner of the window.
+const HUD_SIZE: f64 = 2.0;
+const HUD_MARGIN: f64 = 8.0;
+
+// Where comparator networks are exported to and loaded from.
+const NETWORK_FILE: &str = "network.txt";
+
+// Where the trace of the sort so far is exported to.
+const TRACE_FILE: &str = "trace.txt";
+
+// How many operations a second are sounded and scored, unless the
+// video is paced by operations, when they keep time with it instead.
+const OPS_PER_SEC: u32 = 1000;
+
+// One colour for each worker of a parallel algorithm, repeating if
+// there are more workers than colours.
+const WORKER_COLOURS: [[f32; 4]; 8] = [
+    [0.9, 0.1, 0.1, 1.0],
+    [0.1, 0.5, 0.9, 1.0],
+    [0.1, 0.7, 0.2, 1.0],
+    [0.95, 0.6, 0.0, 1.0],
+    [0.6, 0.2, 0.8, 1.0],
+    [0.0, 0.7, 0.7, 1.0],
+    [0.9, 0.3, 0.6, 1.0],
+    [0.4, 0.3, 0.1, 1.0],
+];
+
 
 /// [App]
 /// The App struct defines the Piston application and associated
 /// data. All fields within this structure are statically accessible
 /// from within the application's associated methods.
 pub struct App { 
-    // OpenGL drawing backend.
-    gl: GlGraphics,
     paused: bool,
     do_tick: bool,
     columns: Vec<i32>, 
-    choice: i32,
-    pointer: usize,
-    bubble_completed: i32,
+    input: Input,
+    seed: u64,
+    shuffle: Option<Shuffle>,
+    show_shuffle: bool,
+    range: Option<Domain>,
+    prompt: Option<String>,
+    quiet: bool,
+    registry: Registry,
+    choice: usize,
+    algorithm: Box<dyn SortAlgorithm>,
+    trace: Trace,
+    history: History,
+    last_step: Step,
     num_cols: i32,
     direction: i32,
+    show_heap: bool,
+    confirmed: bool,
+    cursor: [f64; 2],
+    drawing_from: Option<usize>,
+    scrubbing: bool,
 }
 
 /// [App]
@@ -75,73 +136,269 @@ impl App {
     /// method is specifically meant to be where all calls to OpenGL
     /// happen, and is meant to be called every frame.
     ///
-    /// Being a Piston callback, its only parameters are itself,
-    /// and the Piston render arguments.
+    /// Being a Piston callback, its parameters are itself, the
+    /// Piston render arguments, and the OpenGL backend to draw with.
+    fn render(&mut self, args: &RenderArgs, gl: &mut GlGraphics) {
+        gl.draw(args.viewport(), |c, gl| self.draw(c, gl));
+    }
 
-    fn render(&mut self, args: &RenderArgs) {
+    /// [Draw]
+    ///
+    /// Draws everything with whichever graphics backend it is given,
+    /// which is OpenGL in the window, or the software canvas when
+    /// running headless.
+    fn draw<G: graphics::Graphics>(&self, c: graphics::Context, gl: &mut G) {
         use graphics::*;
 
-        // Variables for colouring:
-        let mut r: f32;
-        let mut g: f32;
-        let mut b: f32;
-        let mut diff_r: i32;
-        let mut diff_b: i32;
-        let mut column: [f32; 4];
         let background: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+        let strip_background: [f32; 4] = [0.92, 0.92, 0.92, 1.0];
+        let highlight: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+        let pivot: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+        let range_background: [f32; 4] = [1.0, 0.97, 0.85, 1.0];
+        let buffer_background: [f32; 4] = [0.85, 0.92, 1.0, 1.0];
+        let rotation_background: [f32; 4] = [1.0, 0.86, 0.7, 1.0];
+        let rotation: [f32; 4] = [0.95, 0.45, 0.0, 1.0];
+        let boundary: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+        let heap_link: [f32; 4] = [0.5, 0.5, 0.5, 0.6];
+        let active_link: [f32; 4] = [0.9, 0.1, 0.1, 1.0];
+        let histogram: [f32; 4] = [0.35, 0.45, 0.7, 1.0];
+        let timeline: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+        let hud_text: [f32; 4] = [0.15, 0.15, 0.15, 1.0];
 
-        // Variables for column position
-        let mut col_height: f64;
-        let col_width: f64 = (SCREEN_WIDTH / self.num_cols as f64) * 0.5;
-        let mut x: f64;
-        let mut y: f64;
-
-        // The following block of code will overwrite the OpenGL window with background colour.
-        self.gl.draw(args.viewport(), |c, gl| {
-            // Create the necessary components to draw with:
-            let background_fill =
-                rectangle::rectangle_by_corners(0.0, 0.0, SCREEN_WIDTH as f64, SCREEN_HEIGHT as f64);
-            let transform = c.transform;
-
-            // Collect all components and write to the screen.
-            rectangle(background, background_fill, transform, gl);
-        });
+        let num_cols = self.num_cols;
+        let domain = self.domain();
+        let columns = &self.columns;
+        let last_step = self.last_step;
+        let last_events = self.trace.last();
+        let panels = self.algorithm.panels();
+        let markers = self.running().markers();
+        let heap_links = if self.show_heap { self.algorithm.heap_links() } else { vec![] };
+        let beads = self.algorithm.beads();
+        let work = self.algorithm.work();
+        let drawing_from = self.drawing_from;
+        let (now, end) = (self.trace.steps(), self.history.end());
+        let hud = self.hud();
 
-        // Draw loop for the columns
-        for i in 0..self.num_cols {
+        // When the algorithm has panels, such as an auxiliary buffer,
+        // the columns move up to make room for a strip beneath them for
+        // each panel. The columns get twice the room of a strip. The
+        // timeline keeps the very bottom of the window to itself.
+        let bottom = SCREEN_HEIGHT - TIMELINE_HEIGHT;
+        let strip = bottom * 0.75 / (2 + panels.len()) as f64;
+        let base = bottom * 0.25 + strip * 2.0;
+        let height = if panels.is_empty() { bottom * 0.75 } else { strip * 2.0 - bottom * 0.02 };
 
-            // Handling Column Position
-            col_height = (self.columns[i as usize] as f64 / self.num_cols as f64) * (SCREEN_HEIGHT * 0.75) * 0.5;
+        // Overwrite the OpenGL window with background colour.
+        let background_fill =
+            rectangle::rectangle_by_corners(0.0, 0.0, SCREEN_WIDTH, SCREEN_HEIGHT);
+        rectangle(background, background_fill, c.transform, gl);
 
-            x = (i as f64 * col_width * 2.0) + col_width;
-            y = SCREEN_HEIGHT - col_height;
-    
-            let square = centered([x, y, col_width - 0.5, col_height]);
+        // Shade the range the algorithm is working on, along with
+        // any internal buffers and blocks being rotated.
+        for marker in &markers {
+            let (low, high, colour) = match *marker {
+                Marker::Range(low, high) => (low, high, range_background),
+                Marker::Buffer(low, high) => (low, high, buffer_background),
+                Marker::Rotation(low, high) => (low, high, rotation_background),
+                _ => continue,
+            };
+            let shade = rectangle::rectangle_by_corners(column_left(low, num_cols), base - height, column_left(high, num_cols), base);
+            rectangle(colour, shade, c.transform, gl);
+        }
 
-            // Handling Column Colour
-            if self.columns[i as usize] > self.num_cols / 2{
-                diff_r = 0;
-                diff_b = self.columns[i as usize] - self.num_cols / 2;
+        // Draw loop for the columns, where columns touched by the
+        // last step are drawn in black, or orange when the step was
+        // part of a rotation, and pivots in magenta. After a round
+        // of a parallel algorithm, each worker's columns are drawn
+        // in that worker's colour.
+        let mut touched = vec![false; columns.len()];
+        for (i, touch) in touched.iter_mut().enumerate() {
+            *touch = last_events.iter().any(|event| event.touches(i));
+        }
+        let mut workers = vec![None; columns.len()];
+        if last_step == Step::Round {
+            for w in &work {
+                workers[w.i] = Some(WORKER_COLOURS[w.worker % WORKER_COLOURS.len()]);
+                workers[w.j] = Some(WORKER_COLOURS[w.worker % WORKER_COLOURS.len()]);
+            }
+        }
+        let rotating = |i: usize| markers.iter().any(|marker| matches!(*marker, Marker::Rotation(low, high) if low <= i && i < high));
+        for (i, &value) in columns.iter().enumerate().filter(|_| beads.is_empty()) {
+            let colour = if let Some(colour) = workers[i] {
+                colour
+            } else if touched[i] && rotating(i) {
+                rotation
+            } else if touched[i] {
+                highlight
+            } else if markers.contains(&Marker::Pivot(i)) {
+                pivot
             } else {
-                diff_r = self.num_cols / 2 - self.columns[i as usize];
-                diff_b = 0;
+                column_colour(value, domain)
+            };
+            rectangle(colour, column_rect(i, value, num_cols, domain, base, height), c.transform, gl);
+        }
+
+        // Bead sort's beads take the place of the columns, each one
+        // drawn where the top unit of a column would be.
+        for bead in &beads {
+            let colour = if bead.falling { highlight } else { column_colour(bead.value, domain) };
+            let [x, y] = column_top(bead.position, bead.value, num_cols, domain, base, height);
+            let (width, depth) = (SCREEN_WIDTH / num_cols as f64, height / domain.levels());
+            ellipse(colour, [x - width * 0.45, y, width * 0.9, depth * 0.9], c.transform, gl);
+        }
+
+        // Partition boundaries are drawn as lines between columns.
+        for marker in &markers {
+            if let Marker::Boundary(i) = *marker {
+                let x = column_left(i, num_cols);
+                let line = rectangle::rectangle_by_corners(x - 1.0, base - height, x + 1.0, base);
+                rectangle(boundary, line, c.transform, gl);
             }
+        }
 
-            r = diff_r as f32 / (self.num_cols as f32 / 2.0);
-            if self.columns[i as usize] <= self.num_cols / 2 {g = self.columns[i as usize] as f32 / (self.num_cols as f32 / 2.0);} 
-            else {g = (self.num_cols - self.columns[i as usize]) as f32 / (self.num_cols as f32 / 2.0);} 
-            b = diff_b as f32 / (self.num_cols as f32 / 2.0);
+        // The implicit heap tree is drawn as links between the tops
+        // of the columns, with the sifted node's links on top.
+        for active in [false, true] {
+            for link in heap_links.iter().filter(|link| link.active == active) {
+                let (colour, radius) = if active { (active_link, 1.5) } else { (heap_link, 0.5) };
+                let from = column_top(link.parent, columns[link.parent], num_cols, domain, base, height);
+                let to = column_top(link.child, columns[link.child], num_cols, domain, base, height);
+                line_from_to(colour, radius, from, to, c.transform, gl);
+            }
+        }
+
+        for (n, panel) in panels.iter().enumerate() {
+            let strip_base = base + strip * (n + 1) as f64;
+            let strip_height = strip - bottom * 0.02;
+            let fill = rectangle::rectangle_by_corners(0.0, strip_base - strip_height, SCREEN_WIDTH, strip_base);
+            rectangle(strip_background, fill, c.transform, gl);
+
+            match *panel {
+                // Auxiliary buffers line up with the columns above.
+                Panel::Aux(aux) => {
+                    for (i, slot) in aux.iter().enumerate() {
+                        if let Some(value) = *slot {
+                            let written = last_events.iter().any(|event| matches!(*event, Event::AuxWrite(k, _) if k == i));
+                            let colour = if written { highlight } else { column_colour(value, domain) };
+                            rectangle(colour, column_rect(i, value, num_cols, domain, strip_base, strip_height), c.transform, gl);
+                        }
+                    }
+                }
+                // Histograms are scaled so the biggest count fills the
+                // strip, after squeezing them down to a bar a pixel.
+                Panel::Histogram(counts, active) => {
+                    let bars = histogram_bars(counts, SCREEN_WIDTH as usize);
+                    let active = active.map(|key| key * bars.len() / counts.len());
+                    let most = bars.iter().copied().max().unwrap_or(0).max(1);
+                    let width = SCREEN_WIDTH / bars.len().max(1) as f64;
+                    for (i, &count) in bars.iter().enumerate() {
+                        let colour = if active == Some(i) { highlight } else { histogram };
+                        let top = strip_base - strip_height * count as f64 / most as f64;
+                        let bar = rectangle::rectangle_by_corners(i as f64 * width + 0.5, top, (i + 1) as f64 * width - 0.5, strip_base);
+                        rectangle(colour, bar, c.transform, gl);
+                    }
+                }
+                // Trees get a row per level, with links down to each
+                // node's children.
+                Panel::Tree(nodes, active) => {
+                    let levels = (nodes.len() + 1).ilog2().max(1) as usize;
+                    let row = strip_height / levels as f64;
+                    let centre = |k: usize| {
+                        let level = (k + 1).ilog2() as usize;
+                        let width = SCREEN_WIDTH / (1 << level) as f64;
+                        [(k + 1 - (1 << level)) as f64 * width + width * 0.5, strip_base - strip_height + (level as f64 + 0.5) * row]
+                    };
 
-            column = [r, g, b, 1.0];
-            
-            // OpenGL is used for rendering it to the screen.
-            self.gl.draw(args.viewport(), |c, gl| {
+                    for k in 0..nodes.len() {
+                        for child in [2 * k + 1, 2 * k + 2].into_iter().filter(|&child| child < nodes.len()) {
+                            line_from_to(heap_link, 0.5, centre(k), centre(child), c.transform, gl);
+                        }
+                    }
+                    for (k, node) in nodes.iter().enumerate() {
+                        if let Some(value) = *node {
+                            let colour = if active == Some(k) { highlight } else { column_colour(value, domain) };
+                            let [x, y] = centre(k);
+                            let size = (SCREEN_WIDTH / (1 << (k + 1).ilog2()) as f64 * 0.8).min(14.0).min(row * 0.8);
+                            rectangle(colour, centered([x, y, size * 0.5, size * 0.5]), c.transform, gl);
+                        }
+                    }
+                }
+                // Piles stand in the slots of the columns, with each
+                // card's colour showing its value.
+                Panel::Piles(piles, active) => {
+                    let deepest = piles.iter().map(|pile| pile.len()).max().unwrap_or(0).max(1);
+                    let card = strip_height / deepest as f64;
+                    for (p, pile) in piles.iter().enumerate() {
+                        for (k, &value) in pile.iter().enumerate() {
+                            let top_card = active == Some(p) && k + 1 == pile.len();
+                            let colour = if top_card { highlight } else { column_colour(value, domain) };
+                            let bottom = strip_base - k as f64 * card;
+                            let rect = rectangle::rectangle_by_corners(column_left(p, num_cols) + 0.5, bottom - card, column_left(p + 1, num_cols) - 0.5, bottom);
+                            rectangle(colour, rect, c.transform, gl);
+                        }
+                    }
+                }
+                // Networks are drawn as Knuth's wire diagrams turned on
+                // their side, so that each wire hangs beneath its column
+                // and time runs down the strip. Comparators which would
+                // overlap get rows of their own within their layer.
+                Panel::Network(network, done) => {
+                    let mut rows: Vec<Vec<(usize, usize, usize)>> = vec![];
+                    let mut index = 0;
+                    for layer in network.layers() {
+                        let first = rows.len();
+                        for &(i, j) in layer {
+                            let row = match (first..rows.len()).find(|&r| rows[r].iter().all(|&(a, b, _)| b < i || j < a)) {
+                                Some(row) => row,
+                                None => {
+                                    rows.push(vec![]);
+                                    rows.len() - 1
+                                }
+                            };
+                            rows[row].push((i, j, index));
+                            index += 1;
+                        }
+                    }
+
+                    let slot = SCREEN_WIDTH / num_cols as f64;
+                    let wire = |w: usize| (w as f64 + 0.5) * slot;
+                    for w in 0..network.wires() {
+                        let colour = if drawing_from == Some(w) { active_link } else { heap_link };
+                        line_from_to(colour, 0.5, [wire(w), strip_base - strip_height], [wire(w), strip_base], c.transform, gl);
+                    }
+
+                    let row_height = strip_height / rows.len().max(1) as f64;
+                    let dot = (slot * 0.3).clamp(1.0, 3.0);
+                    for (r, row) in rows.iter().enumerate() {
+                        let y = strip_base - strip_height + (r as f64 + 0.5) * row_height;
+                        for &(i, j, index) in row {
+                            let colour = if index + 1 == done { active_link } else if index < done { heap_link } else { boundary };
+                            line_from_to(colour, 1.0, [wire(i), y], [wire(j), y], c.transform, gl);
+                            rectangle(colour, centered([wire(i), y, dot, dot]), c.transform, gl);
+                            rectangle(colour, centered([wire(j), y, dot, dot]), c.transform, gl);
+                        }
+                    }
+                }
+            }
+        }
 
-                let transform = c
-                    .transform;
+        // The timeline fills up to the current step, out of the
+        // furthest step the sort has reached.
+        rectangle(strip_background, [0.0, bottom, SCREEN_WIDTH, TIMELINE_HEIGHT], c.transform, gl);
+        let x = if end == 0 { 0.0 } else { SCREEN_WIDTH * now as f64 / end as f64 };
+        rectangle(timeline, [0.0, bottom + TIMELINE_HEIGHT * 0.25, x, TIMELINE_HEIGHT * 0.5], c.transform, gl);
+        rectangle(highlight, centered([x, bottom + TIMELINE_HEIGHT * 0.5, 2.0, TIMELINE_HEIGHT * 0.5]), c.transform, gl);
 
-                rectangle(column, square, transform, gl);
-            });
+        // The statistics sit in the top left corner, above where
+        // even the tallest column reaches, on a panel wide enough
+        // for the longest line.
+        let widest = hud.iter().map(|line| font::width(line, HUD_SIZE)).fold(0.0, f64::max);
+        let line_height = font::height(HUD_SIZE) + HUD_SIZE * 2.0;
+        let panel = [HUD_MARGIN * 0.5, HUD_MARGIN * 0.5, widest + HUD_MARGIN, hud.len() as f64 * line_height + HUD_MARGIN * 0.5];
+        rectangle(strip_background, panel, c.transform, gl);
+        for (n, line) in hud.iter().enumerate() {
+            let y = HUD_MARGIN + n as f64 * line_height;
+            font::text(hud_text, HUD_SIZE, line, [HUD_MARGIN, y], c.transform, gl);
         }
     }
 
@@ -152,24 +409,29 @@ impl App {
     /// the application logic (as opposed to rendering) using callbacks.
     /// The update method contains user-defined logic which does not
     /// necessarily have to do with drawing to OpenGL.
-    
     fn update(&mut self, _args: &UpdateArgs) {
-        if !self.paused || self.do_tick{
-            // Pick Sorting Algorithm
-
-            match self.choice.abs(){ 
-                0=>self.bubble_step(self.direction), 
-                1=>self.selection_step(), 
-                2=>self.insertion_step(), 
-                _=>println!("{}", self.choice),
+        // A shuffle being shown runs before the sort, and the sort
+        // starts over from wherever the shuffle left the columns.
+        if let Some(shuffle) = &mut self.shuffle {
+            if !self.paused || self.do_tick {
+                self.last_step = self.trace.step(shuffle, &mut self.columns, self.direction);
+                if self.last_step == Step::Done {
+                    self.restart();
+                }
+                self.do_tick = false;
             }
+            return;
+        }
 
-            // Call Sorting Algorithm
+        if self.blocked() || self.refusal().is_some() {
+            self.do_tick = false;
+            return;
+        }
 
-            if self.bubble_completed < (self.columns.len() - 2) as i32 && self.pointer == 0 {
-                self.bubble_completed += 1;
-            }
+        if !self.paused || self.do_tick{
+            // Call Sorting Algorithm
 
+            self.last_step = self.history.step(&mut self.algorithm, &mut self.columns, &mut self.trace, self.direction);
 
             // Done
 
@@ -185,115 +447,639 @@ impl App {
     /// and support for mouse interaction. Such input is necessary
     /// for clearing the board, regenerating the board, and drawing
     /// directly to the board.
-
     fn event<E: GenericEvent>(&mut self, e: &E) {
-        use piston::input::{Button, Key};
+        use piston::input::{Button, Key, MouseButton};
+
+        if let Some(cursor) = e.mouse_cursor_args() {
+            self.cursor = cursor;
+            if self.scrubbing {
+                self.scrub();
+            }
+        }
+
+        // Dragging along the timeline scrubs through the sort, and
+        // comparator networks are drawn with the mouse elsewhere, by
+        // clicking the two columns to join.
+        match e.press_args() {
+            Some(Button::Mouse(MouseButton::Left)) if self.cursor[1] >= SCREEN_HEIGHT - TIMELINE_HEIGHT => {
+                self.scrubbing = true;
+                self.scrub();
+            }
+            Some(Button::Mouse(MouseButton::Left)) => self.click_wire(),
+            Some(Button::Mouse(MouseButton::Right)) => self.edit_network(Network::pop),
+            _ => {}
+        }
+        if let Some(Button::Mouse(MouseButton::Left)) = e.release_args() {
+            self.scrubbing = false;
+        }
+
+        // While the seed prompt is open, typing goes into the prompt
+        // rather than working the usual keys.
+        if let Some(prompt) = &mut self.prompt {
+            if let Some(text) = e.text_args() {
+                prompt.extend(text.chars().filter(char::is_ascii_digit));
+            }
+            match e.press_args() {
+                Some(Button::Keyboard(Key::Backspace)) => {
+                    prompt.pop();
+                }
+                Some(Button::Keyboard(Key::Return)) => self.enter_seed(),
+                Some(Button::Keyboard(Key::S)) => self.prompt = None,
+                _ => {}
+            }
+            return;
+        }
 
         // Key Functions Added! (see readme)
         if let Some(Button::Keyboard(key)) = e.press_args() {
                 match key {
-                    Key::Space => {self.paused = !self.paused; if self.paused { println!("paused") } else { println!("playing") };},
+                    Key::Space => self.paused = !self.paused,
                     Key::W => self.do_tick = true,
-                    Key::R => self.randomize(),
-                    Key::Right => (self.choice, self.pointer, self.bubble_completed) = ((self.choice + 1) % 3, 0, 0),
-                    Key::Left => (self.choice, self.pointer, self.bubble_completed) = ((self.choice - 1) % 3, 0, 0),
-                    Key::Up => {self.direction = 1; (self.pointer, self.bubble_completed) = (0, 0)},
-                    Key::Down => {self.direction = -1; (self.pointer, self.bubble_completed) = (0, 0)},
-                    Key::NumPadPlus => {self.num_cols += 1; self.columns.push((self.columns.len() + 1) as i32);},
-                    Key::NumPadMinus => {let biggest = self.find_largest(); self.columns.remove(biggest); self.num_cols -= 1;},
+                    Key::B => self.seek(self.trace.steps().saturating_sub(1)),
+                    Key::Home => self.seek(0),
+                    Key::End => self.seek(self.history.end()),
+                    Key::R => self.generate_next(Input::Uniform),
+                    Key::D1 => self.generate_next(INPUTS[1]),
+                    Key::D2 => self.generate_next(INPUTS[2]),
+                    Key::D3 => self.generate_next(INPUTS[3]),
+                    Key::D4 => self.generate_next(INPUTS[4]),
+                    Key::D5 => self.generate_next(INPUTS[5]),
+                    Key::D6 => self.generate_next(INPUTS[6]),
+                    Key::D7 => self.generate_next(INPUTS[7]),
+                    Key::D8 => self.generate_next(INPUTS[8]),
+                    Key::D9 => self.generate_next(INPUTS[9]),
+                    Key::D0 => self.generate_next(INPUTS[10]),
+                    Key::O => self.next_option(),
+                    Key::H => self.show_heap = !self.show_heap,
+                    Key::S => self.prompt = Some(String::new()),
+                    Key::V => self.show_shuffle = !self.show_shuffle,
+                    Key::C => self.confirmed = true,
+                    Key::X => self.edit_network(|network| *network = Network::new(network.wires())),
+                    Key::E => self.export_network(),
+                    Key::L => self.load_network(),
+                    Key::T => self.export_trace(),
+                    Key::Right => self.select((self.choice + 1) % self.registry.count()),
+                    Key::Left => self.select((self.choice + self.registry.count() - 1) % self.registry.count()),
+                    Key::Up => {self.direction = 1; self.restart()},
+                    Key::Down => {self.direction = -1; self.restart()},
+                    Key::NumPadPlus => self.resize(self.num_cols + 1),
+                    Key::NumPadMinus => self.resize(self.num_cols - 1),
                     _ => {}
             }
         }
     }
 
 
-    /// [Randomize]
-    /// 
-    /// This method is called any time we want to randomize the array,
+    /// [Generate]
+    ///
+    /// This method is called any time we want new columns to sort,
     /// since that is the best way to see sorting algorithms in action.
-    /// 
-    /// Randomization is done using the thread_rng random method, which
-    /// generates a float from 0-1. Then, based on that float we select
-    /// an index in columns, remove that value there and put it in a temp
-    /// vector, then repeat until our columns is empty. Our temp vector
-    /// is now randomized, so we copy it to the columns vector and we're
-    /// done.
+    /// The columns are made by the given input distribution from the
+    /// current seed, then stretched onto the domain, so the seed and
+    /// input shown along with the statistics are all it takes to get
+    /// the same columns again.
+    ///
+    /// A uniform shuffle can be shown one swap at a time before the
+    /// sort begins, which ends with exactly the same columns.
+    fn generate(&mut self, input: Input) {
+        self.input = input;
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let num_cols = self.num_cols as usize;
+        let showing = input == Input::Uniform && self.show_shuffle;
+        let values = if showing { (1..=num_cols as i32).collect() } else { input.generate(num_cols, &mut rng) };
+        self.columns = self.domain().stretch(&values, num_cols);
+        self.restart();
+        if showing {
+            self.shuffle = Some(Shuffle::new(rng));
+        }
+        if !self.quiet {
+            println!("{} input, seed {}", input.name(), self.seed);
+        }
+    }
 
-    fn randomize(&mut self) {
-        (self.pointer, self.bubble_completed) = (0, 0);
+    /// [Generate Next]
+    ///
+    /// Moves on to the next seed and generates new columns from it.
+    /// Each seed is drawn from the one before, so a whole session of
+    /// shuffles can be repeated by starting from the same seed.
+    fn generate_next(&mut self, input: Input) {
+        self.seed = StdRng::seed_from_u64(self.seed).gen();
+        self.generate(input);
+    }
 
-        let mut temp:Vec<i32> = vec![];
-        let mut length;
-        let mut rng = rand::thread_rng();
+    /// [Enter Seed]
+    ///
+    /// Closes the seed prompt, and generates the columns again from
+    /// the seed typed into it, if there was one.
+    fn enter_seed(&mut self) {
+        let Some(seed) = self.prompt.take().and_then(|prompt| prompt.parse().ok()) else {
+            return;
+        };
+        self.seed = seed;
+        self.generate(self.input);
+    }
+
+    /// [Select]
+    ///
+    /// Swaps the running algorithm for the one at the given index in
+    /// the registry. The new algorithm starts from scratch on whatever
+    /// the columns currently look like.
+    fn select(&mut self, choice: usize) {
+        self.choice = choice;
+        self.algorithm = self.registry.create(choice);
+        self.restart();
+        self.confirmed = false;
+        self.drawing_from = None;
+    }
 
-        for _i in 0..self.num_cols{
-            length = self.columns.len();
-            let rand: f64 = rng.gen();
-            let index = (rand * length as f64) as usize;
-            temp.push(self.columns.swap_remove(index));
+    /// [Seek]
+    ///
+    /// Pauses the sort and winds it backwards or forwards to just after
+    /// the given step.
+    fn seek(&mut self, step: usize) {
+        self.paused = true;
+        if self.shuffle.is_some() {
+            return;
+        }
+        if let Some(last_step) = self.history.seek(step, &mut self.algorithm, &mut self.columns, &mut self.trace, self.direction) {
+            self.last_step = last_step;
         }
+    }
+
+    /// [Scrub]
+    ///
+    /// Seeks to the step under the mouse on the timeline.
+    fn scrub(&mut self) {
+        let fraction = (self.cursor[0] / SCREEN_WIDTH).clamp(0.0, 1.0);
+        self.seek((fraction * self.history.end() as f64).round() as usize);
+    }
+
+    /// [Resize]
+    ///
+    /// Changes the number of columns, generating them all again from
+    /// the same input and seed.
+    fn resize(&mut self, num_cols: i32) {
+        self.num_cols = num_cols.max(1);
+        self.generate(self.input);
+    }
+
+    /// [Click Wire]
+    ///
+    /// Picks the column under the mouse as one end of a comparator, if
+    /// the running algorithm is a comparator network. Once both ends
+    /// have been picked, the comparator is added to the network.
+    fn click_wire(&mut self) {
+        if self.algorithm.network().is_none() {
+            return;
+        }
+
+        let wire = ((self.cursor[0] / SCREEN_WIDTH * self.num_cols as f64) as usize).min(self.columns.len() - 1);
+        match self.drawing_from.take() {
+            None => self.drawing_from = Some(wire),
+            Some(from) => self.edit_network(|network| network.push(from, wire)),
+        }
+    }
+
+    /// [Edit Network]
+    ///
+    /// Makes a change to the running comparator network, which then
+    /// starts again from scratch. A network built for a different
+    /// number of columns is replaced by an empty one first.
+    fn edit_network(&mut self, edit: impl FnOnce(&mut Network)) {
+        let Some(network) = self.algorithm.network() else {
+            return;
+        };
+
+        let mut network = if network.wires() == self.columns.len() { network.clone() } else { Network::new(self.columns.len()) };
+        edit(&mut network);
+        self.algorithm.set_network(network);
+        self.restart();
+    }
+
+    /// [Export Network]
+    ///
+    /// Saves the running comparator network to the network file.
+    fn export_network(&self) {
+        if let Some(network) = self.algorithm.network() {
+            match std::fs::write(NETWORK_FILE, network.text()) {
+                Ok(()) => println!("saved network to {}", NETWORK_FILE),
+                Err(error) => println!("couldn't save network: {}", error),
+            }
+        }
+    }
+
+    /// [Load Network]
+    ///
+    /// Loads a comparator network from the network file, and sets the
+    /// number of columns to match it.
+    fn load_network(&mut self) {
+        if self.algorithm.network().is_none() {
+            return;
+        }
+
+        let loaded = std::fs::read_to_string(NETWORK_FILE).map_err(|error| error.to_string()).and_then(|text| Network::parse(&text));
+        match loaded {
+            Ok(network) => {
+                println!("loaded network from {}", NETWORK_FILE);
+                self.resize(network.wires().max(1) as i32);
+                self.algorithm.set_network(network);
+                self.restart();
+            }
+            Err(error) => println!("couldn't load network: {}", error),
+        }
+    }
+
+    /// [Export Trace]
+    ///
+    /// Saves every operation the running algorithm has performed since
+    /// it started to the trace file.
+    fn export_trace(&self) {
+        match std::fs::write(TRACE_FILE, self.trace.text()) {
+            Ok(()) => println!("saved {} steps to {}", self.trace.steps(), TRACE_FILE),
+            Err(error) => println!("couldn't save trace: {}", error),
+        }
+    }
+
+    /// [Next Option]
+    ///
+    /// Cycles the setting of the running algorithm, if it has one,
+    /// such as the pivot rule of a quicksort.
+    fn next_option(&mut self) {
+        self.algorithm.next_option();
+        self.history.branch(self.trace.steps());
+        if let Some(option) = self.algorithm.option() {
+            println!("{}", option);
+        }
+    }
+
+    /// [Blocked]
+    ///
+    /// True while the running algorithm refuses to run because there
+    /// are more columns than it can sort in any reasonable time, and
+    /// it hasn't been confirmed with C yet.
+    fn blocked(&self) -> bool {
+        !self.confirmed && self.algorithm.limit().is_some_and(|limit| self.columns.len() > limit)
+    }
+
+    /// [Refusal]
+    ///
+    /// Why the running algorithm won't sort the columns at all, if it
+    /// won't, such as counting sort when the values span too wide a
+    /// range to count. There is no running it anyway.
+    fn refusal(&self) -> Option<String> {
+        self.algorithm.refusal(&self.columns)
+    }
+
+    /// [Restart]
+    ///
+    /// Throws away the progress of the running algorithm, along with
+    /// its trace, and any shuffle being shown. This has to happen
+    /// whenever the columns or the direction change underneath it. The
+    /// algorithm's own random choices start over from the seed too.
+    fn restart(&mut self) {
+        self.shuffle = None;
+        self.algorithm.reset();
+        self.algorithm.seed(self.seed);
+        self.trace.start(&self.columns);
+        self.history.start(self.algorithm.as_ref(), &self.columns);
+        self.last_step = Step::Done;
+    }
 
-        self.columns = temp;
+    /// [Domain]
+    ///
+    /// The range of values the columns are drawn against, which is the
+    /// range asked for on the command line, or else 1 up to the number
+    /// of columns, which every input distribution fills.
+    fn domain(&self) -> Domain {
+        self.range.unwrap_or(Domain::new(1, self.num_cols))
+    }
+
+    /// [Running]
+    ///
+    /// Whatever is stepping through the columns right now, which is
+    /// the shuffle while one is being shown, and the sort otherwise.
+    fn running(&self) -> &dyn SortAlgorithm {
+        match &self.shuffle {
+            Some(shuffle) => shuffle,
+            None => self.algorithm.as_ref(),
+        }
     }
 
-    /// [Bubble Step]
-    /// 
-    /// This method performs exactly one step of a bubble sort algorithm.
-    /// Doing it one step at a time is nice for visualizing exactly what
-    /// is going on, and so I opted to do it this way. 
-    /// 
-    /// We keep track of the current progress using the pointer and 
-    /// bubble_complete variables which belong to the application. Once
-    /// a pass is completed, we reset the pointer and start anew.
-    /// 
-    /// The direction parameter controls the order that we sort it in.
-    /// When set to +1 it will sort in ascending, and when set to -1 it
-    /// will be descending.
+    /// [Title]
+    ///
+    /// The window title, naming the running algorithm along with its
+    /// setting and status, such as the gap a Shell sort is using.
+    fn title(&self) -> String {
+        let mut title = format!("Sorting Algorithms - {}", self.registry.name(self.choice));
+
+        for extra in [self.algorithm.option(), self.algorithm.status()].into_iter().flatten() {
+            title.push_str(" - ");
+            title.push_str(&extra);
+        }
 
-    fn bubble_step(&mut self, direction: i32) {
-        let i = self.pointer;
-        let j = i + 1;
+        if let (true, Some(limit)) = (self.blocked(), self.algorithm.limit()) {
+            title.push_str(&format!(" - more than {} columns, press C to run anyway", limit));
+        }
 
-        if direction * self.columns[i] > direction * self.columns[j] {
-            let temp = self.columns[i];
-            self.columns[i] = self.columns[j];
-            self.columns[j] = temp;
+        if let Some(refusal) = self.refusal() {
+            title.push_str(&format!(" - {}, so it won't run", refusal));
         }
 
-        if self.pointer < self.columns.len() - 2 - self.bubble_completed as usize {
-            self.pointer += 1;
-        } else {
-            self.pointer = 0;
+        title
+    }
+
+    /// [HUD]
+    ///
+    /// The lines of statistics drawn over the window, describing the
+    /// sort and counting the operations it has performed so far.
+    fn hud(&self) -> Vec<String> {
+        let stats = self.trace.stats();
+        let direction = if self.direction > 0 { "ascending" } else { "descending" };
+        let mut step = format!("step {}/{}", self.trace.steps(), self.history.end());
+        if self.paused {
+            step.push_str(" (paused)");
         }
+
+        let mut lines = vec![self.registry.name(self.choice).to_string()];
+        lines.extend(self.algorithm.option());
+        lines.extend(self.algorithm.status());
+        let domain = self.domain();
+        lines.push(format!("{}, N = {}, values {} to {}", direction, self.columns.len(), domain.min, domain.max));
+        lines.push(match (&self.prompt, &self.shuffle) {
+            (Some(prompt), _) => format!("seed: {}_ (enter to use, S to cancel)", prompt),
+            (None, Some(_)) => format!("{} input, seed {}, shuffling", self.input.name(), self.seed),
+            (None, None) => format!("{} input, seed {}", self.input.name(), self.seed),
+        });
+        lines.push(format!("comparisons {}, swaps {}", stats.compares, stats.swaps));
+        lines.push(format!("reads {}, writes {}", stats.reads, stats.writes));
+        lines.push(format!("peak aux memory {}", self.trace.peak_memory()));
+        lines.push(step);
+        lines
+    }
+}
+
+/// [Column Rect]
+///
+/// Works out where the bar for a value belongs. Bars stand on the
+/// base line, and the largest value of the domain reaches the full
+/// height.
+fn column_rect(i: usize, value: i32, num_cols: i32, domain: Domain, base: f64, height: f64) -> [f64; 4] {
+    let col_width: f64 = (SCREEN_WIDTH / num_cols as f64) * 0.5;
+    let col_height = domain.fraction(value) * height * 0.5;
+
+    let x = (i as f64 * col_width * 2.0) + col_width;
+    let y = base - col_height;
+
+    centered([x, y, col_width - 0.5, col_height])
+}
+
+/// [Column Top]
+///
+/// The point at the middle of the top of the bar for a value.
+fn column_top(i: usize, value: i32, num_cols: i32, domain: Domain, base: f64, height: f64) -> [f64; 2] {
+    let slot = SCREEN_WIDTH / num_cols as f64;
+    [(i as f64 + 0.5) * slot, base - domain.fraction(value) * height]
+}
+
+/// [Column Left]
+///
+/// The x coordinate of the left edge of the slot for column i.
+fn column_left(i: usize, num_cols: i32) -> f64 {
+    i as f64 * SCREEN_WIDTH / num_cols as f64
+}
+
+/// [Histogram Bars]
+///
+/// A histogram squeezed down to at most the given number of bars, for
+/// when there are more keys than pixels to draw them in. Each bar is
+/// the total count of the neighbouring keys it covers.
+fn histogram_bars(counts: &[usize], most: usize) -> Vec<usize> {
+    if counts.len() <= most {
+        return counts.to_vec();
     }
+    let mut bars = vec![0; most];
+    for (key, &count) in counts.iter().enumerate() {
+        bars[key * most / counts.len()] += count;
+    }
+    bars
+}
+
+/// [Column Colour]
+///
+/// Columns fade from red for the smallest values, through green in
+/// the middle, to blue for the largest values of the domain.
+fn column_colour(value: i32, domain: Domain) -> [f32; 4] {
+    let x = domain.fraction(value) as f32 * 2.0;
+
+    let r = (1.0 - x).max(0.0);
+    let g = if x <= 1.0 { x } else { 2.0 - x };
+    let b = (x - 1.0).max(0.0);
 
-    fn selection_step(&mut self) {
+    [r, g, b, 1.0]
+}
 
+/// [Run Offscreen]
+///
+/// Runs the sort to the end without a window, including any shuffle
+/// shown first, handing a frame to the given function as often as the
+/// pace asks for, along with the very last one, which is told that it
+/// is the last. Going by operations rather than steps keeps the video
+/// moving at an even speed through algorithms whose steps do very
+/// different amounts of work. Every frame then stands for exactly the
+/// same number of operations, so a step doing the work of several
+/// frames is shown for all of them, and whatever is left over counts
+/// towards the next frame. Returns the number of frames handed over.
+fn run_offscreen(app: &mut App, pace: Pace, mut frame: impl FnMut(&App, bool) -> Result<(), String>) -> Result<usize, String> {
+    if let (true, Some(limit)) = (app.blocked(), app.algorithm.limit()) {
+        return Err(format!("{} won't run on more than {} columns", app.registry.name(app.choice), limit));
     }
 
-    fn insertion_step(&mut self) {
+    let mut frames = 0;
+    let mut pending = 0;
+    for step in 0.. {
+        let shuffling = app.shuffle.is_some();
+        if step > 0 {
+            let before = app.trace.stats().total();
+            app.update(&UpdateArgs { dt: 0.0 });
+            // The trace starts again when a shown shuffle hands over to
+            // the sort, so a smaller total is all new operations.
+            let after = app.trace.stats().total();
+            pending += if after >= before { after - before } else { after };
+        }
 
+        let done = step > 0 && !shuffling && app.last_step == Step::Done;
+        let due = match pace {
+            Pace::Steps(every) => usize::from(step % every == 0),
+            Pace::Operations(_) if step == 0 => 1,
+            Pace::Operations(operations) => {
+                let due = pending / operations;
+                pending %= operations;
+                due
+            }
+        };
+        let repeats = if done { due.max(1) } else { due };
+        for repeat in 1..=repeats {
+            frame(app, done && repeat == repeats)?;
+            frames += 1;
+        }
+        if done {
+            break;
+        }
     }
+    Ok(frames)
+}
 
-    /// [Find Largest]
-    /// 
-    /// Finds the index of the largest element in the vector, because
-    /// apparently that's not an integrated feature of vectors already
-    /// in the standard library.
+/// [Headless]
+///
+/// Runs the sort to the end without a window, saving the frames as
+/// numbered PNGs, and returns how many were saved.
+fn headless(app: &mut App, options: &Options) -> Result<usize, String> {
+    std::fs::create_dir_all(&options.frames).map_err(|error| error.to_string())?;
 
-    fn find_largest(&mut self) -> usize {
-        let mut max: usize = 0; 
+    let [width, height] = options.size.unwrap_or([SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32]);
+    let mut canvas = Canvas::new(width, height);
+    let mut saved = 0;
+    run_offscreen(app, options.pace, |app, _| {
+        app.draw(canvas.context(SCREEN_WIDTH, SCREEN_HEIGHT), &mut canvas);
+        canvas.save_png(&format!("{}/frame_{:05}.png", options.frames, saved))?;
+        saved += 1;
+        Ok(())
+    })
+}
+
+/// [Export]
+///
+/// Runs the sort to the end without a window and saves it as an
+/// animation or a video, holding on the sorted columns at the end.
+/// Animated PNGs need the number of frames before the first one, so
+/// for those the sort is run once without drawing to count them, then
+/// wound back to the start and run again for real. Returns the number
+/// of frames drawn.
+fn export(app: &mut App, options: &Options, path: &str) -> Result<usize, String> {
+    let mut frames = 0;
+    if Format::of(path)? == Format::Apng {
+        frames = rehearse(app, |app| run_offscreen(app, options.pace, |_, _| Ok(())))?;
+    }
 
-        for i in 1..self.num_cols {
-            if self.columns[max] < self.columns[i as usize] {
-                max = i as usize;
+    let [width, height] = options.size.unwrap_or([SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32]);
+    let mut canvas = Canvas::new(width, height);
+    let mut animation = Animation::create(path, width, height, frames as u32, options)?;
+    let frames = run_offscreen(app, options.pace, |app, last| {
+        app.draw(canvas.context(SCREEN_WIDTH, SCREEN_HEIGHT), &mut canvas);
+        animation.frame(&canvas, last)
+    })?;
+    animation.finish()?;
+    Ok(frames)
+}
+
+/// [Replay]
+///
+/// Runs the sort to the end without a window, handing every operation
+/// to the given function along with the columns as they were just
+/// before it. The columns are kept up to date alongside the trace, as
+/// the app's own columns have already moved on by the end of a step.
+/// Stops at the first error the function returns, and otherwise
+/// returns the number of operations handed over.
+fn replay(app: &mut App, mut operation: impl FnMut(&Event, &[i32], Domain) -> Result<(), String>) -> Result<usize, String> {
+    let mut columns = app.columns.clone();
+    let mut total = 0;
+    let mut operations = 0;
+    run_offscreen(app, Pace::Steps(1), |app, _| {
+        // Each update takes at most one step, so anything new is in the
+        // last step, and a smaller total means the trace started over.
+        let after = app.trace.stats().total();
+        let new = if after >= total { after - total } else { after };
+        total = after;
+        let events = app.trace.last();
+        for event in &events[events.len() - new.min(events.len())..] {
+            operation(event, &columns, app.domain())?;
+            match *event {
+                Event::Swap(i, j) => columns.swap(i, j),
+                Event::Write(i, value) => columns[i] = value,
+                _ => {}
             }
+            operations += 1;
         }
-        
-        return max
+        Ok(())
+    })?;
+    Ok(operations)
+}
+
+/// [Operations Per Second]
+///
+/// How many operations a second the sound and the score play at.
+fn operations_per_second(options: &Options) -> u32 {
+    match (options.ops_per_sec, options.pace) {
+        (Some(operations), _) => operations,
+        (None, Pace::Operations(operations)) => (operations as u32).saturating_mul(options.fps),
+        (None, Pace::Steps(_)) => OPS_PER_SEC,
     }
 }
 
+/// [Sonify]
+///
+/// Runs the sort to the end without a window and saves the sound of
+/// it as a WAV file, where each operation sounds the values it touched.
+/// Returns the number of operations sounded.
+fn sonify(app: &mut App, options: &Options, path: &str) -> Result<usize, String> {
+    let mut sonifier = Sonifier::create(path, options, operations_per_second(options))?;
+    let operations = replay(app, |event, columns, domain| sonifier.operation(event, columns, domain))?;
+    sonifier.finish()?;
+    Ok(operations)
+}
+
+/// [Score]
+///
+/// Runs the sort to the end without a window and saves it as a MIDI
+/// file, where each operation plays the values it touched as notes.
+/// Returns the number of operations scored.
+fn score(app: &mut App, options: &Options, path: &str) -> Result<usize, String> {
+    let mut score = Score::new(options, operations_per_second(options));
+    let operations = replay(app, |event, columns, domain| {
+        score.operation(event, columns, domain);
+        Ok(())
+    })?;
+    score.save_midi(path)?;
+    Ok(operations)
+}
+
+/// [Rehearse]
+///
+/// Runs f on the app, then winds the app back to the columns and the
+/// shuffle it started with, so that the same sort can be run again.
+fn rehearse<T>(app: &mut App, f: impl FnOnce(&mut App) -> T) -> T {
+    let (columns, shuffle) = (app.columns.clone(), app.shuffle.clone());
+    let result = f(app);
+    app.columns = columns;
+    app.restart();
+    app.shuffle = shuffle;
+    result
+}
+
+/// [Offscreen]
+///
+/// Does everything asked for which happens without a window, first
+/// saving the sound and the score, then the frames or the animation,
+/// each from the start of the same sort. Returns a message for each
+/// file saved.
+fn offscreen(app: &mut App, options: &Options) -> Result<Vec<String>, String> {
+    let mut messages = vec![];
+    if let Some(path) = &options.sound {
+        let operations = rehearse(app, |app| sonify(app, options, path))?;
+        messages.push(format!("saved {} operations of sound to {}", operations, path));
+    }
+    if let Some(path) = &options.midi {
+        let operations = rehearse(app, |app| score(app, options, path))?;
+        messages.push(format!("saved {} operations as notes to {}", operations, path));
+    }
+    match (&options.export, options.headless) {
+        (Some(path), _) => messages.push(format!("saved {} frames to {}", export(app, options, path)?, path)),
+        (None, true) => messages.push(format!("saved {} frames to {}", headless(app, options)?, options.frames)),
+        (None, false) => {}
+    }
+    Ok(messages)
+}
+
 /// [Main]
 ///
 /// Note: Most of this main method comes from a Piston tutorial.
@@ -301,45 +1087,122 @@ impl App {
 ///
 /// This method sets up the application state, and initializes the OpenGL backend for
 /// execution by Piston.
-
 fn main() {
-    // Change this to OpenGL::V2_1 if not working.
-    let opengl = OpenGL::V3_2;
-
-    // Create a Glutin window.
-    let mut window: Window = WindowSettings::new("Sorting Algorithms", [SCREEN_WIDTH, SCREEN_HEIGHT])
-        .graphics_api(opengl)
-        .exit_on_esc(true)
-        .build()
-        .unwrap();
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            println!("{}", error);
+            std::process::exit(1);
+        }
+    };
 
+    let num_cols = options.columns.unwrap_or(NUM_COLS);
     let mut columns = vec![];
 
-    for i in 1..NUM_COLS + 1 {
+    for i in 1..num_cols + 1 {
         columns.push(i);
     }
 
     // Create a new simulation, and run it
+    let registry = algorithms::registry();
+    let choice = match &options.algorithm {
+        Some(name) => registry.find(name).unwrap_or_else(|| {
+            println!("unknown algorithm {}", name);
+            std::process::exit(1);
+        }),
+        None => 0,
+    };
+    let algorithm = registry.create(choice);
+    let mut trace = Trace::default();
+    trace.start(&columns);
+    let mut history = History::default();
+    history.start(algorithm.as_ref(), &columns);
+
     let mut app = App {
-        gl: GlGraphics::new(opengl),
         paused: false,
         do_tick: false,
-        columns: columns,
-        choice: 0,
-        pointer: 0,
-        bubble_completed: 0,
-        num_cols: NUM_COLS,
+        columns,
+        input: options.input.unwrap_or(Input::Uniform),
+        seed: options.seed.unwrap_or_else(rand::random),
+        shuffle: None,
+        show_shuffle: options.show_shuffle,
+        range: options.range,
+        prompt: None,
+        quiet: options.export.as_deref() == Some("-"),
+        registry,
+        choice,
+        algorithm,
+        trace,
+        history,
+        last_step: Step::Done,
+        num_cols,
         direction: 1,
+        show_heap: true,
+        confirmed: false,
+        cursor: [0.0, 0.0],
+        drawing_from: None,
+        scrubbing: false,
     };
 
+    // The first algorithm is seeded like any other, even if the
+    // columns are left as they are.
+    app.restart();
+
+    // The columns start out sorted, unless an input, a seed or a range
+    // of values to make them from was asked for.
+    if options.input.is_some() || options.seed.is_some() || options.range.is_some() {
+        app.generate(app.input);
+    }
+
+    // Sound, scores, exporting and running headless all happen without
+    // a window.
+    // A video written to standard output would be spoilt by messages
+    // printed along with it, so those go to standard error instead.
+    if options.sound.is_some() || options.midi.is_some() || options.export.is_some() || options.headless {
+        match offscreen(&mut app, &options) {
+            Ok(messages) => {
+                for message in messages {
+                    if app.quiet {
+                        eprintln!("{}", message);
+                    } else {
+                        println!("{}", message);
+                    }
+                }
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // Change this to OpenGL::V2_1 if not working.
+    let opengl = OpenGL::V3_2;
+
+    // Create a Glutin window.
+    let mut window: Window = WindowSettings::new("Sorting Algorithms", [SCREEN_WIDTH, SCREEN_HEIGHT])
+        .graphics_api(opengl)
+        .exit_on_esc(true)
+        .build()
+        .unwrap();
+    let mut gl = GlGraphics::new(opengl);
+
     // The main piston loop, which actually runs all the app
     // functions repeatedly
     let mut events = Events::new(EventSettings::new());
+    let mut title = String::new();
     while let Some(e) = events.next(&mut window) {
         app.event(&e);
 
+        // Only touch the window title when it actually changes.
+        if app.title() != title {
+            title = app.title();
+            window.set_title(title.clone());
+        }
+
         if let Some(args) = e.render_args() {
-            app.render(&args);
+            app.render(&args, &mut gl);
         }
 
         if let Some(args) = e.update_args() {
@@ -347,3 +1210,17 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histograms_squeeze_into_bars() {
+        assert_eq!(histogram_bars(&[1, 2, 3], 4), [1, 2, 3]);
+        assert_eq!(histogram_bars(&[1, 2, 3, 4, 5, 6], 3), [3, 7, 11]);
+        let bars = histogram_bars(&vec![1; 1 << 20], 1280);
+        assert_eq!(bars.len(), 1280);
+        assert_eq!(bars.iter().sum::<usize>(), 1 << 20);
+    }
+}
@@ -0,0 +1,251 @@
+/*****************************************************************/
+//! [MIDI Export]
+/*****************************************************************/
+//!
+//! Writes a sort out as a Standard MIDI File, so that it can be opened
+//! in a sequencer and arranged like any other music. Operations are
+//! laid out one sixteenth note apart, with the tempo set so that they
+//! go by at the same rate as in the sound, and every value an
+//! operation touches becomes a note lasting until the next operation.
+//! The tempo is taken from the operation rate rather than the step
+//! rate, since a step can be anything from one compare to a whole
+//! round of a parallel sort, and the rhythm would be lost.
+//!
+//! Values are spread over the same pitch range as the sound, then
+//! moved to the nearest note of the chosen scale, all in C. Compares,
+//! swaps, and reads and writes each get a channel of their own, so
+//! that they can be given different instruments.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::algorithms::Event;
+use crate::input::Domain;
+use crate::options::Options;
+
+// Ticks to a quarter note, with four operations to a quarter note.
+const DIVISION: u16 = 96;
+const OPERATION: u64 = DIVISION as u64 / 4;
+
+// The longest time between two messages a MIDI file can write, in
+// ticks, as times are at most four bytes of seven bits.
+const MOST_TICKS: u64 = 0x0FFF_FFFF;
+
+// The channels operations are played on, counting from zero, and how
+// hard their notes are struck.
+const COMPARE_CHANNEL: u8 = 0;
+const SWAP_CHANNEL: u8 = 1;
+const ACCESS_CHANNEL: u8 = 2;
+const COMPARE_VELOCITY: u8 = 80;
+const SWAP_VELOCITY: u8 = 100;
+const ACCESS_VELOCITY: u8 = 64;
+
+/// [Scale]
+/// The notes values are moved to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scale {
+    /// Every note, so values are only rounded to the nearest.
+    Chromatic,
+    Major,
+    Minor,
+    Pentatonic,
+    Blues,
+    WholeTone,
+}
+
+impl Scale {
+    /// [From Name]
+    /// The scale with the given name, if there is one.
+    pub fn from_name(name: &str) -> Option<Scale> {
+        match name {
+            "chromatic" => Some(Scale::Chromatic),
+            "major" => Some(Scale::Major),
+            "minor" => Some(Scale::Minor),
+            "pentatonic" => Some(Scale::Pentatonic),
+            "blues" => Some(Scale::Blues),
+            "whole-tone" => Some(Scale::WholeTone),
+            _ => None,
+        }
+    }
+
+    /// [Degrees]
+    /// The notes of the scale, in semitones above C.
+    fn degrees(self) -> &'static [u8] {
+        match self {
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::Pentatonic => &[0, 2, 4, 7, 9],
+            Scale::Blues => &[0, 3, 5, 6, 7, 10],
+            Scale::WholeTone => &[0, 2, 4, 6, 8, 10],
+        }
+    }
+
+    /// [Nearest]
+    /// The note of the scale nearest to a note, going down on a tie.
+    fn nearest(self, note: f64) -> u8 {
+        let note = note.round().clamp(0.0, 127.0) as i32;
+        let in_scale = |note: i32| (0..=127).contains(&note) && self.degrees().contains(&((note % 12) as u8));
+        (0..12).flat_map(|distance| [note - distance, note + distance]).find(|&note| in_scale(note)).unwrap_or(note) as u8
+    }
+}
+
+/// [Score]
+/// The notes of a sort, built up an operation at a time. Each note is
+/// kept as the tick it starts on, its channel, its note number and its
+/// velocity. Ticks are counted in u64, which no sort will run out of.
+pub struct Score {
+    scale: Scale,
+    notes: [f64; 2],
+    operations_per_second: f64,
+    operations: u64,
+    played: Vec<(u64, u8, u8, u8)>,
+}
+
+impl Score {
+    /// [New]
+    /// An empty score using the pitch range and the scale in the options,
+    /// which lays out operations at the given rate.
+    pub fn new(options: &Options, operations_per_second: u32) -> Score {
+        Score {
+            scale: options.scale,
+            notes: options.pitch.map(|frequency| 69.0 + 12.0 * (frequency as f64 / 440.0).log2()),
+            operations_per_second: operations_per_second as f64,
+            operations: 0,
+            played: vec![],
+        }
+    }
+
+    /// [Operation]
+    /// Adds the next operation, given the columns as they were just
+    /// before it, playing a note for each value it touches. A write
+    /// plays the value written.
+    pub fn operation(&mut self, event: &Event, columns: &[i32], domain: Domain) {
+        let tick = self.operations * OPERATION;
+        self.operations += 1;
+        let (channel, velocity, values) = match *event {
+            Event::Compare(i, j) => (COMPARE_CHANNEL, COMPARE_VELOCITY, vec![columns[i], columns[j]]),
+            Event::Swap(i, j) => (SWAP_CHANNEL, SWAP_VELOCITY, vec![columns[i], columns[j]]),
+            Event::Read(i) => (ACCESS_CHANNEL, ACCESS_VELOCITY, vec![columns[i]]),
+            Event::Write(_, value) => (ACCESS_CHANNEL, ACCESS_VELOCITY, vec![value]),
+            Event::AuxWrite(..) => return,
+        };
+        let mut notes: Vec<u8> = values.into_iter().map(|value| self.note(value, domain)).collect();
+        notes.dedup();
+        for note in notes {
+            self.played.push((tick, channel, note, velocity));
+        }
+    }
+
+    /// [Note]
+    /// The note number a value is played as.
+    fn note(&self, value: i32, domain: Domain) -> u8 {
+        let height = (value as i64 - domain.min as i64) as f64 / (domain.levels() - 1.0).max(1.0);
+        let [low, high] = self.notes;
+        self.scale.nearest(low + (high - low) * height.clamp(0.0, 1.0))
+    }
+
+    /// [Save MIDI]
+    /// Writes the score to a single track MIDI file. Every note is let
+    /// go just before the notes of the next operation are played, so
+    /// that the same note played twice in a row is heard twice. A track
+    /// which goes too long without a note, or is too long altogether,
+    /// can't be written, and is an error rather than a corrupt file.
+    pub fn save_midi(&self, path: &str) -> Result<(), String> {
+        // A quarter note is four operations long.
+        let tempo = (4_000_000.0 / self.operations_per_second).round().clamp(1.0, 0xFF_FFFF as f64) as u32;
+
+        let mut messages: Vec<(u64, Vec<u8>)> = vec![(0, [0xFF, 0x51, 0x03].into_iter().chain(tempo.to_be_bytes()[1..].iter().copied()).collect())];
+        for &(tick, channel, note, velocity) in &self.played {
+            messages.push((tick + OPERATION, vec![0x80 | channel, note, 0]));
+            messages.push((tick, vec![0x90 | channel, note, velocity]));
+        }
+        // Notes let go come before notes played at the same time.
+        messages.sort_by_key(|(tick, message)| (*tick, message[0] & 0xF0 == 0x90));
+        let end = messages.last().map_or(0, |(tick, _)| *tick);
+        messages.push((end, vec![0xFF, 0x2F, 0x00]));
+
+        let mut track = vec![];
+        let mut previous = 0;
+        for (tick, message) in messages {
+            let delta = tick - previous;
+            if delta > MOST_TICKS {
+                return Err("the score goes too long without a note for a MIDI file".to_string());
+            }
+            track.extend(variable_length(delta as u32));
+            track.extend(message);
+            previous = tick;
+        }
+
+        let length = u32::try_from(track.len()).map_err(|_| "the score is too long for a MIDI file")?;
+
+        let mut out = BufWriter::new(File::create(path).map_err(|error| error.to_string())?);
+        let mut header = vec![];
+        header.extend(b"MThd");
+        header.extend(6u32.to_be_bytes());
+        // Format 0, a single track.
+        header.extend(0u16.to_be_bytes());
+        header.extend(1u16.to_be_bytes());
+        header.extend(DIVISION.to_be_bytes());
+        header.extend(b"MTrk");
+        header.extend(length.to_be_bytes());
+        out.write_all(&header).and_then(|()| out.write_all(&track)).and_then(|()| out.flush()).map_err(|error| error.to_string())
+    }
+}
+
+/// [Variable Length]
+/// A number the way MIDI files write times, seven bits to a byte with
+/// the most significant first, and the top bit set on every byte but
+/// the last.
+fn variable_length(mut number: u32) -> Vec<u8> {
+    let mut bytes = vec![(number & 0x7F) as u8];
+    number >>= 7;
+    while number > 0 {
+        bytes.insert(0, (number & 0x7F) as u8 | 0x80);
+        number >>= 7;
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn midi_has_a_valid_header() {
+        let mut score = Score::new(&Options::default(), 100);
+        let domain = Domain::new(1, 3);
+        for event in [Event::Compare(0, 1), Event::Swap(1, 2), Event::Read(0), Event::Write(2, 1), Event::AuxWrite(0, 3)] {
+            score.operation(&event, &[1, 2, 3], domain);
+        }
+
+        let path = std::env::temp_dir().join(format!("sorting-score-{}.mid", std::process::id()));
+        score.save_midi(path.to_str().unwrap()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&bytes[..4], b"MThd");
+        assert_eq!(bytes[4..14], [0, 0, 0, 6, 0, 0, 0, 1, 0, DIVISION as u8]);
+        assert_eq!(&bytes[14..18], b"MTrk");
+        assert_eq!(u32::from_be_bytes(bytes[18..22].try_into().unwrap()) as usize, bytes.len() - 22);
+        assert_eq!(bytes[22..26], [0, 0xFF, 0x51, 0x03]);
+        // Four operations to a quarter note at 100 a second is 40ms.
+        assert_eq!(bytes[26..29], [0x00, 0x9C, 0x40]);
+        assert!(bytes.ends_with(&[0xFF, 0x2F, 0x00]));
+    }
+
+    #[test]
+    fn times_are_written_seven_bits_a_byte() {
+        assert_eq!(variable_length(0), [0x00]);
+        assert_eq!(variable_length(0x7F), [0x7F]);
+        assert_eq!(variable_length(0x80), [0x81, 0x00]);
+        assert_eq!(variable_length(MOST_TICKS as u32), [0xFF, 0xFF, 0xFF, 0x7F]);
+    }
+
+    #[test]
+    fn notes_span_the_widest_domain() {
+        let score = Score::new(&Options::default(), 100);
+        let domain = Domain::new(i32::MIN, i32::MAX);
+        assert!(score.note(i32::MIN, domain) < score.note(i32::MAX, domain));
+    }
+}
@@ -0,0 +1,185 @@
+/*****************************************************************/
+//! [Command Line Options]
+/*****************************************************************/
+//!
+//! Everything which can be set when the visualizer is started, such
+//! as the distribution the columns start out in, the seed they are
+//! generated from and the range of values they hold, along with
+//! running without a window to draw the sort to files instead, as
+//! numbered frames, an animation or a video, and to sound or notes.
+
+use crate::export::Palette;
+use crate::input::{Domain, Input, INPUTS};
+use crate::midi::Scale;
+use crate::sound::{Envelope, Waveform, SAMPLE_RATE};
+
+/// [Pace]
+/// How often a frame is drawn when running without a window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pace {
+    /// Every so many steps.
+    Steps(usize),
+    /// Whenever at least so many operations have been performed since
+    /// the last frame, however many steps they took.
+    Operations(usize),
+}
+
+/// [Options]
+/// The settings given on the command line. Anything left out keeps
+/// its usual behaviour.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub input: Option<Input>,
+    pub seed: Option<u64>,
+    pub show_shuffle: bool,
+    pub range: Option<Domain>,
+    pub algorithm: Option<String>,
+    pub columns: Option<i32>,
+    pub headless: bool,
+    pub frames: String,
+    pub pace: Pace,
+    pub size: Option<[u32; 2]>,
+    pub export: Option<String>,
+    pub delay: u32,
+    pub hold: u32,
+    pub palette: Palette,
+    pub fps: u32,
+    pub sound: Option<String>,
+    pub waveform: Waveform,
+    pub envelope: Envelope,
+    pub pitch: [u32; 2],
+    pub ops_per_sec: Option<u32>,
+    pub midi: Option<String>,
+    pub scale: Scale,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            input: None,
+            seed: None,
+            show_shuffle: false,
+            range: None,
+            algorithm: None,
+            columns: None,
+            headless: false,
+            frames: "frames".to_string(),
+            pace: Pace::Steps(1),
+            size: None,
+            export: None,
+            delay: 40,
+            hold: 2000,
+            palette: Palette::Adaptive,
+            fps: 30,
+            sound: None,
+            waveform: Waveform::Sine,
+            envelope: Envelope { attack: 5, release: 40 },
+            pitch: [120, 1200],
+            ops_per_sec: None,
+            midi: None,
+            scale: Scale::Chromatic,
+        }
+    }
+}
+
+impl Options {
+    /// [Parse]
+    /// Reads the options from the command line arguments, not including
+    /// the program name, or explains what was wrong with them.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--input" => {
+                    let names: Vec<&str> = INPUTS.iter().map(|input| input.name()).collect();
+                    let name = args.next().ok_or(format!("--input needs one of {}", names.join(", ")))?;
+                    let input = Input::from_name(&name).ok_or(format!("unknown input {}, expected one of {}", name, names.join(", ")))?;
+                    options.input = Some(input);
+                }
+                "--seed" => {
+                    let seed = args.next().ok_or("--seed needs a number")?;
+                    options.seed = Some(seed.parse().map_err(|_| format!("--seed needs a number, not {}", seed))?);
+                }
+                "--show-shuffle" => options.show_shuffle = true,
+                "--range" => {
+                    let range = args.next().ok_or("--range needs MIN..MAX")?;
+                    options.range = Some(Domain::parse(&range).ok_or(format!("--range needs MIN..MAX, not {}", range))?);
+                }
+                "--algorithm" => options.algorithm = Some(args.next().ok_or("--algorithm needs a name")?),
+                "--columns" => options.columns = Some(number(&arg, args.next())?),
+                "--headless" => options.headless = true,
+                "--frames" => options.frames = args.next().ok_or("--frames needs a directory")?,
+                "--every" => options.pace = Pace::Steps(number(&arg, args.next())?),
+                "--ops-per-frame" => options.pace = Pace::Operations(number(&arg, args.next())?),
+                "--fps" => options.fps = number(&arg, args.next())?,
+                "--size" => {
+                    let size = args.next().ok_or("--size needs WIDTHxHEIGHT")?;
+                    let parsed = size.split_once('x').and_then(|(width, height)| Some([width.parse().ok()?, height.parse().ok()?]));
+                    options.size = Some(parsed.filter(|&[width, height]| width > 0 && height > 0).ok_or(format!("--size needs WIDTHxHEIGHT, not {}", size))?);
+                }
+                "--export" => options.export = Some(args.next().ok_or("--export needs a file")?),
+                "--delay" => options.delay = number(&arg, args.next())?,
+                "--hold" => options.hold = number(&arg, args.next())?,
+                "--palette" => {
+                    let name = args.next().ok_or("--palette needs adaptive, web or grey")?;
+                    options.palette = Palette::from_name(&name).ok_or(format!("--palette needs adaptive, web or grey, not {}", name))?;
+                }
+                "--sound" => options.sound = Some(args.next().ok_or("--sound needs a file")?),
+                "--waveform" => {
+                    let name = args.next().ok_or("--waveform needs sine, triangle or square")?;
+                    options.waveform = Waveform::from_name(&name).ok_or(format!("--waveform needs sine, triangle or square, not {}", name))?;
+                }
+                "--envelope" => {
+                    let envelope = args.next().ok_or("--envelope needs ATTACK,RELEASE")?;
+                    options.envelope = Envelope::parse(&envelope).ok_or(format!("--envelope needs ATTACK,RELEASE, not {}", envelope))?;
+                }
+                "--pitch" => {
+                    let pitch = args.next().ok_or("--pitch needs LOW..HIGH")?;
+                    let parsed = pitch.split_once("..").and_then(|(low, high)| Some([low.trim().parse().ok()?, high.trim().parse().ok()?]));
+                    options.pitch = parsed.filter(|&[low, high]| low > 0 && low <= high).ok_or(format!("--pitch needs LOW..HIGH, not {}", pitch))?;
+                    if options.pitch[1] > SAMPLE_RATE / 2 {
+                        return Err(format!("--pitch can't go above {} hertz, half the sample rate, not {}", SAMPLE_RATE / 2, options.pitch[1]));
+                    }
+                }
+                "--ops-per-sec" => options.ops_per_sec = Some(number(&arg, args.next())?),
+                "--midi" => options.midi = Some(args.next().ok_or("--midi needs a file")?),
+                "--scale" => {
+                    let scales = "chromatic, major, minor, pentatonic, blues or whole-tone";
+                    let name = args.next().ok_or(format!("--scale needs {}", scales))?;
+                    options.scale = Scale::from_name(&name).ok_or(format!("--scale needs {}, not {}", scales, name))?;
+                }
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// [Number]
+/// The positive number following an option.
+fn number<T: std::str::FromStr + Default + PartialOrd>(option: &str, arg: Option<String>) -> Result<T, String> {
+    let text = arg.ok_or(format!("{} needs a number", option))?;
+    match text.parse() {
+        Ok(number) if number > T::default() => Ok(number),
+        _ => Err(format!("{} needs a positive number, not {}", option, text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// [Parse]
+    /// The options given by a command line of space separated words.
+    fn parse(line: &str) -> Result<Options, String> {
+        Options::parse(line.split(' ').map(str::to_string))
+    }
+
+    #[test]
+    fn pitches_stop_at_half_the_sample_rate() {
+        assert_eq!(parse("--pitch 100..22050").map(|options| options.pitch), Ok([100, 22050]));
+        assert!(parse("--pitch 100..22051").is_err());
+        assert!(parse("--pitch 500..100").is_err());
+    }
+}
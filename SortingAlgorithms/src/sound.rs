@@ -0,0 +1,307 @@
+/*****************************************************************/
+//! [Sonification]
+/*****************************************************************/
+//!
+//! Turns a sort into sound without needing any audio hardware. The
+//! operations are laid out one after another at a fixed rate, and
+//! every compare or swap plays a short tone for each value it
+//! touches, higher for larger values. Reads and writes play a tone
+//! too, since some algorithms, such as merge sorts, do almost all of
+//! their work with them. Writes to an algorithm's own buffer take
+//! their turn in silence, so that the sound keeps time with a video
+//! paced by operations. The tones are mixed together and written out
+//! as a WAV file, which can then be put together with an exported
+//! video. Only the tones still sounding are held in memory, and every
+//! sample before them is written out as soon as it is finished, so a
+//! sort can be sounded for as long as a WAV file can hold.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+use crate::algorithms::Event;
+use crate::input::Domain;
+use crate::options::Options;
+
+// Samples per second, the rate of a CD.
+pub const SAMPLE_RATE: u32 = 44100;
+
+// The most samples a WAV file can hold, since its sizes are 32 bits
+// and count the 36 bytes of header after the first size as well.
+const MOST_SAMPLES: usize = (u32::MAX as usize - 36) / 2;
+
+// How loud a single tone is, leaving room for tones which overlap.
+const VOLUME: f64 = 0.5;
+
+/// [Waveform]
+/// The shape of the wave a tone is made of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    /// A pure tone.
+    Sine,
+    /// Softer than a square, brighter than a sine.
+    Triangle,
+    /// The buzz of an old computer.
+    Square,
+}
+
+impl Waveform {
+    /// [From Name]
+    /// The waveform with the given name, if there is one.
+    pub fn from_name(name: &str) -> Option<Waveform> {
+        match name {
+            "sine" => Some(Waveform::Sine),
+            "triangle" => Some(Waveform::Triangle),
+            "square" => Some(Waveform::Square),
+            _ => None,
+        }
+    }
+
+    /// [Sample]
+    /// The height of the wave, from -1 to 1, the given number of cycles
+    /// after it starts.
+    fn sample(self, cycles: f64) -> f64 {
+        let phase = cycles.fract();
+        match self {
+            Waveform::Sine => (std::f64::consts::TAU * phase).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+        }
+    }
+}
+
+/// [Envelope]
+/// How a tone swells and fades, in milliseconds. It rises from silence
+/// to full volume over the attack, then falls away over the release.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Envelope {
+    pub attack: u32,
+    pub release: u32,
+}
+
+impl Envelope {
+    /// [Parse]
+    /// An envelope written as ATTACK,RELEASE, such as 5,40.
+    pub fn parse(text: &str) -> Option<Envelope> {
+        let (attack, release) = text.split_once(',')?;
+        let envelope = Envelope { attack: attack.trim().parse().ok()?, release: release.trim().parse().ok()? };
+        (envelope.attack + envelope.release > 0).then_some(envelope)
+    }
+
+    /// [Volume]
+    /// How loud a tone is, from 0 to 1, the given number of seconds
+    /// after it starts.
+    fn volume(self, time: f64) -> f64 {
+        let (attack, release) = (self.attack as f64 / 1000.0, self.release as f64 / 1000.0);
+        if time < attack {
+            time / attack
+        } else {
+            (1.0 - (time - attack) / release.max(f64::EPSILON)).max(0.0)
+        }
+    }
+
+    /// [Length]
+    /// How many seconds a tone lasts.
+    fn length(self) -> f64 {
+        (self.attack + self.release) as f64 / 1000.0
+    }
+}
+
+/// [Sonifier]
+/// The sound of a sort, written to a WAV file an operation at a time.
+/// Values are turned into pitches between the low and high frequency,
+/// spaced so that every step up the domain is the same musical
+/// interval. The window holds the samples from written onwards, which
+/// tones are still being mixed into.
+pub struct Sonifier {
+    waveform: Waveform,
+    envelope: Envelope,
+    pitch: [f64; 2],
+    operations_per_second: f64,
+    operations: usize,
+    window: VecDeque<f64>,
+    written: usize,
+    out: BufWriter<File>,
+}
+
+impl Sonifier {
+    /// [Create]
+    /// A silent WAV file at path, using the sound settings in the
+    /// options, which lays out operations at the given rate. The
+    /// header is left empty until the length is known.
+    pub fn create(path: &str, options: &Options, operations_per_second: u32) -> Result<Sonifier, String> {
+        let mut out = BufWriter::new(File::create(path).map_err(|error| error.to_string())?);
+        out.write_all(&[0; 44]).map_err(|error| error.to_string())?;
+        Ok(Sonifier {
+            waveform: options.waveform,
+            envelope: options.envelope,
+            pitch: options.pitch.map(|frequency| frequency as f64),
+            operations_per_second: operations_per_second as f64,
+            operations: 0,
+            window: VecDeque::new(),
+            written: 0,
+            out,
+        })
+    }
+
+    /// [Operation]
+    /// Adds the next operation, given the columns as they were just
+    /// before it, playing a tone for each value it touches. A write
+    /// sounds the value written. Tones never start before the ones
+    /// already added, so everything before this one is finished.
+    pub fn operation(&mut self, event: &Event, columns: &[i32], domain: Domain) -> Result<(), String> {
+        let start = self.operations as f64 / self.operations_per_second;
+        self.operations += 1;
+        self.flush((start * SAMPLE_RATE as f64).round() as usize)?;
+        let values = match *event {
+            Event::Compare(i, j) | Event::Swap(i, j) => vec![columns[i], columns[j]],
+            Event::Read(i) => vec![columns[i]],
+            Event::Write(_, value) => vec![value],
+            Event::AuxWrite(..) => vec![],
+        };
+        for value in values {
+            self.tone(start, self.frequency(value, domain));
+        }
+        Ok(())
+    }
+
+    /// [Frequency]
+    /// The pitch of a value in hertz.
+    fn frequency(&self, value: i32, domain: Domain) -> f64 {
+        let height = (value as i64 - domain.min as i64) as f64 / (domain.levels() - 1.0).max(1.0);
+        let [low, high] = self.pitch;
+        low * (high / low).powf(height.clamp(0.0, 1.0))
+    }
+
+    /// [Tone]
+    /// Mixes in one tone at the given pitch, starting at the given
+    /// number of seconds, which is no earlier than the window.
+    fn tone(&mut self, start: f64, frequency: f64) {
+        let first = (start * SAMPLE_RATE as f64).round() as usize - self.written;
+        let length = (self.envelope.length() * SAMPLE_RATE as f64).round() as usize;
+        if self.window.len() < first + length {
+            self.window.resize(first + length, 0.0);
+        }
+        for (k, sample) in self.window.range_mut(first..first + length).enumerate() {
+            let time = k as f64 / SAMPLE_RATE as f64;
+            *sample += VOLUME * self.envelope.volume(time) * self.waveform.sample(frequency * time);
+        }
+    }
+
+    /// [Flush]
+    /// Writes out every sample before until, which no tone will be
+    /// mixed into any more, padding with silence past the window.
+    /// Wherever so many tones overlap that they would be too loud, they
+    /// are softly squeezed into range rather than clipped, since the
+    /// loudest moment isn't known until the end. A WAV file can't hold
+    /// more than 4GB of sound, so a longer one is an error rather than
+    /// a file whose sizes have wrapped around.
+    fn flush(&mut self, until: usize) -> Result<(), String> {
+        if until > MOST_SAMPLES {
+            return Err("the sound is too long for a WAV file".to_string());
+        }
+        while self.written < until {
+            let sample = self.window.pop_front().unwrap_or(0.0).tanh();
+            let sample = (sample * i16::MAX as f64).round() as i16;
+            self.out.write_all(&sample.to_le_bytes()).map_err(|error| error.to_string())?;
+            self.written += 1;
+        }
+        Ok(())
+    }
+
+    /// [Finish]
+    /// Writes out the rest of the sound, lasting at least as long as
+    /// the operations, even when the last of them are silent, and then
+    /// fills in the header of the mono, 16 bit WAV file.
+    pub fn finish(mut self) -> Result<(), String> {
+        let length = (self.operations as f64 / self.operations_per_second * SAMPLE_RATE as f64).ceil() as usize;
+        self.flush(length.max(self.written + self.window.len()))?;
+        let data = self.written as u32 * 2;
+
+        let mut header = vec![];
+        header.extend(b"RIFF");
+        header.extend((36 + data).to_le_bytes());
+        header.extend(b"WAVEfmt ");
+        header.extend(16u32.to_le_bytes());
+        // Plain PCM, with one channel.
+        header.extend(1u16.to_le_bytes());
+        header.extend(1u16.to_le_bytes());
+        header.extend(SAMPLE_RATE.to_le_bytes());
+        header.extend((SAMPLE_RATE * 2).to_le_bytes());
+        // Two bytes per sample, of sixteen bits.
+        header.extend(2u16.to_le_bytes());
+        header.extend(16u16.to_le_bytes());
+        header.extend(b"data");
+        header.extend(data.to_le_bytes());
+        self.out.seek(SeekFrom::Start(0)).map_err(|error| error.to_string())?;
+        self.out.write_all(&header).map_err(|error| error.to_string())?;
+        self.out.flush().map_err(|error| error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// [Temporary]
+    /// A path for a WAV file which won't clash with another test's.
+    fn temporary(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("sorting-sound-{}-{}.wav", name, std::process::id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn wav_has_a_valid_header() {
+        let path = temporary("header");
+        let mut sonifier = Sonifier::create(&path, &Options::default(), 100).unwrap();
+        let domain = Domain::new(1, 3);
+        for event in [Event::Compare(0, 1), Event::Swap(1, 2), Event::Read(0), Event::Write(2, 1), Event::AuxWrite(0, 3)] {
+            sonifier.operation(&event, &[1, 2, 3], domain).unwrap();
+        }
+        sonifier.finish().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let number = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(number(4), bytes.len() - 8);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(number(24), SAMPLE_RATE as usize);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(number(40), bytes.len() - 44);
+        // Five operations at 100 a second last at least 50ms, even
+        // though the last of them is silent.
+        assert!(number(40) >= SAMPLE_RATE as usize / 20 * 2);
+    }
+
+    #[test]
+    fn only_sounding_tones_are_kept() {
+        let path = temporary("window");
+        let mut sonifier = Sonifier::create(&path, &Options::default(), 1000).unwrap();
+        let tone = (Options::default().envelope.length() * SAMPLE_RATE as f64).round() as usize;
+        let domain = Domain::new(1, 2);
+        for _ in 0..5000 {
+            sonifier.operation(&Event::Swap(0, 1), &[1, 2], domain).unwrap();
+            assert!(sonifier.window.len() <= tone);
+        }
+        sonifier.finish().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn pitches_span_the_widest_domain() {
+        let path = temporary("pitch");
+        let sonifier = Sonifier::create(&path, &Options::default(), 100).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let domain = Domain::new(i32::MIN, i32::MAX);
+        let [low, high] = sonifier.pitch;
+        assert_eq!(sonifier.frequency(i32::MIN, domain), low);
+        assert!((sonifier.frequency(i32::MAX, domain) - high).abs() < 1e-6);
+    }
+}